@@ -1,9 +1,13 @@
 use std::f32::consts::PI;
 
-use rand::{thread_rng, Rng};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
-use crate::math::{ray::Ray, vec3::Vec3};
+use crate::math::{
+    ray::{Ray, RayDifferentials},
+    sampler,
+    vec3::Vec3,
+};
 
 // camera manages the transformation between screen space and world space
 // it takes in camera location and orientation and image output dimensions,
@@ -56,19 +60,28 @@ impl Camera {
         }
     }
 
+    pub fn output_width(&self) -> u32 {
+        self.output_width
+    }
+
+    pub fn output_height(&self) -> u32 {
+        self.output_height
+    }
+
     pub fn ray_for_pixel(&self, pixel_x: u32, pixel_y: u32) -> Ray {
-        let mut rng = thread_rng();
+        let mut rng = sampler::rng();
         // QUESTION: is it okay to use the same two random values for two different purposes?
         let s: f32 = rng.gen();
         let t: f32 = rng.gen();
 
-        let target = {
-            // QUESTION: also, do we still need to put noise here if we're using defocus blur?
-            // normalized screen coords (-1 to 1)
-            let u = 2.0 * (pixel_x as f32 + s) / (self.output_width as f32) - 1.0;
-            // pixel_y traverses from top to bottom, so negate
-            let v = -(2.0 * (pixel_y as f32 + t) / (self.output_height as f32) - 1.0);
+        // QUESTION: also, do we still need to put noise here if we're using defocus blur?
+        // normalized screen coords (-1 to 1)
+        let u = 2.0 * (pixel_x as f32 + s) / (self.output_width as f32) - 1.0;
+        // pixel_y traverses from top to bottom, so negate
+        let v = -(2.0 * (pixel_y as f32 + t) / (self.output_height as f32) - 1.0);
 
+        // the point on the focus plane at some screen coords
+        let target = |u: f32, v: f32| {
             Vec3::lin_comb(vec![
                 (1.0, &self.position),
                 (1.0, &self.camera_forward),
@@ -89,7 +102,18 @@ impl Camera {
             (origin_offset_x, &self.camera_right.normalize()),
             (origin_offset_y, &self.camera_up.normalize()),
         ]);
-        let dir = (&target - &origin).normalize();
-        Ray { origin, dir }
+        let dir = (&target(u, v) - &origin).normalize();
+
+        // the neighboring pixels' rays go through the same spot on the lens
+        let pixel_width = 2.0 / self.output_width as f32;
+        let pixel_height = 2.0 / self.output_height as f32;
+        let differentials = RayDifferentials {
+            x_origin: origin.clone(),
+            x_dir: (&target(u + pixel_width, v) - &origin).normalize(),
+            y_origin: origin.clone(),
+            y_dir: (&target(u, v - pixel_height) - &origin).normalize(),
+        };
+
+        Ray::new(origin, dir).with_differentials(differentials)
     }
 }
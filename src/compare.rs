@@ -0,0 +1,561 @@
+use std::f32::consts::PI;
+
+use crate::math::{
+    color::{Color, ColorMatrix},
+    shaping::{clamp, lerp},
+    vec3::Vec3,
+};
+
+// Tools for quantifying the difference between two images, eg. two renders of the same scene
+// with different sampling settings. Everything here works on `ColorMatrix`, so in-memory renders
+// can be compared without ever writing them to disk.
+//
+// All of the metrics assume display-referred colors (ie. gamma corrected, in [0,1]), which is what
+// `render` produces.
+
+pub struct Comparison {
+    pub mse: f32,
+    // in decibels; infinite if the images are identical
+    pub psnr: f32,
+    pub ssim: f32,
+    // mean of the per-pixel FLIP-style error, in [0,1]
+    pub flip: f32,
+    flip_map: Channel,
+}
+
+impl Comparison {
+    // visualizes the per-pixel FLIP error, going from black (no difference)
+    // through purple and orange to pale yellow (maximal difference)
+    pub fn difference_image(&self) -> ColorMatrix {
+        let mut mat = ColorMatrix::new(self.flip_map.width, self.flip_map.height);
+        for row in 0..self.flip_map.height {
+            for col in 0..self.flip_map.width {
+                *mat.at_mut(row, col) = false_color(self.flip_map.at(row, col));
+            }
+        }
+        mat
+    }
+}
+
+// panics if the two images don't have the same dimensions
+pub fn compare(reference: &ColorMatrix, test: &ColorMatrix) -> Comparison {
+    let flip_map = flip_error_map(reference, test);
+    let flip = flip_map.mean();
+    let mse = mse(reference, test);
+
+    Comparison {
+        mse,
+        psnr: psnr_from_mse(mse),
+        ssim: ssim(reference, test),
+        flip,
+        flip_map,
+    }
+}
+
+// mean squared error, averaged over all pixels and all three channels
+pub fn mse(reference: &ColorMatrix, test: &ColorMatrix) -> f32 {
+    assert_same_dimensions(reference, test);
+
+    let mut sum = 0.0;
+    for row in 0..reference.height() {
+        for col in 0..reference.width() {
+            let (a, b) = (reference.at(row, col), test.at(row, col));
+            sum += (a.r() - b.r()).powi(2) + (a.g() - b.g()).powi(2) + (a.b() - b.b()).powi(2);
+        }
+    }
+
+    sum / (3 * reference.width() * reference.height()) as f32
+}
+
+// peak signal-to-noise ratio, using 1.0 as the peak value
+pub fn psnr(reference: &ColorMatrix, test: &ColorMatrix) -> f32 {
+    psnr_from_mse(mse(reference, test))
+}
+
+fn psnr_from_mse(mse: f32) -> f32 {
+    if mse == 0.0 {
+        f32::INFINITY
+    } else {
+        -10.0 * mse.log10()
+    }
+}
+
+// mean structural similarity of the luminance of the two images, using the usual
+// gaussian window with std. deviation 1.5 pixels
+pub fn ssim(reference: &ColorMatrix, test: &ColorMatrix) -> f32 {
+    assert_same_dimensions(reference, test);
+
+    const C1: f32 = 0.01 * 0.01;
+    const C2: f32 = 0.03 * 0.03;
+
+    let x = Channel::from_fn(reference.width(), reference.height(), |row, col| {
+        luma(reference.at(row, col))
+    });
+    let y = Channel::from_fn(test.width(), test.height(), |row, col| {
+        luma(test.at(row, col))
+    });
+
+    let window = gaussian_kernel(1.5);
+    let mean_x = x.blur(&window);
+    let mean_y = y.blur(&window);
+    let mean_xx = x.zip(&x, |a, b| a * b).blur(&window);
+    let mean_yy = y.zip(&y, |a, b| a * b).blur(&window);
+    let mean_xy = x.zip(&y, |a, b| a * b).blur(&window);
+
+    let mut sum = 0.0;
+    for i in 0..x.data.len() {
+        let (mx, my) = (mean_x.data[i], mean_y.data[i]);
+        let var_x = mean_xx.data[i] - mx * mx;
+        let var_y = mean_yy.data[i] - my * my;
+        let cov = mean_xy.data[i] - mx * my;
+
+        sum += ((2.0 * mx * my + C1) * (2.0 * cov + C2))
+            / ((mx * mx + my * my + C1) * (var_x + var_y + C2));
+    }
+
+    sum / x.data.len() as f32
+}
+
+// the mean of the per-pixel FLIP-style error
+pub fn flip(reference: &ColorMatrix, test: &ColorMatrix) -> f32 {
+    flip_error_map(reference, test).mean()
+}
+
+// A simplified take on NVIDIA's FLIP metric (Andersson et al. 2020): both images are filtered with
+// an approximation of the contrast sensitivity of the human eye, then compared in a perceptually
+// uniform color space. That color error is then amplified in places where edges or points differ
+// between the two images.
+//
+// The viewing conditions are fixed to FLIP's defaults (0.7m away from a 0.7m wide 4k monitor).
+fn flip_error_map(reference: &ColorMatrix, test: &ColorMatrix) -> Channel {
+    assert_same_dimensions(reference, test);
+
+    const PIXELS_PER_DEGREE: f32 = 67.0;
+    const COLOR_EXPONENT: f32 = 0.7;
+    const COLOR_CUTOFF: f32 = 0.4;
+    const COLOR_CUTOFF_ERROR: f32 = 0.95;
+    const FEATURE_EXPONENT: f32 = 0.5;
+
+    let filtered_ref = csf_filtered_lab(reference, PIXELS_PER_DEGREE);
+    let filtered_test = csf_filtered_lab(test, PIXELS_PER_DEGREE);
+
+    // the largest possible color difference is the one between green and blue
+    let max_color_error = hyab(
+        &hunt_adjust(&linear_rgb_to_lab(&Vec3::new(0.0, 1.0, 0.0))),
+        &hunt_adjust(&linear_rgb_to_lab(&Vec3::new(0.0, 0.0, 1.0))),
+    )
+    .powf(COLOR_EXPONENT);
+
+    let feature_sigma = 0.5 * 0.082 * PIXELS_PER_DEGREE;
+    let features_ref = Features::detect(reference, feature_sigma);
+    let features_test = Features::detect(test, feature_sigma);
+
+    Channel::from_fn(reference.width(), reference.height(), |row, col| {
+        let i = row * reference.width() + col;
+
+        // remap the color error so that small differences take up most of the range
+        let color_error = hyab(&filtered_ref[i], &filtered_test[i]).powf(COLOR_EXPONENT);
+        let cutoff = COLOR_CUTOFF * max_color_error;
+        let color_error = if color_error < cutoff {
+            color_error * COLOR_CUTOFF_ERROR / cutoff
+        } else {
+            COLOR_CUTOFF_ERROR
+                + (color_error - cutoff) / (max_color_error - cutoff) * (1.0 - COLOR_CUTOFF_ERROR)
+        };
+
+        let feature_error = (features_ref.edges.data[i] - features_test.edges.data[i])
+            .abs()
+            .max((features_ref.points.data[i] - features_test.points.data[i]).abs());
+        let feature_error = (feature_error / 2.0f32.sqrt()).powf(FEATURE_EXPONENT);
+
+        clamp(color_error, 0.0, 1.0).powf(1.0 - clamp(feature_error, 0.0, 1.0))
+    })
+}
+
+// spatially filters an image in the opponent YCxCz color space, then returns the
+// (hunt-adjusted) Lab value of every pixel, in row major order
+fn csf_filtered_lab(mat: &ColorMatrix, pixels_per_degree: f32) -> Vec<Vec3> {
+    let (width, height) = (mat.width(), mat.height());
+    let ycxcz: Vec<Vec3> = (0..height)
+        .flat_map(|row| (0..width).map(move |col| (row, col)))
+        .map(|(row, col)| xyz_to_ycxcz(&linear_rgb_to_xyz(&srgb_to_linear(mat.at(row, col)))))
+        .collect();
+
+    // each channel is filtered with a sum of gaussians: (weight, spread) pairs
+    // (these are the parameters from the FLIP paper, in units of degrees)
+    let csf: [&[(f32, f32)]; 3] = [
+        &[(1.0, 0.0047)],
+        &[(1.0, 0.0053)],
+        &[(34.1, 0.04), (13.5, 0.025)],
+    ];
+
+    let channels: Vec<Channel> = csf
+        .iter()
+        .enumerate()
+        .map(|(index, terms)| {
+            let channel = Channel::from_fn(width, height, |row, col| {
+                component(&ycxcz[row * width + col], index)
+            });
+            let total_weight: f32 = terms.iter().map(|(weight, _)| weight).sum();
+
+            terms
+                .iter()
+                .map(|(weight, spread)| {
+                    let sigma = (spread / (2.0 * PI * PI)).sqrt() * pixels_per_degree;
+                    channel
+                        .blur(&gaussian_kernel(sigma))
+                        .map(|v| v * weight / total_weight)
+                })
+                .reduce(|a, b| a.zip(&b, |x, y| x + y))
+                .unwrap()
+        })
+        .collect();
+
+    (0..width * height)
+        .map(|i| {
+            let filtered = Vec3::new(
+                channels[0].data[i],
+                channels[1].data[i],
+                channels[2].data[i],
+            );
+            let rgb = xyz_to_linear_rgb(&ycxcz_to_xyz(&filtered));
+            let rgb = Vec3::new(
+                clamp(rgb.x, 0.0, 1.0),
+                clamp(rgb.y, 0.0, 1.0),
+                clamp(rgb.z, 0.0, 1.0),
+            );
+            hunt_adjust(&linear_rgb_to_lab(&rgb))
+        })
+        .collect()
+}
+
+// magnitudes of the edge (first derivative) and point (second derivative) responses
+// of the normalized lightness of an image
+struct Features {
+    edges: Channel,
+    points: Channel,
+}
+
+impl Features {
+    fn detect(mat: &ColorMatrix, sigma: f32) -> Features {
+        let lightness = Channel::from_fn(mat.width(), mat.height(), |row, col| {
+            linear_rgb_to_lab(&srgb_to_linear(mat.at(row, col))).x / 100.0
+        });
+
+        let radius = (3.0 * sigma).ceil() as i32;
+        let offsets = || (-radius..=radius).map(|x| x as f32);
+
+        let gaussian = gaussian_kernel(sigma);
+        let first_derivative = normalize_signed(
+            offsets()
+                .map(|x| -x * (-x * x / (2.0 * sigma * sigma)).exp())
+                .collect(),
+        );
+        let second_derivative = normalize_signed(
+            offsets()
+                .map(|x| (x * x / (sigma * sigma) - 1.0) * (-x * x / (2.0 * sigma * sigma)).exp())
+                .collect(),
+        );
+
+        let magnitude = |derivative: &[f32]| {
+            let dx = lightness.convolve(derivative, &gaussian);
+            let dy = lightness.convolve(&gaussian, derivative);
+            dx.zip(&dy, |a, b| (a * a + b * b).sqrt())
+        };
+
+        Features {
+            edges: magnitude(&first_derivative),
+            points: magnitude(&second_derivative),
+        }
+    }
+}
+
+// a single channel image, stored in row major order
+struct Channel {
+    width: usize,
+    height: usize,
+    data: Vec<f32>,
+}
+
+impl Channel {
+    fn from_fn(width: usize, height: usize, f: impl Fn(usize, usize) -> f32) -> Channel {
+        let data = (0..height)
+            .flat_map(|row| (0..width).map(move |col| (row, col)))
+            .map(|(row, col)| f(row, col))
+            .collect();
+        Channel {
+            width,
+            height,
+            data,
+        }
+    }
+
+    fn at(&self, row: usize, column: usize) -> f32 {
+        self.data[row * self.width + column]
+    }
+
+    fn mean(&self) -> f32 {
+        self.data.iter().sum::<f32>() / self.data.len() as f32
+    }
+
+    fn map(&self, f: impl Fn(f32) -> f32) -> Channel {
+        Channel::from_fn(self.width, self.height, |row, col| f(self.at(row, col)))
+    }
+
+    fn zip(&self, other: &Channel, f: impl Fn(f32, f32) -> f32) -> Channel {
+        Channel::from_fn(self.width, self.height, |row, col| {
+            f(self.at(row, col), other.at(row, col))
+        })
+    }
+
+    fn blur(&self, kernel: &[f32]) -> Channel {
+        self.convolve(kernel, kernel)
+    }
+
+    // separable convolution with odd-length kernels, clamping at the image borders
+    fn convolve(&self, horizontal: &[f32], vertical: &[f32]) -> Channel {
+        let clamped = |index: i32, len: usize| index.clamp(0, len as i32 - 1) as usize;
+
+        let h_radius = (horizontal.len() / 2) as i32;
+        let pass = Channel::from_fn(self.width, self.height, |row, col| {
+            horizontal
+                .iter()
+                .enumerate()
+                .map(|(k, weight)| {
+                    let c = clamped(col as i32 + k as i32 - h_radius, self.width);
+                    weight * self.at(row, c)
+                })
+                .sum()
+        });
+
+        let v_radius = (vertical.len() / 2) as i32;
+        Channel::from_fn(self.width, self.height, |row, col| {
+            vertical
+                .iter()
+                .enumerate()
+                .map(|(k, weight)| {
+                    let r = clamped(row as i32 + k as i32 - v_radius, self.height);
+                    weight * pass.at(r, col)
+                })
+                .sum()
+        })
+    }
+}
+
+// a normalized gaussian kernel spanning three standard deviations in each direction
+fn gaussian_kernel(sigma: f32) -> Vec<f32> {
+    let radius = (3.0 * sigma).ceil().max(1.0) as i32;
+    let kernel: Vec<f32> = (-radius..=radius)
+        .map(|x| (-(x * x) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect();
+    let total: f32 = kernel.iter().sum();
+    kernel.into_iter().map(|w| w / total).collect()
+}
+
+// scales the positive and negative weights of a kernel so they sum to 1 and -1, respectively
+fn normalize_signed(kernel: Vec<f32>) -> Vec<f32> {
+    let positive: f32 = kernel.iter().filter(|w| **w > 0.0).sum();
+    let negative: f32 = -kernel.iter().filter(|w| **w < 0.0).sum::<f32>();
+    kernel
+        .into_iter()
+        .map(|w| if w > 0.0 { w / positive } else { w / negative })
+        .collect()
+}
+
+fn assert_same_dimensions(a: &ColorMatrix, b: &ColorMatrix) {
+    assert!(
+        a.width() == b.width() && a.height() == b.height(),
+        "cannot compare a {}x{} image with a {}x{} image",
+        a.width(),
+        a.height(),
+        b.width(),
+        b.height()
+    );
+}
+
+fn component(v: &Vec3, index: usize) -> f32 {
+    match index {
+        0 => v.x,
+        1 => v.y,
+        _ => v.z,
+    }
+}
+
+fn luma(color: &Color) -> f32 {
+    0.2126 * color.r() + 0.7152 * color.g() + 0.0722 * color.b()
+}
+
+//
+// color space conversions
+//
+
+const D65_WHITE: Vec3 = Vec3 {
+    x: 0.950456,
+    y: 1.0,
+    z: 1.088754,
+};
+
+fn srgb_to_linear(color: &Color) -> Vec3 {
+    let f = |c: f32| {
+        if c <= 0.04045 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    Vec3::new(f(color.r()), f(color.g()), f(color.b()))
+}
+
+fn linear_rgb_to_xyz(rgb: &Vec3) -> Vec3 {
+    Vec3::new(
+        0.4124564 * rgb.x + 0.3575761 * rgb.y + 0.1804375 * rgb.z,
+        0.2126729 * rgb.x + 0.7151522 * rgb.y + 0.072175 * rgb.z,
+        0.0193339 * rgb.x + 0.119192 * rgb.y + 0.9503041 * rgb.z,
+    )
+}
+
+fn xyz_to_linear_rgb(xyz: &Vec3) -> Vec3 {
+    Vec3::new(
+        3.2404542 * xyz.x - 1.5371385 * xyz.y - 0.4985314 * xyz.z,
+        -0.969266 * xyz.x + 1.8760108 * xyz.y + 0.041556 * xyz.z,
+        0.0556434 * xyz.x - 0.2040259 * xyz.y + 1.0572252 * xyz.z,
+    )
+}
+
+fn xyz_to_ycxcz(xyz: &Vec3) -> Vec3 {
+    let (x, y, z) = (
+        xyz.x / D65_WHITE.x,
+        xyz.y / D65_WHITE.y,
+        xyz.z / D65_WHITE.z,
+    );
+    Vec3::new(116.0 * y - 16.0, 500.0 * (x - y), 200.0 * (y - z))
+}
+
+fn ycxcz_to_xyz(ycxcz: &Vec3) -> Vec3 {
+    let y = (ycxcz.x + 16.0) / 116.0;
+    let x = y + ycxcz.y / 500.0;
+    let z = y - ycxcz.z / 200.0;
+    Vec3::new(x * D65_WHITE.x, y * D65_WHITE.y, z * D65_WHITE.z)
+}
+
+fn linear_rgb_to_lab(rgb: &Vec3) -> Vec3 {
+    let xyz = linear_rgb_to_xyz(rgb);
+    let f = |t: f32| {
+        let delta: f32 = 6.0 / 29.0;
+        if t > delta.powi(3) {
+            t.cbrt()
+        } else {
+            t / (3.0 * delta * delta) + 4.0 / 29.0
+        }
+    };
+    let (fx, fy, fz) = (
+        f(xyz.x / D65_WHITE.x),
+        f(xyz.y / D65_WHITE.y),
+        f(xyz.z / D65_WHITE.z),
+    );
+    Vec3::new(116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+}
+
+// the hunt effect: colorfulness appears lower at lower lightness
+fn hunt_adjust(lab: &Vec3) -> Vec3 {
+    Vec3::new(lab.x, 0.01 * lab.x * lab.y, 0.01 * lab.x * lab.z)
+}
+
+// a color distance that behaves better than euclidean distance for large differences
+fn hyab(lab1: &Vec3, lab2: &Vec3) -> f32 {
+    let diff = lab1 - lab2;
+    diff.x.abs() + (diff.y * diff.y + diff.z * diff.z).sqrt()
+}
+
+// a rough approximation of matplotlib's 'magma' color map
+fn false_color(value: f32) -> Color {
+    const STOPS: [(u8, u8, u8); 6] = [
+        (0, 0, 4),
+        (59, 15, 112),
+        (140, 41, 129),
+        (222, 73, 104),
+        (254, 159, 109),
+        (252, 253, 191),
+    ];
+
+    let scaled = clamp(value, 0.0, 1.0) * (STOPS.len() - 1) as f32;
+    let index = (scaled.floor() as usize).min(STOPS.len() - 2);
+    let (r0, g0, b0) = STOPS[index];
+    let (r1, g1, b1) = STOPS[index + 1];
+
+    lerp(
+        scaled - index as f32,
+        &Color::from_rgb_u8(r0, g0, b0),
+        &Color::from_rgb_u8(r1, g1, b1),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn checkerboard(width: usize, height: usize, dark: f32, light: f32) -> ColorMatrix {
+        let mut mat = ColorMatrix::new(width, height);
+        for row in 0..height {
+            for col in 0..width {
+                let value = if (row / 2 + col / 2) % 2 == 0 {
+                    dark
+                } else {
+                    light
+                };
+                *mat.at_mut(row, col) = Color::from_rgb_f32(value, value, value);
+            }
+        }
+        mat
+    }
+
+    #[test]
+    fn identical_images_have_no_error() {
+        let img = checkerboard(8, 6, 0.1, 0.9);
+        let comparison = compare(&img, &img);
+
+        assert_eq!(comparison.mse, 0.0);
+        assert_eq!(comparison.psnr, f32::INFINITY);
+        assert!((comparison.ssim - 1.0).abs() < 1e-4);
+        assert!(comparison.flip < 1e-4);
+    }
+
+    #[test]
+    fn black_vs_white() {
+        let black = checkerboard(4, 4, 0.0, 0.0);
+        let white = checkerboard(4, 4, 1.0, 1.0);
+
+        assert_eq!(mse(&black, &white), 1.0);
+        assert_eq!(psnr(&black, &white), 0.0);
+        assert!(flip(&black, &white) > 0.9);
+    }
+
+    #[test]
+    fn larger_differences_give_larger_errors() {
+        let reference = checkerboard(8, 8, 0.2, 0.8);
+        let close = checkerboard(8, 8, 0.25, 0.75);
+        let far = checkerboard(8, 8, 0.5, 0.5);
+
+        assert!(mse(&reference, &close) < mse(&reference, &far));
+        assert!(psnr(&reference, &close) > psnr(&reference, &far));
+        assert!(ssim(&reference, &close) > ssim(&reference, &far));
+        assert!(flip(&reference, &close) < flip(&reference, &far));
+    }
+
+    #[test]
+    fn difference_image_matches_dimensions() {
+        let a = checkerboard(5, 3, 0.0, 1.0);
+        let b = checkerboard(5, 3, 1.0, 0.0);
+        let diff = compare(&a, &b).difference_image();
+
+        assert_eq!(diff.width(), 5);
+        assert_eq!(diff.height(), 3);
+        assert_ne!(*diff.at(0, 0), Color::from_rgb_u8(0, 0, 4));
+    }
+
+    #[test]
+    #[should_panic]
+    fn cannot_compare_different_dimensions() {
+        mse(&ColorMatrix::new(2, 2), &ColorMatrix::new(3, 2));
+    }
+}
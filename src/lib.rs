@@ -1,4 +1,5 @@
 pub mod camera;
+pub mod compare;
 pub mod math;
 pub mod render;
 pub mod scene;
@@ -1,20 +1,43 @@
-use std::fs;
+use std::{
+    env, fs,
+    path::PathBuf,
+    process,
+    str::FromStr,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
 
+use image::{ImageBuffer, Rgb};
 use pixels::{Error, Pixels, SurfaceTexture};
 use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use rays::{
     camera::Camera,
-    math::{color::Color, vec3::Vec3},
-    render::render,
+    compare::compare,
+    math::{
+        color::{Color, ColorMatrix},
+        vec3::Vec3,
+    },
+    render::{
+        checkpoint::{Checkpoint, CheckpointSchedule},
+        progress::{format_duration, CancellationToken, RenderObserver, RenderProgress},
+        render_with_progress, resume_render, RenderSettings,
+    },
     scene::{
         object::{
-            geometry::{plane::Plane, sphere::Sphere},
+            geometry::{csg::Difference, plane::Plane, sphere::Sphere},
             material::{
-                lambertian::Lambertian, metal::Metal, translucent::Translucent, ScatterRay,
+                conductor::{ComplexIor, Conductor},
+                lambertian::Lambertian,
+                metal::Metal,
+                principled::Principled,
+                translucent::Translucent,
             },
-            Object,
+            texture::{checkerboard::Checkerboard, procedural::Marble, Textured},
+            Material, Object,
         },
+        registry::Registry,
         sky::Sky,
         Scene, SceneBuilder,
     },
@@ -22,39 +45,258 @@ use rays::{
 use winit::{
     dpi::{LogicalSize, PhysicalPosition},
     event::{Event, VirtualKeyCode, WindowEvent},
-    event_loop::{ControlFlow, EventLoop},
+    event_loop::{ControlFlow, EventLoopBuilder},
     window::WindowBuilder,
 };
 
 fn main() -> Result<(), Error> {
-    // scene setup
-    let scene = make_initial_test_scene().build().unwrap();
+    let args: Vec<String> = env::args().skip(1).collect();
 
-    // _write_to_yaml(&scene, "test_scene.yaml");
+    let result = match args.first().map(String::as_str) {
+        Some("render") => render_to_file(&args[1..]),
+        Some("compare") => compare_images(&args[1..]),
+        _ => return preview(),
+    };
 
-    // render
-    let output_width = 800;
-    let output_height = 500;
-    let samples_per_pixel = 1;
-    let bounce_depth = 10;
+    if let Err(message) = result {
+        eprintln!("error: {message}");
+        process::exit(1);
+    }
+    Ok(())
+}
 
-    let color_matrix = render(
-        scene,
-        output_width,
-        output_height,
+const RENDER_USAGE: &str = "usage: rays render [--scene <scene.yaml>] [--output <image>] \
+[--samples <n>] [--depth <n>] [--seed <n>] [--time-limit <seconds>] \
+[--checkpoint <file>] [--checkpoint-interval <seconds>] [--resume <file>] \
+[--stats-json <file>] [--clamp <max sample value>]";
+
+// Renders are resumed (or extended with more samples) by passing the checkpoint file to
+// `--resume`, along with the same scene and depth as the original render. The checkpoint
+// keeps being updated in place unless `--checkpoint` says otherwise.
+
+fn render_to_file(args: &[String]) -> Result<(), String> {
+    let mut scene_path = None;
+    let mut output_path = "render.png".to_string();
+    let mut samples_per_pixel = 10;
+    let mut bounce_depth = 10;
+    let mut seed = 0;
+    let mut time_budget = None;
+    let mut checkpoint_path = None;
+    let mut checkpoint_interval = 60.0;
+    let mut resume_path = None;
+    let mut stats_path = None;
+    let mut sample_clamp = None;
+
+    let mut args = args.iter();
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .ok_or_else(|| format!("missing value for {flag}\n{RENDER_USAGE}"))?;
+        match flag.as_str() {
+            "--scene" => scene_path = Some(value.clone()),
+            "--output" => output_path = value.clone(),
+            "--samples" => samples_per_pixel = parse_value(flag, value)?,
+            "--depth" => bounce_depth = parse_value(flag, value)?,
+            "--seed" => seed = parse_value(flag, value)?,
+            "--time-limit" => {
+                time_budget = Some(Duration::from_secs_f32(parse_value(flag, value)?))
+            }
+            "--checkpoint" => checkpoint_path = Some(PathBuf::from(value)),
+            "--checkpoint-interval" => checkpoint_interval = parse_value(flag, value)?,
+            "--resume" => resume_path = Some(PathBuf::from(value)),
+            "--stats-json" => stats_path = Some(value.clone()),
+            "--clamp" => sample_clamp = Some(parse_value(flag, value)?),
+            _ => return Err(format!("unknown option {flag}\n{RENDER_USAGE}")),
+        }
+    }
+
+    let scene = match scene_path {
+        Some(path) => read_from_yaml(&path)?,
+        None => make_initial_test_scene().build().unwrap(),
+    };
+
+    let mut settings = RenderSettings::new(
+        scene.camera.output_width(),
+        scene.camera.output_height(),
         samples_per_pixel,
         bounce_depth,
     );
+    settings.seed = seed;
+    settings.time_budget = time_budget;
+    settings.sample_clamp = sample_clamp;
+
+    let mut checkpoint = match &resume_path {
+        Some(path) => {
+            let checkpoint = Checkpoint::load(path)
+                .map_err(|err| format!("could not read {}: {err}", path.display()))?;
+            if checkpoint.width() != settings.output_width
+                || checkpoint.height() != settings.output_height
+            {
+                return Err(format!(
+                    "{} is a {}x{} render, but the scene is {}x{}",
+                    path.display(),
+                    checkpoint.width(),
+                    checkpoint.height(),
+                    settings.output_width,
+                    settings.output_height
+                ));
+            }
+            eprintln!(
+                "resuming from {} samples per pixel",
+                checkpoint.samples_per_pixel()
+            );
+            checkpoint
+        }
+        None => Checkpoint::new(seed, settings.output_width, settings.output_height),
+    };
+
+    settings.checkpoint = checkpoint_path
+        .or(resume_path)
+        .map(|path| CheckpointSchedule {
+            path,
+            interval: Duration::from_secs_f32(checkpoint_interval),
+        });
+
+    let mut progress_bar = ProgressBar::default();
+    let stats = resume_render(
+        &scene,
+        &settings,
+        &mut checkpoint,
+        &mut progress_bar,
+        &CancellationToken::new(),
+    );
+    progress_bar.finish();
+    let color_matrix = checkpoint.image();
+
+    eprintln!("{stats}");
+    if let Some(stats_path) = stats_path {
+        let json = serde_json::to_string_pretty(&stats).unwrap();
+        fs::write(&stats_path, json)
+            .map_err(|err| format!("could not write {stats_path}: {err}"))?;
+    }
+
+    let img_buffer: ImageBuffer<Rgb<u8>, Vec<u8>> = color_matrix.into();
+    img_buffer
+        .save(&output_path)
+        .map_err(|err| format!("could not write {output_path}: {err}"))?;
+    println!("wrote render to {output_path}");
+
+    Ok(())
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value for {flag}: {value}"))
+}
 
-    // write to output
-    // let img_buffer: ImageBuffer<Rgb<u8>, Vec<u8>> = color_matrix.into();
-    // img_buffer.save("target/debug/img_out/render.png").unwrap();
+// draws a progress bar to stderr, at most ten times a second
+#[derive(Default)]
+struct ProgressBar {
+    last_drawn: Option<Instant>,
+    last_progress: Option<RenderProgress>,
+}
+
+impl ProgressBar {
+    const WIDTH: usize = 30;
+
+    fn draw(&self, progress: &RenderProgress) {
+        let filled = (progress.fraction_complete() * ProgressBar::WIDTH as f32) as usize;
+        eprint!(
+            "\r[{}{}] {progress}  ",
+            "#".repeat(filled),
+            "-".repeat(ProgressBar::WIDTH - filled)
+        );
+    }
+
+    fn finish(&self) {
+        if let Some(progress) = &self.last_progress {
+            self.draw(progress);
+            eprintln!();
+            eprintln!(
+                "rendered {} samples in {}",
+                progress.samples_completed,
+                format_duration(progress.elapsed)
+            );
+        }
+    }
+}
+
+impl RenderObserver for ProgressBar {
+    fn on_progress(&mut self, progress: &RenderProgress) {
+        self.last_progress = Some(progress.clone());
+
+        let redraw_interval = Duration::from_millis(100);
+        if self
+            .last_drawn
+            .is_none_or(|t| t.elapsed() >= redraw_interval)
+        {
+            self.last_drawn = Some(Instant::now());
+            self.draw(progress);
+        }
+    }
+}
+
+// usage: rays compare <reference> <test> [--diff <output>]
+fn compare_images(args: &[String]) -> Result<(), String> {
+    let (reference_path, test_path, diff_path) = match args {
+        [reference, test] => (reference, test, "diff.png"),
+        [reference, test, flag, diff] if flag == "--diff" => (reference, test, diff.as_str()),
+        _ => return Err("usage: rays compare <reference> <test> [--diff <output>]".to_string()),
+    };
+
+    let load = |path: &str| {
+        image::open(path)
+            .map(|img| ColorMatrix::from(&img))
+            .map_err(|err| format!("could not read {path}: {err}"))
+    };
+    let reference = load(reference_path)?;
+    let test = load(test_path)?;
+
+    if reference.width() != test.width() || reference.height() != test.height() {
+        return Err(format!(
+            "image dimensions differ ({}x{} vs {}x{})",
+            reference.width(),
+            reference.height(),
+            test.width(),
+            test.height()
+        ));
+    }
+
+    let comparison = compare(&reference, &test);
+    println!("MSE:  {:.6}", comparison.mse);
+    println!("PSNR: {:.2} dB", comparison.psnr);
+    println!("SSIM: {:.4}", comparison.ssim);
+    println!("FLIP: {:.4}", comparison.flip);
+
+    let img_buffer: ImageBuffer<Rgb<u8>, Vec<u8>> = comparison.difference_image().into();
+    img_buffer
+        .save(diff_path)
+        .map_err(|err| format!("could not write {diff_path}: {err}"))?;
+    println!("wrote difference image to {diff_path}");
+
+    Ok(())
+}
+
+enum PreviewEvent {
+    Progress(RenderProgress),
+    Finished(ColorMatrix),
+}
+
+fn preview() -> Result<(), Error> {
+    // render settings
+    let output_width = 800;
+    let output_height = 500;
+    let samples_per_pixel = 1;
+    let bounce_depth = 10;
+    let settings =
+        RenderSettings::new(output_width, output_height, samples_per_pixel, bounce_depth);
 
     // winit stuff
     // vvvvvvvvvvv
     env_logger::init();
 
-    let event_loop = EventLoop::new();
+    let event_loop = EventLoopBuilder::<PreviewEvent>::with_user_event().build();
 
     let window = {
         let size = LogicalSize::new(output_width as f64, output_height as f64);
@@ -73,36 +315,87 @@ fn main() -> Result<(), Error> {
         Pixels::new(output_width, output_height, surface_texture)?
     };
 
+    // render on a separate thread so that the window stays responsive,
+    // reporting progress back to the event loop
+    let cancel = CancellationToken::new();
+    {
+        let proxy = event_loop.create_proxy();
+        let cancel = cancel.clone();
+
+        thread::spawn(move || {
+            // scene setup
+            let scene = make_initial_test_scene().build().unwrap();
+
+            // _write_to_yaml(&scene, "test_scene.yaml");
+
+            let mut last_sent: Option<Instant> = None;
+            let mut observer = |progress: &RenderProgress| {
+                if last_sent.is_none_or(|t| t.elapsed() >= Duration::from_millis(100)) {
+                    last_sent = Some(Instant::now());
+                    // the event loop might already be gone if the window was closed
+                    let _ = proxy.send_event(PreviewEvent::Progress(progress.clone()));
+                }
+            };
+            let color_matrix = render_with_progress(&scene, &settings, &mut observer, &cancel);
+
+            let _ = proxy.send_event(PreviewEvent::Finished(color_matrix));
+        });
+    }
+
+    let mut color_matrix: Option<ColorMatrix> = None;
+    let mut last_progress: Option<RenderProgress> = None;
+
     event_loop.run(move |event, _, control_flow| {
         match event {
             Event::WindowEvent {
                 window_id,
                 event: window_event,
             } if window_id == window.id() => match window_event {
-                WindowEvent::KeyboardInput { input, .. } => {
-                    if input.virtual_keycode == Some(VirtualKeyCode::Escape) {
-                        *control_flow = ControlFlow::Exit
-                    }
+                WindowEvent::KeyboardInput { input, .. }
+                    if input.virtual_keycode == Some(VirtualKeyCode::Escape) =>
+                {
+                    cancel.cancel();
+                    *control_flow = ControlFlow::Exit
+                }
+                WindowEvent::CloseRequested => {
+                    cancel.cancel();
+                    *control_flow = ControlFlow::Exit
                 }
-                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
                 _ => {}
             },
+            Event::UserEvent(PreviewEvent::Progress(progress)) => {
+                window.set_title(&format!("rays | {progress}"));
+                last_progress = Some(progress);
+            }
+            Event::UserEvent(PreviewEvent::Finished(finished_matrix)) => {
+                match &last_progress {
+                    Some(progress) => window.set_title(&format!(
+                        "rays | rendered in {}",
+                        format_duration(progress.elapsed)
+                    )),
+                    None => window.set_title("rays"),
+                }
+                color_matrix = Some(finished_matrix);
+                window.request_redraw();
+            }
             Event::RedrawRequested(_) => {
                 // draw stuff
-                let pixel_buffer = pixels.frame_mut();
-                for (pix_index, pixel) in pixel_buffer.chunks_exact_mut(4).enumerate() {
-                    let row = pix_index / output_width as usize;
-                    let col = pix_index % output_width as usize;
-
-                    let color_from_mat = color_matrix.at(row, col);
-
-                    let color = [
-                        (255.0 * color_from_mat.r()) as u8,
-                        (255.0 * color_from_mat.g()) as u8,
-                        (255.0 * color_from_mat.b()) as u8,
-                        0xff,
-                    ];
-                    pixel.copy_from_slice(&color);
+                if let Some(color_matrix) = &color_matrix {
+                    let pixel_buffer = pixels.frame_mut();
+                    for (pix_index, pixel) in pixel_buffer.chunks_exact_mut(4).enumerate() {
+                        let row = pix_index / output_width as usize;
+                        let col = pix_index % output_width as usize;
+
+                        let color_from_mat = color_matrix.at(row, col);
+
+                        let color = [
+                            (255.0 * color_from_mat.r()) as u8,
+                            (255.0 * color_from_mat.g()) as u8,
+                            (255.0 * color_from_mat.b()) as u8,
+                            0xff,
+                        ];
+                        pixel.copy_from_slice(&color);
+                    }
                 }
 
                 pixels.render().unwrap();
@@ -122,6 +415,12 @@ fn _write_to_yaml(scene: &Scene, name: &str) {
     .unwrap();
 }
 
+fn read_from_yaml(path: &str) -> Result<Scene, String> {
+    let contents =
+        fs::read_to_string(path).map_err(|err| format!("could not read {path}: {err}"))?;
+    serde_yaml::from_str(&contents).map_err(|err| format!("could not parse {path}: {err}"))
+}
+
 // temporary until I'm done churning on scene storage
 fn make_initial_test_scene() -> SceneBuilder {
     let mut scene = Scene::builder();
@@ -130,58 +429,73 @@ fn make_initial_test_scene() -> SceneBuilder {
     let sphere1 = Sphere::new(0.5, Vec3::new(-1.0, 0.5, -2.0));
     let sphere2 = Sphere::new(0.5, Vec3::new(-2.0, 0.5, 1.0));
     let sphere3 = Sphere::new(5.0, Vec3::new(5.0, 5.0, -5.0));
-    let sphere4 = Sphere::new(0.75, Vec3::new(-0.75, 0.75, 2.0));
-    let mut sphere5 = Sphere::new(0.65, Vec3::new(-0.75, 0.75, 2.0));
-    sphere5.flip_orientation();
+    // a hollow glass ball
+    let shell = Difference::new(
+        Arc::new(Sphere::new(0.75, Vec3::new(-0.75, 0.75, 2.0))),
+        vec![Arc::new(Sphere::new(0.65, Vec3::new(-0.75, 0.75, 2.0)))],
+    );
 
     // let plane = Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
     let plane = Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
 
-    let lambert_gray = Lambertian::new(Color::from_rgb_f32(0.5, 0.5, 0.5));
-    let lambert_pink = Lambertian::new(Color::from_rgb_u8(255, 121, 198));
+    // unit squares on the floor, for a sense of scale
+    let floor_checks = Checkerboard::new(
+        Color::from_rgb_f32(0.6, 0.6, 0.6).into(),
+        Color::from_rgb_f32(0.35, 0.35, 0.35).into(),
+        1.0,
+    );
+    let lambert_checkered = Lambertian::new(Textured::Texture(Box::new(floor_checks)));
+    let pink_marble = Marble::new(
+        vec![
+            Color::from_rgb_u8(255, 121, 198),
+            Color::from_rgb_u8(255, 230, 245),
+        ],
+        1.5,
+        0,
+    );
+    let lambert_pink = Lambertian::new(Textured::Texture(Box::new(pink_marble)));
     // let lambert_purple = Lambertian::new(Color::from_rgb_u8(189, 147, 249));
-    let lambert_green = Lambertian::new(Color::from_rgb_u8(80, 250, 123));
-    let metal_yellow = Metal::new(Color::from_rgb_u8(241, 250, 140), 0.4);
+    let principled_green = Principled::new(Color::from_rgb_u8(80, 250, 123))
+        .with_roughness(0.4)
+        .with_clearcoat(1.0);
+    let metal_yellow = Conductor::new(ComplexIor::Gold, 0.4);
     let metal_orange = Metal::new(Color::from_rgb_u8(255, 184, 108), 0.3);
 
-    let glass = Translucent::new(1.5);
+    let mut registry = Registry::new();
+    let glass = registry.add_material("glass", Translucent::new(1.5));
 
     let object0 = Object {
-        geometry: Box::new(sphere0),
-        material: Box::new(lambert_pink),
+        geometry: Arc::new(sphere0),
+        material: Arc::new(lambert_pink),
     };
     let object1 = Object {
-        geometry: Box::new(sphere1),
-        material: Box::new(lambert_green),
+        geometry: Arc::new(sphere1),
+        material: Arc::new(principled_green),
     };
     let object2 = Object {
-        geometry: Box::new(sphere2),
-        material: Box::new(metal_yellow),
+        geometry: Arc::new(sphere2),
+        material: Arc::new(metal_yellow),
     };
     let object3 = Object {
-        geometry: Box::new(sphere3),
-        material: Box::new(metal_orange),
+        geometry: Arc::new(sphere3),
+        material: Arc::new(metal_orange),
     };
     let object4 = Object {
-        geometry: Box::new(plane),
-        material: Box::new(lambert_gray),
+        geometry: Arc::new(plane),
+        material: Arc::new(lambert_checkered),
     };
     let object5 = Object {
-        geometry: Box::new(sphere4),
-        material: Box::new(glass.clone()),
-    };
-    let object6 = Object {
-        geometry: Box::new(sphere5),
-        material: Box::new(glass),
+        geometry: Arc::new(shell),
+        material: glass,
     };
 
+    scene.registry(registry);
     scene.add_object(object0);
     scene.add_object(object1);
     scene.add_object(object2);
     scene.add_object(object3);
     scene.add_object(object4);
     scene.add_object(object5);
-    scene.add_object(object6);
 
     // window setup
     let output_width = 800;
@@ -208,33 +522,34 @@ fn make_initial_test_scene() -> SceneBuilder {
 
 fn _make_tutorial_end_scene() -> SceneBuilder {
     let mut scene = Scene::builder();
+    scene.registry(Registry::new());
 
     let sphere0 = Sphere::new(1.0, Vec3::new(0.0, 1.0, 0.0));
     let sphere0_mat = Translucent::new(1.5);
     scene.add_object(Object {
-        geometry: Box::new(sphere0),
-        material: Box::new(sphere0_mat),
+        geometry: Arc::new(sphere0),
+        material: Arc::new(sphere0_mat),
     });
 
     let sphere1 = Sphere::new(1.0, Vec3::new(-4.0, 1.0, 0.0));
     let sphere1_mat = Lambertian::new(Color::from_rgb_f32(0.1, 0.2, 0.4));
     scene.add_object(Object {
-        geometry: Box::new(sphere1),
-        material: Box::new(sphere1_mat),
+        geometry: Arc::new(sphere1),
+        material: Arc::new(sphere1_mat),
     });
 
     let sphere2 = Sphere::new(1.0, Vec3::new(4.0, 1.0, 0.0));
     let sphere2_mat = Metal::new(Color::from_rgb_f32(0.5, 0.6, 0.7), 0.0);
     scene.add_object(Object {
-        geometry: Box::new(sphere2),
-        material: Box::new(sphere2_mat),
+        geometry: Arc::new(sphere2),
+        material: Arc::new(sphere2_mat),
     });
 
     let floor = Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
     let floor_mat = Lambertian::new(Color::from_rgb_f32(0.5, 0.5, 0.5));
     scene.add_object(Object {
-        geometry: Box::new(floor),
-        material: Box::new(floor_mat),
+        geometry: Arc::new(floor),
+        material: Arc::new(floor_mat),
     });
 
     let mut rng: StdRng = SeedableRng::seed_from_u64(7);
@@ -249,7 +564,7 @@ fn _make_tutorial_end_scene() -> SceneBuilder {
                 radius,
                 z as f32 + center_offset_z,
             );
-            let sphere = Box::new(Sphere::new(radius, center));
+            let sphere = Arc::new(Sphere::new(radius, center));
 
             // generate material at random
             let rand_color = Color::from_rgb_f32(
@@ -257,10 +572,10 @@ fn _make_tutorial_end_scene() -> SceneBuilder {
                 rng.gen_range(0.3..0.8),
                 rng.gen_range(0.5..1.0),
             );
-            let material: Box<dyn ScatterRay> = match rng.gen::<f32>() {
-                x if x < 0.8 => Box::new(Lambertian::new(rand_color)),
-                x if x < 0.95 => Box::new(Metal::new(rand_color, 0.5 * rng.gen::<f32>())),
-                _ => Box::new(Translucent::new(1.5)),
+            let material: Material = match rng.gen::<f32>() {
+                x if x < 0.8 => Arc::new(Lambertian::new(rand_color)),
+                x if x < 0.95 => Arc::new(Metal::new(rand_color, 0.5 * rng.gen::<f32>())),
+                _ => Arc::new(Translucent::new(1.5)),
             };
 
             scene.add_object(Object {
@@ -1,4 +1,10 @@
 pub mod color;
+pub mod mat4;
+pub mod noise;
+pub mod quaternion;
 pub mod ray;
+pub mod roots;
+pub mod sampler;
 pub mod shaping;
+pub mod transform;
 pub mod vec3;
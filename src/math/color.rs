@@ -1,6 +1,6 @@
 use std::ops::{Add, Mul};
 
-use image::{ImageBuffer, Rgb};
+use image::{ColorType, DynamicImage, ImageBuffer, Rgb};
 use serde::{Deserialize, Serialize};
 
 use crate::math::shaping::clamp;
@@ -36,6 +36,10 @@ impl Color {
         self.0.z
     }
 
+    pub fn is_finite(&self) -> bool {
+        self.0.x.is_finite() && self.0.y.is_finite() && self.0.z.is_finite()
+    }
+
     pub fn from_clamped(v: Vec3) -> Self {
         Color(Vec3::new(
             clamp(v.x, 0.0, 1.0),
@@ -122,6 +126,7 @@ mod tests {
 
 // put this somewhere else eventually
 // also, find a better name
+#[derive(Debug, PartialEq)]
 pub struct ColorMatrix(Vec<Vec<Color>>);
 
 impl ColorMatrix {
@@ -163,6 +168,26 @@ impl From<ColorMatrix> for ImageBuffer<Rgb<u8>, Vec<u8>> {
     }
 }
 
+// 8 and 16 bit images are assumed to already be gamma corrected (like our own renders),
+// but float images (eg. EXR or HDR files) hold linear values, so we gamma correct those here
+impl From<&DynamicImage> for ColorMatrix {
+    fn from(img: &DynamicImage) -> Self {
+        let exponent = match img.color() {
+            ColorType::Rgb32F | ColorType::Rgba32F => 1.0 / 2.2,
+            _ => 1.0,
+        };
+
+        let rgb = img.to_rgb32f();
+        let mut mat = ColorMatrix::new(rgb.width() as usize, rgb.height() as usize);
+        for (col_index, row_index, pixel) in rgb.enumerate_pixels() {
+            let [r, g, b] = pixel.0.map(|c| c.max(0.0).powf(exponent));
+            *mat.at_mut(row_index as usize, col_index as usize) = Color::from_rgb_f32(r, g, b);
+        }
+
+        mat
+    }
+}
+
 #[cfg(test)]
 mod color_mat_tests {
     use super::*;
@@ -190,4 +215,15 @@ mod color_mat_tests {
         *bottom_right_entry = Color::from_rgb_f32(1.0, 1.0, 1.0);
         assert_eq!(*mat.at(2, 1), Color::from_rgb_f32(1.0, 1.0, 1.0));
     }
+
+    #[test]
+    fn create_from_image() {
+        let mut img = ImageBuffer::new(2, 3);
+        *img.get_pixel_mut(1, 2) = Rgb([255u8, 0, 51]);
+        let mat = ColorMatrix::from(&DynamicImage::ImageRgb8(img));
+
+        assert_eq!(mat.width(), 2);
+        assert_eq!(mat.height(), 3);
+        assert_eq!(*mat.at(2, 1), Color::from_rgb_f32(1.0, 0.0, 0.2));
+    }
 }
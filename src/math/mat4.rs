@@ -0,0 +1,189 @@
+use std::ops::Mul;
+
+use serde::{Deserialize, Serialize};
+
+use super::vec3::Vec3;
+
+// a 4x4 matrix, stored row by row. points and vectors are treated as columns,
+// with points getting a 1 in their fourth coordinate and vectors getting a 0
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Mat4(pub [[f32; 4]; 4]);
+
+impl Mat4 {
+    pub fn identity() -> Mat4 {
+        Mat4::diagonal(1.0, 1.0, 1.0)
+    }
+
+    pub fn translation(v: &Vec3) -> Mat4 {
+        Mat4([
+            [1.0, 0.0, 0.0, v.x],
+            [0.0, 1.0, 0.0, v.y],
+            [0.0, 0.0, 1.0, v.z],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn scaling(v: &Vec3) -> Mat4 {
+        Mat4::diagonal(v.x, v.y, v.z)
+    }
+
+    fn diagonal(x: f32, y: f32, z: f32) -> Mat4 {
+        Mat4([
+            [x, 0.0, 0.0, 0.0],
+            [0.0, y, 0.0, 0.0],
+            [0.0, 0.0, z, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+
+    pub fn at(&self, row: usize, column: usize) -> f32 {
+        self.0[row][column]
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut result = [[0.0; 4]; 4];
+        for (row, entries) in result.iter_mut().enumerate() {
+            for (column, entry) in entries.iter_mut().enumerate() {
+                *entry = self.0[column][row];
+            }
+        }
+        Mat4(result)
+    }
+
+    // Gauss-Jordan elimination with partial pivoting.
+    // Returns `None` if the matrix is (numerically) singular.
+    pub fn inverse(&self) -> Option<Mat4> {
+        let mut m = self.0;
+        let mut inv = Mat4::identity().0;
+
+        for col in 0..4 {
+            // swap the row with the largest entry in this column into place
+            let pivot = (col..4)
+                .max_by(|&a, &b| m[a][col].abs().total_cmp(&m[b][col].abs()))
+                .unwrap();
+            if m[pivot][col].abs() < 1e-12 {
+                return None;
+            }
+            m.swap(col, pivot);
+            inv.swap(col, pivot);
+
+            let scale = 1.0 / m[col][col];
+            for k in 0..4 {
+                m[col][k] *= scale;
+                inv[col][k] *= scale;
+            }
+
+            for row in 0..4 {
+                if row == col {
+                    continue;
+                }
+                let factor = m[row][col];
+                for k in 0..4 {
+                    m[row][k] -= factor * m[col][k];
+                    inv[row][k] -= factor * inv[col][k];
+                }
+            }
+        }
+
+        Some(Mat4(inv))
+    }
+
+    pub fn transform_point(&self, p: &Vec3) -> Vec3 {
+        let m = &self.0;
+        let x = m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3];
+        let y = m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3];
+        let z = m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3];
+        let w = m[3][0] * p.x + m[3][1] * p.y + m[3][2] * p.z + m[3][3];
+
+        // w is always 1 for affine transformations
+        if w == 1.0 {
+            Vec3::new(x, y, z)
+        } else {
+            Vec3::new(x / w, y / w, z / w)
+        }
+    }
+
+    // ignores the translation part of the matrix
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.0;
+        Vec3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+}
+
+impl Mul for &Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        let mut result = [[0.0; 4]; 4];
+        for (row, entries) in result.iter_mut().enumerate() {
+            for (column, entry) in entries.iter_mut().enumerate() {
+                *entry = (0..4).map(|k| self.0[row][k] * rhs.0[k][column]).sum();
+            }
+        }
+        Mat4(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: &Mat4, b: &Mat4) {
+        for row in 0..4 {
+            for column in 0..4 {
+                assert!(
+                    (a.at(row, column) - b.at(row, column)).abs() < 1e-5,
+                    "{a:?} != {b:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn translate_points_but_not_vectors() {
+        let m = Mat4::translation(&Vec3::new(1.0, 2.0, 3.0));
+        let v = Vec3::new(1.0, 1.0, 1.0);
+        assert_eq!(m.transform_point(&v), Vec3::new(2.0, 3.0, 4.0));
+        assert_eq!(m.transform_vector(&v), v);
+    }
+
+    #[test]
+    fn multiply_matrices() {
+        let t = Mat4::translation(&Vec3::new(1.0, 0.0, 0.0));
+        let s = Mat4::scaling(&Vec3::new(2.0, 2.0, 2.0));
+
+        // scale first, then translate
+        let p = (&t * &s).transform_point(&Vec3::new(1.0, 1.0, 1.0));
+        assert_eq!(p, Vec3::new(3.0, 2.0, 2.0));
+    }
+
+    #[test]
+    fn transpose_matrix() {
+        let m = Mat4::translation(&Vec3::new(1.0, 2.0, 3.0));
+        assert_eq!(m.transpose().at(3, 1), 2.0);
+        assert_eq!(m.transpose().transpose(), m);
+    }
+
+    #[test]
+    fn invert_matrix() {
+        let m = Mat4([
+            [2.0, 0.0, 1.0, 3.0],
+            [0.0, 1.0, 0.0, -1.0],
+            [1.0, 0.0, 3.0, 0.5],
+            [0.0, 0.0, 0.0, 1.0],
+        ]);
+        let inverse = m.inverse().unwrap();
+        assert_close(&(&m * &inverse), &Mat4::identity());
+        assert_close(&(&inverse * &m), &Mat4::identity());
+    }
+
+    #[test]
+    fn singular_matrix_has_no_inverse() {
+        let m = Mat4::scaling(&Vec3::new(1.0, 0.0, 1.0));
+        assert_eq!(m.inverse(), None);
+    }
+}
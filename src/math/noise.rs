@@ -0,0 +1,186 @@
+use super::sampler::mix;
+use super::vec3::Vec3;
+
+// Smooth random functions of space, for procedural textures. They're all deterministic: the same
+// point and seed always give the same value, and different seeds give unrelated patterns.
+// Features are about one unit across, so points get scaled before being passed in.
+
+// the directions from the center of a cube to the middles of its edges
+const GRADIENTS: [[f32; 3]; 12] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+];
+
+// a random number for each point of the integer lattice
+fn hash(cell: [i64; 3], seed: u32) -> u64 {
+    let h = mix(seed as u64 ^ mix(cell[0] as u64));
+    let h = mix(h ^ cell[1] as u64);
+    mix(h ^ cell[2] as u64)
+}
+
+// the lattice cell containing a point, and where the point is within it (0 to 1 on each axis)
+fn cell_of(p: &Vec3) -> ([i64; 3], [f32; 3]) {
+    let floor = [p.x.floor(), p.y.floor(), p.z.floor()];
+    let cell = floor.map(|c| c as i64);
+    let within = [p.x - floor[0], p.y - floor[1], p.z - floor[2]];
+    (cell, within)
+}
+
+// Perlin's "improved" gradient noise: each lattice point gets a random gradient, and values in
+// between blend smoothly from one to the next. It's zero on lattice points, and stays within
+// about -1 to 1.
+pub fn gradient_noise(p: &Vec3, seed: u32) -> f32 {
+    let (cell, within) = cell_of(p);
+
+    // eases in and out, so that the noise's derivatives are continuous across cells
+    let fade = within.map(|t| t * t * t * (t * (t * 6.0 - 15.0) + 10.0));
+
+    let corner_value = |offset: [i64; 3]| {
+        let corner = [0, 1, 2].map(|i| cell[i] + offset[i]);
+        let gradient = GRADIENTS[(hash(corner, seed) % 12) as usize];
+        (0..3)
+            .map(|i| gradient[i] * (within[i] - offset[i] as f32))
+            .sum::<f32>()
+    };
+    let lerp = |t: f32, a: f32, b: f32| a + t * (b - a);
+    let along_x = |y, z| lerp(fade[0], corner_value([0, y, z]), corner_value([1, y, z]));
+    let along_y = |z| lerp(fade[1], along_x(0, z), along_x(1, z));
+    lerp(fade[2], along_y(0), along_y(1))
+}
+
+// Sums of noise at doubling frequencies and halving amplitudes ("octaves"), which adds finer
+// and finer detail. Each octave gets its own seed, so the octaves don't line up at the origin.
+fn octaves(p: &Vec3, seed: u32, octaves: u32, value: impl Fn(f32) -> f32) -> f32 {
+    let mut sum = 0.0;
+    let mut total_amplitude = 0.0;
+    let (mut frequency, mut amplitude) = (1.0, 1.0);
+    for octave in 0..octaves.max(1) {
+        let noise = gradient_noise(&(frequency * p), seed.wrapping_add(octave));
+        sum += amplitude * value(noise);
+        total_amplitude += amplitude;
+        frequency *= 2.0;
+        amplitude *= 0.5;
+    }
+    sum / total_amplitude
+}
+
+// fractal Brownian motion: a cloudy pattern from about -1 to 1
+pub fn fbm(p: &Vec3, seed: u32, octave_count: u32) -> f32 {
+    octaves(p, seed, octave_count, |noise| noise)
+}
+
+// like `fbm`, but folded at zero, which makes sharp creases: from 0 to about 1
+pub fn turbulence(p: &Vec3, seed: u32, octave_count: u32) -> f32 {
+    octaves(p, seed, octave_count, f32::abs)
+}
+
+// Worley's cellular noise: each lattice cell has a random feature point in it, and this gives the
+// distances to the closest feature point and the second closest. The first makes a pattern of
+// round cells, and the difference between the two is zero along the cells' edges.
+pub fn worley(p: &Vec3, seed: u32) -> (f32, f32) {
+    let (cell, _) = cell_of(p);
+
+    let mut closest = f32::INFINITY;
+    let mut second = f32::INFINITY;
+    for dz in -1..=1 {
+        for dy in -1..=1 {
+            for dx in -1..=1 {
+                let neighbor = [cell[0] + dx, cell[1] + dy, cell[2] + dz];
+
+                // three 21 bit fractions from one hash
+                let h = hash(neighbor, seed);
+                let fraction = |i: u32| ((h >> (21 * i)) & 0x1f_ffff) as f32 / (1 << 21) as f32;
+                let feature = Vec3::new(
+                    neighbor[0] as f32 + fraction(0),
+                    neighbor[1] as f32 + fraction(1),
+                    neighbor[2] as f32 + fraction(2),
+                );
+
+                let distance = (&feature - p).length();
+                if distance < closest {
+                    second = closest;
+                    closest = distance;
+                } else if distance < second {
+                    second = distance;
+                }
+            }
+        }
+    }
+    (closest, second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // points scattered over a few cells, including negative ones
+    fn points() -> impl Iterator<Item = Vec3> {
+        (0..1000).map(|i| {
+            let i = i as f32;
+            Vec3::new(
+                (i * 0.731).sin() * 4.0,
+                (i * 0.377).cos() * 4.0,
+                i * 0.013 - 6.0,
+            )
+        })
+    }
+
+    #[test]
+    fn noise_is_zero_on_the_lattice() {
+        for seed in 0..4 {
+            assert_eq!(gradient_noise(&Vec3::new(3.0, -2.0, 5.0), seed), 0.0);
+        }
+    }
+
+    #[test]
+    fn noise_is_bounded_and_smooth() {
+        let step = Vec3::new(1e-3, 1e-3, 1e-3);
+        for p in points() {
+            let value = gradient_noise(&p, 1);
+            assert!((-1.1..=1.1).contains(&value), "{value} at {p:?}");
+
+            let nearby = gradient_noise(&(&p + &step), 1);
+            assert!((value - nearby).abs() < 0.02, "jump at {p:?}");
+        }
+    }
+
+    #[test]
+    fn seeds_change_the_pattern() {
+        let p = Vec3::new(0.3, 1.7, -2.2);
+        assert_eq!(gradient_noise(&p, 5), gradient_noise(&p, 5));
+        assert_ne!(gradient_noise(&p, 5), gradient_noise(&p, 6));
+        assert_ne!(worley(&p, 5), worley(&p, 6));
+    }
+
+    #[test]
+    fn fractal_noise_ranges() {
+        for p in points() {
+            let value = fbm(&p, 2, 5);
+            assert!((-1.1..=1.1).contains(&value));
+
+            let value = turbulence(&p, 2, 5);
+            assert!((0.0..=1.1).contains(&value));
+        }
+    }
+
+    #[test]
+    fn worley_distances() {
+        for p in points() {
+            let (closest, second) = worley(&p, 3);
+            assert!(closest <= second);
+
+            // some cell's feature point is always within the cell or the next ones over
+            assert!(closest < 3f32.sqrt());
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use std::ops::{Add, Mul};
+
+use serde::{Deserialize, Serialize};
+
+use super::{mat4::Mat4, vec3::Vec3};
+
+// rotations are represented by unit quaternions: rotating by `angle` around the unit vector `axis`
+// is w = cos(angle/2), (x,y,z) = sin(angle/2) * axis
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Quaternion {
+    pub w: f32,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+
+impl Quaternion {
+    pub fn new(w: f32, x: f32, y: f32, z: f32) -> Quaternion {
+        Quaternion { w, x, y, z }
+    }
+
+    pub fn identity() -> Quaternion {
+        Quaternion::new(1.0, 0.0, 0.0, 0.0)
+    }
+
+    // the axis doesn't need to be normalized
+    pub fn from_axis_angle(axis: &Vec3, radians: f32) -> Quaternion {
+        let axis = axis.normalize();
+        let (sin, cos) = (radians / 2.0).sin_cos();
+        Quaternion::new(cos, sin * axis.x, sin * axis.y, sin * axis.z)
+    }
+
+    pub fn length(&self) -> f32 {
+        (self.w * self.w + self.x * self.x + self.y * self.y + self.z * self.z).sqrt()
+    }
+
+    pub fn normalize(&self) -> Quaternion {
+        let len = self.length();
+        Quaternion::new(self.w / len, self.x / len, self.y / len, self.z / len)
+    }
+
+    // for unit quaternions, this is the inverse rotation
+    pub fn conjugate(&self) -> Quaternion {
+        Quaternion::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    pub fn rotate(&self, v: &Vec3) -> Vec3 {
+        // v' = q v q*, simplified using a couple of cross products
+        let u = Vec3::new(self.x, self.y, self.z);
+        let t = 2.0 * &Vec3::cross(&u, v);
+        Vec3::lin_comb(vec![(1.0, v), (self.w, &t), (1.0, &Vec3::cross(&u, &t))])
+    }
+
+    // the rotation matrix of a unit quaternion
+    pub fn to_matrix(&self) -> Mat4 {
+        let Quaternion { w, x, y, z } = *self;
+        Mat4([
+            [
+                1.0 - 2.0 * (y * y + z * z),
+                2.0 * (x * y - w * z),
+                2.0 * (x * z + w * y),
+                0.0,
+            ],
+            [
+                2.0 * (x * y + w * z),
+                1.0 - 2.0 * (x * x + z * z),
+                2.0 * (y * z - w * x),
+                0.0,
+            ],
+            [
+                2.0 * (x * z - w * y),
+                2.0 * (y * z + w * x),
+                1.0 - 2.0 * (x * x + y * y),
+                0.0,
+            ],
+            [0.0, 0.0, 0.0, 1.0],
+        ])
+    }
+}
+
+impl Add for &Quaternion {
+    type Output = Quaternion;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Quaternion::new(
+            self.w + rhs.w,
+            self.x + rhs.x,
+            self.y + rhs.y,
+            self.z + rhs.z,
+        )
+    }
+}
+
+// composes rotations: (p * q) rotates by q first, then by p
+impl Mul for &Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Quaternion::new(
+            self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+
+    fn assert_close(u: &Vec3, v: &Vec3) {
+        assert!((u - v).length() < 1e-5, "{u:?} != {v:?}");
+    }
+
+    #[test]
+    fn rotate_around_axis() {
+        let q = Quaternion::from_axis_angle(&Vec3::new(0.0, 0.0, 2.0), PI / 2.0);
+        assert_close(
+            &q.rotate(&Vec3::new(1.0, 0.0, 0.0)),
+            &Vec3::new(0.0, 1.0, 0.0),
+        );
+    }
+
+    #[test]
+    fn matrix_matches_rotation() {
+        let q = Quaternion::from_axis_angle(&Vec3::new(1.0, 2.0, -1.0), 0.7);
+        let v = Vec3::new(0.3, -2.0, 1.5);
+        assert_close(&q.to_matrix().transform_vector(&v), &q.rotate(&v));
+    }
+
+    #[test]
+    fn compose_rotations() {
+        let about_z = Quaternion::from_axis_angle(&Vec3::new(0.0, 0.0, 1.0), PI / 2.0);
+        let about_x = Quaternion::from_axis_angle(&Vec3::new(1.0, 0.0, 0.0), PI / 2.0);
+        let v = Vec3::new(1.0, 0.0, 0.0);
+
+        // x -> y (about z), then y -> z (about x)
+        assert_close(&(&about_x * &about_z).rotate(&v), &Vec3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn conjugate_undoes_rotation() {
+        let q = Quaternion::from_axis_angle(&Vec3::new(1.0, 1.0, 0.0), 1.2);
+        let v = Vec3::new(0.5, 2.0, -1.0);
+        assert_close(&q.conjugate().rotate(&q.rotate(&v)), &v);
+        assert!((q.length() - 1.0).abs() < 1e-6);
+    }
+}
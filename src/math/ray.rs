@@ -4,11 +4,35 @@ use super::vec3::Vec3;
 pub struct Ray {
     pub origin: Vec3,
     pub dir: Vec3,
+
+    // for rays from the camera: the rays through the neighboring pixels, which say how big the
+    // pixel looks where the ray hits something (used to filter textures)
+    pub differentials: Option<RayDifferentials>,
+}
+
+// the rays one pixel to the right (x) and one pixel down (y)
+#[derive(Debug, PartialEq, Clone)]
+pub struct RayDifferentials {
+    pub x_origin: Vec3,
+    pub x_dir: Vec3,
+    pub y_origin: Vec3,
+    pub y_dir: Vec3,
 }
 
 impl Ray {
     pub fn new(origin: Vec3, dir: Vec3) -> Ray {
-        Ray { origin, dir }
+        Ray {
+            origin,
+            dir,
+            differentials: None,
+        }
+    }
+
+    pub fn with_differentials(self, differentials: RayDifferentials) -> Ray {
+        Ray {
+            differentials: Some(differentials),
+            ..self
+        }
     }
 
     pub fn at(&self, t: f32) -> Vec3 {
@@ -0,0 +1,179 @@
+// Real roots of low degree polynomials, for intersecting rays with curved surfaces.
+// Each function returns the roots in increasing order; repeated roots may show up once or twice.
+
+pub fn solve_quadratic(a: f32, b: f32, c: f32) -> Vec<f32> {
+    if a == 0.0 {
+        return if b == 0.0 { vec![] } else { vec![-c / b] };
+    }
+
+    let disc = b * b - 4.0 * a * c;
+    if disc < 0.0 {
+        return vec![];
+    }
+
+    // avoids cancellation between -b and the square root when they're close
+    let q = -0.5 * (b + b.signum() * disc.sqrt());
+    let (r0, r1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+    if r0 < r1 {
+        vec![r0, r1]
+    } else {
+        vec![r1, r0]
+    }
+}
+
+// x^3 + a x^2 + b x + c = 0
+pub fn solve_cubic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    // substituting x = y - a/3 gives y^3 + p y + q = 0
+    let shift = a / 3.0;
+    let p = b - a * shift;
+    let q = c - b * shift + 2.0 * shift * shift * shift;
+
+    let half_q = q / 2.0;
+    let third_p = p / 3.0;
+    let disc = half_q * half_q + third_p * third_p * third_p;
+
+    let mut roots = if disc > 0.0 {
+        // one real root (Cardano)
+        let sqrt_disc = disc.sqrt();
+        vec![(-half_q + sqrt_disc).cbrt() + (-half_q - sqrt_disc).cbrt() - shift]
+    } else if third_p == 0.0 {
+        vec![-shift]
+    } else {
+        // three real roots (trigonometric method)
+        let r = (-third_p).sqrt();
+        let phi = (-half_q / (r * r * r)).clamp(-1.0, 1.0).acos();
+        (0..3)
+            .map(|k| 2.0 * r * ((phi + 2.0 * std::f64::consts::PI * k as f64) / 3.0).cos() - shift)
+            .collect()
+    };
+
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+// c4 x^4 + c3 x^3 + c2 x^2 + c1 x + c0 = 0, using Ferrari's method and then polishing the
+// roots with a few Newton steps, since the closed form loses a lot of precision
+pub fn solve_quartic(c4: f64, c3: f64, c2: f64, c1: f64, c0: f64) -> Vec<f64> {
+    if c4 == 0.0 {
+        let mut roots = solve_cubic_general(c3, c2, c1, c0);
+        roots.sort_by(f64::total_cmp);
+        return roots;
+    }
+
+    let (a, b, c, d) = (c3 / c4, c2 / c4, c1 / c4, c0 / c4);
+
+    // substituting x = y - a/4 gives y^4 + p y^2 + q y + r = 0
+    let shift = a / 4.0;
+    let p = b - 6.0 * shift * shift;
+    let q = c - 2.0 * b * shift + 8.0 * shift * shift * shift;
+    let r = d - c * shift + b * shift * shift - 3.0 * shift * shift * shift * shift;
+
+    let mut ys = vec![];
+    if q.abs() < 1e-12 {
+        // biquadratic: a quadratic in y^2
+        for z in solve_quadratic_f64(1.0, p, r) {
+            if z >= 0.0 {
+                ys.push(z.sqrt());
+                ys.push(-z.sqrt());
+            }
+        }
+    } else {
+        // Pick m so that (y^2 + p/2 + m)^2 - (the original quartic) is a perfect square,
+        // then the quartic factors into two quadratics. m is a positive root of this cubic.
+        let m = solve_cubic(p, p * p / 4.0 - r, -q * q / 8.0)
+            .into_iter()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if m <= 0.0 {
+            return vec![];
+        }
+
+        let s = (2.0 * m).sqrt();
+        let offset = q / (2.0 * s);
+        ys.extend(solve_quadratic_f64(1.0, -s, p / 2.0 + m + offset));
+        ys.extend(solve_quadratic_f64(1.0, s, p / 2.0 + m - offset));
+    }
+
+    let polynomial = |x: f64| (((c4 * x + c3) * x + c2) * x + c1) * x + c0;
+    let derivative = |x: f64| ((4.0 * c4 * x + 3.0 * c3) * x + 2.0 * c2) * x + c1;
+
+    let mut roots: Vec<f64> = ys
+        .into_iter()
+        .map(|y| {
+            let mut x = y - shift;
+            for _ in 0..3 {
+                let slope = derivative(x);
+                if slope == 0.0 {
+                    break;
+                }
+                x -= polynomial(x) / slope;
+            }
+            x
+        })
+        .collect();
+
+    roots.sort_by(f64::total_cmp);
+    roots
+}
+
+fn solve_quadratic_f64(a: f64, b: f64, c: f64) -> Vec<f64> {
+    let disc = b * b - 4.0 * a * c;
+    if disc < 0.0 {
+        return vec![];
+    }
+    let q = -0.5 * (b + b.signum() * disc.sqrt());
+    if q == 0.0 {
+        return vec![0.0];
+    }
+    vec![q / a, c / q]
+}
+
+fn solve_cubic_general(c3: f64, c2: f64, c1: f64, c0: f64) -> Vec<f64> {
+    if c3 == 0.0 {
+        if c2 == 0.0 {
+            return if c1 == 0.0 { vec![] } else { vec![-c0 / c1] };
+        }
+        return solve_quadratic_f64(c2, c1, c0);
+    }
+    solve_cubic(c2 / c3, c1 / c3, c0 / c3)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(actual: &[f64], expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?} != {expected:?}");
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-6, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn quadratic_roots() {
+        assert_eq!(solve_quadratic(1.0, -3.0, 2.0), vec![1.0, 2.0]);
+        assert_eq!(solve_quadratic(1.0, 0.0, 1.0), vec![]);
+        assert_eq!(solve_quadratic(0.0, 2.0, -4.0), vec![2.0]);
+    }
+
+    #[test]
+    fn cubic_roots() {
+        // (x - 1)(x - 2)(x + 3)
+        assert_roots(&solve_cubic(0.0, -7.0, 6.0), &[-3.0, 1.0, 2.0]);
+        // (x - 2)(x^2 + 1)
+        assert_roots(&solve_cubic(-2.0, 1.0, -2.0), &[2.0]);
+    }
+
+    #[test]
+    fn quartic_roots() {
+        // (x - 1)(x - 2)(x - 3)(x - 4)
+        assert_roots(
+            &solve_quartic(1.0, -10.0, 35.0, -50.0, 24.0),
+            &[1.0, 2.0, 3.0, 4.0],
+        );
+        // (x^2 - 4)(x^2 + 1), which is biquadratic
+        assert_roots(&solve_quartic(2.0, 0.0, -6.0, 0.0, -8.0), &[-2.0, 2.0]);
+        // (x - 1)(x + 2)(x^2 + x + 5)
+        assert_roots(&solve_quartic(1.0, 2.0, 4.0, 3.0, -10.0), &[-2.0, 1.0]);
+        assert_roots(&solve_quartic(1.0, 0.0, 0.0, 0.0, 1.0), &[]);
+    }
+}
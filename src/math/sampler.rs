@@ -0,0 +1,86 @@
+use std::cell::RefCell;
+
+use rand::{rngs::StdRng, Error, RngCore, SeedableRng};
+
+// All of the randomness used while rendering comes from here rather than from `thread_rng`,
+// so that renders are reproducible: before computing each sample, the renderer reseeds this
+// thread's generator based only on the render's seed, the pixel and the sample's index.
+// That means a render can be stopped and resumed (or extended with more samples) and still
+// give exactly the same result as one that ran in one go.
+
+thread_local! {
+    static SAMPLER: RefCell<StdRng> = RefCell::new(StdRng::from_entropy());
+}
+
+// a handle to the current thread's sample generator, used just like `thread_rng()`
+#[derive(Debug, Clone, Copy)]
+pub struct SamplerRng;
+
+pub fn rng() -> SamplerRng {
+    SamplerRng
+}
+
+pub fn seed_for_sample(render_seed: u64, pixel_index: u64, sample_index: u32) {
+    let seed = mix(mix(render_seed ^ mix(pixel_index)) ^ sample_index as u64);
+    SAMPLER.with(|sampler| *sampler.borrow_mut() = StdRng::seed_from_u64(seed));
+}
+
+// the finalizer from splitmix64, which scrambles nearby inputs into unrelated outputs
+// (also used to hash lattice points for noise)
+pub fn mix(x: u64) -> u64 {
+    let x = x.wrapping_add(0x9e3779b97f4a7c15);
+    let x = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    let x = (x ^ (x >> 27)).wrapping_mul(0x94d049bb133111eb);
+    x ^ (x >> 31)
+}
+
+impl RngCore for SamplerRng {
+    fn next_u32(&mut self) -> u32 {
+        SAMPLER.with(|sampler| sampler.borrow_mut().next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        SAMPLER.with(|sampler| sampler.borrow_mut().next_u64())
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        SAMPLER.with(|sampler| sampler.borrow_mut().fill_bytes(dest))
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        SAMPLER.with(|sampler| sampler.borrow_mut().try_fill_bytes(dest))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use super::*;
+
+    #[test]
+    fn same_sample_gives_same_numbers() {
+        seed_for_sample(7, 12, 3);
+        let first: [f32; 4] = rng().gen();
+
+        seed_for_sample(7, 12, 3);
+        let second: [f32; 4] = rng().gen();
+
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn different_samples_give_different_numbers() {
+        seed_for_sample(7, 12, 3);
+        let first: f32 = rng().gen();
+
+        seed_for_sample(7, 12, 4);
+        let second: f32 = rng().gen();
+
+        seed_for_sample(7, 13, 3);
+        let third: f32 = rng().gen();
+
+        assert_ne!(first, second);
+        assert_ne!(first, third);
+    }
+}
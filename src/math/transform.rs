@@ -0,0 +1,197 @@
+use serde::{Deserialize, Serialize};
+
+use super::{mat4::Mat4, quaternion::Quaternion, ray::Ray, vec3::Vec3};
+
+// An affine transformation made of a scale, then a rotation, then a translation.
+// The matrix, its inverse and the inverse's transpose (for normals) are computed up front, since
+// they get used for every ray.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "TransformParts", into = "TransformParts")]
+pub struct Transform {
+    parts: TransformParts,
+    matrix: Mat4,
+    inverse: Mat4,
+    inverse_transpose: Mat4,
+}
+
+// what a transform looks like in a scene file. any of the parts can be left out
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TransformParts {
+    #[serde(default = "zero")]
+    translation: Vec3,
+    #[serde(default = "Quaternion::identity")]
+    rotation: Quaternion,
+    #[serde(default = "one")]
+    scale: Vec3,
+}
+
+fn zero() -> Vec3 {
+    Vec3::new(0.0, 0.0, 0.0)
+}
+
+fn one() -> Vec3 {
+    Vec3::new(1.0, 1.0, 1.0)
+}
+
+impl Transform {
+    // panics if any component of the scale is zero
+    pub fn new(translation: Vec3, rotation: Quaternion, scale: Vec3) -> Transform {
+        Transform::try_from(TransformParts {
+            translation,
+            rotation,
+            scale,
+        })
+        .unwrap()
+    }
+
+    pub fn identity() -> Transform {
+        Transform::new(zero(), Quaternion::identity(), one())
+    }
+
+    pub fn translation(translation: Vec3) -> Transform {
+        Transform::new(translation, Quaternion::identity(), one())
+    }
+
+    pub fn rotation(rotation: Quaternion) -> Transform {
+        Transform::new(zero(), rotation, one())
+    }
+
+    pub fn scale(scale: Vec3) -> Transform {
+        Transform::new(zero(), Quaternion::identity(), scale)
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.matrix
+    }
+
+    pub fn inverse_matrix(&self) -> &Mat4 {
+        &self.inverse
+    }
+
+    pub fn point(&self, p: &Vec3) -> Vec3 {
+        self.matrix.transform_point(p)
+    }
+
+    pub fn vector(&self, v: &Vec3) -> Vec3 {
+        self.matrix.transform_vector(v)
+    }
+
+    // Normals don't transform like other vectors (think of a non-uniform scale), so this uses
+    // the inverse transpose of the matrix instead. The result is normalized.
+    pub fn normal(&self, n: &Vec3) -> Vec3 {
+        self.inverse_transpose.transform_vector(n).normalize()
+    }
+
+    pub fn inverse_point(&self, p: &Vec3) -> Vec3 {
+        self.inverse.transform_point(p)
+    }
+
+    pub fn inverse_vector(&self, v: &Vec3) -> Vec3 {
+        self.inverse.transform_vector(v)
+    }
+
+    // Brings a ray from the outside into the transform's local space. The direction isn't
+    // renormalized, so a point at parameter t along the local ray is the image of the point
+    // at parameter t along the original ray.
+    pub fn ray_to_local(&self, ray: &Ray) -> Ray {
+        Ray::new(
+            self.inverse_point(&ray.origin),
+            self.inverse_vector(&ray.dir),
+        )
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform::identity()
+    }
+}
+
+impl TryFrom<TransformParts> for Transform {
+    type Error = String;
+
+    fn try_from(parts: TransformParts) -> Result<Self, Self::Error> {
+        let matrix = &(&Mat4::translation(&parts.translation)
+            * &parts.rotation.normalize().to_matrix())
+            * &Mat4::scaling(&parts.scale);
+        let inverse = matrix.inverse().ok_or(format!(
+            "transforms can't scale anything down to zero, but the scale is {:?}",
+            parts.scale
+        ))?;
+        let inverse_transpose = inverse.transpose();
+
+        Ok(Transform {
+            parts,
+            matrix,
+            inverse,
+            inverse_transpose,
+        })
+    }
+}
+
+impl From<Transform> for TransformParts {
+    fn from(transform: Transform) -> Self {
+        transform.parts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+
+    fn assert_close(u: &Vec3, v: &Vec3) {
+        assert!((u - v).length() < 1e-5, "{u:?} != {v:?}");
+    }
+
+    #[test]
+    fn scale_then_rotate_then_translate() {
+        let transform = Transform::new(
+            Vec3::new(0.0, 0.0, 5.0),
+            Quaternion::from_axis_angle(&Vec3::new(0.0, 0.0, 1.0), PI / 2.0),
+            Vec3::new(2.0, 1.0, 1.0),
+        );
+
+        let p = transform.point(&Vec3::new(1.0, 0.0, 0.0));
+        assert_close(&p, &Vec3::new(0.0, 2.0, 5.0));
+        assert_close(&transform.inverse_point(&p), &Vec3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn transform_normals_with_inverse_transpose() {
+        // squash the 45 degree plane x + y = 0 along x
+        let transform = Transform::scale(Vec3::new(0.5, 1.0, 1.0));
+        let normal = Vec3::new(1.0, 1.0, 0.0).normalize();
+
+        // the plane becomes 2x + y = 0
+        let expected = Vec3::new(2.0, 1.0, 0.0).normalize();
+        assert_close(&transform.normal(&normal), &expected);
+    }
+
+    #[test]
+    fn keep_ray_parameter_in_local_space() {
+        let transform = Transform::new(
+            Vec3::new(1.0, 2.0, 3.0),
+            Quaternion::from_axis_angle(&Vec3::new(1.0, 1.0, 0.0), 0.4),
+            Vec3::new(3.0, 0.5, 2.0),
+        );
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.2, -0.3, 1.0));
+        let local_ray = transform.ray_to_local(&ray);
+
+        assert_close(&transform.point(&local_ray.at(2.5)), &ray.at(2.5));
+    }
+
+    #[test]
+    fn deserialize_with_defaults() {
+        let yaml = "translation: { x: 1.0, y: 0.0, z: 0.0 }";
+        let transform: Transform = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(transform, Transform::translation(Vec3::new(1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn reject_zero_scale() {
+        let yaml = "scale: { x: 0.0, y: 1.0, z: 1.0 }";
+        assert!(serde_yaml::from_str::<Transform>(yaml).is_err());
+    }
+}
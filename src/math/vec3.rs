@@ -5,6 +5,8 @@ use std::{
     ops::{Add, Mul, Neg, Sub},
 };
 
+use super::sampler;
+
 // TODO: replace 'f32' with a more generic type?
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct Vec3 {
@@ -56,7 +58,7 @@ impl Vec3 {
     // You can prove that vectors generated according to this formula
     // are uniformly distributed on the unit sphere.
     pub fn random_unit_vector() -> Vec3 {
-        let mut rng = rand::thread_rng();
+        let mut rng = sampler::rng();
         // generate two numbers in [0,1)
         let s: f32 = rng.gen();
         let t: f32 = rng.gen();
@@ -77,7 +79,7 @@ impl Vec3 {
 
     pub fn random_subunit_vector() -> Vec3 {
         // take a random unit vector and scale it down by a random amount
-        let mut rng = rand::thread_rng();
+        let mut rng = sampler::rng();
         let unit_vec = Vec3::random_unit_vector();
 
         // generate a number in [0,1)
@@ -100,6 +102,20 @@ impl Vec3 {
         incident - &(2.0 * Vec3::dot(incident, unit_normal) * unit_normal)
     }
 
+    // two unit vectors perpendicular to a unit vector and to each other, ordered so that
+    // their cross product is that vector
+    pub fn tangents(unit_normal: &Vec3) -> (Vec3, Vec3) {
+        // start from whichever axis is further from the normal, so that the projection is stable
+        let axis = if unit_normal.x.abs() > 0.9 {
+            Vec3::new(0.0, 1.0, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let first = (&axis - &(Vec3::dot(&axis, unit_normal) * unit_normal)).normalize();
+        let second = Vec3::cross(unit_normal, &first);
+        (first, second)
+    }
+
     // the refractive index is n_out/n_in, and we assume that the normal is
     // oriented from the volume with refractive index n_out towards the volume with index n_in
     pub fn refract(incident: &Vec3, normal: &Vec3, refractive_index: f32) -> Vec3 {
@@ -108,7 +124,9 @@ impl Vec3 {
 
         // parallel and perpendicular components to the surface
         let refracted_parallel = refractive_index * &(&unit_incident + &(-dot * normal));
-        let refracted_perp = -(1.0 - refracted_parallel.length().powi(2)).sqrt() * normal;
+        // past the critical angle this would be the square root of a negative number,
+        // so clamp to zero and just send the ray along the surface
+        let refracted_perp = -(1.0 - refracted_parallel.length().powi(2)).max(0.0).sqrt() * normal;
         &refracted_parallel + &refracted_perp
     }
 }
@@ -261,6 +279,15 @@ mod tests {
         assert!(n_in * sin_in - n_out * sin_out < 1e-6)
     }
 
+    #[test]
+    fn refract_past_critical_angle_is_finite() {
+        let n = Vec3::new(0.0, 1.0, 0.0);
+        let v = Vec3::new(1.0, -0.1, 0.0).normalize();
+        let refracted = Vec3::refract(&v, &n, 1.5);
+
+        assert!(refracted.x.is_finite() && refracted.y.is_finite() && refracted.z.is_finite());
+    }
+
     #[test]
     fn empty_lin_comb_is_zero() {
         assert_eq!(Vec3::lin_comb(vec![]), Vec3::new(0.0, 0.0, 0.0));
@@ -274,4 +301,18 @@ mod tests {
         let result = Vec3::lin_comb(vec![(1.0, &u), (-2.0, &v), (3.0, &w)]);
         assert_eq!(result, Vec3::new(7.0, -18.0, -13.0));
     }
+
+    #[test]
+    fn tangents_are_orthonormal() {
+        for n in [
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(1.0, -2.0, 3.0).normalize(),
+        ] {
+            let (t, b) = Vec3::tangents(&n);
+            assert!((t.length() - 1.0).abs() < 1e-6);
+            assert!(Vec3::dot(&t, &n).abs() < 1e-6);
+            assert!((&Vec3::cross(&t, &b) - &n).length() < 1e-6);
+        }
+    }
 }
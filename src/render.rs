@@ -1,51 +1,238 @@
+use std::time::{Duration, Instant};
+
 use crate::{
     math::{
         color::{Color, ColorMatrix},
         ray::Ray,
+        sampler,
+        vec3::Vec3,
     },
-    scene::Scene,
+    scene::{object::material::medium, Scene},
 };
 
-pub fn render(
-    scene: Scene,
-    output_width: u32,
-    output_height: u32,
-    samples_per_pixel: u32,
-    bounce_depth: u32,
+use self::{
+    checkpoint::{Checkpoint, CheckpointSchedule},
+    progress::{CancellationToken, RenderObserver, RenderProgress},
+    stats::{NonFiniteSample, PathEnd, PathVertex, RenderStats},
+};
+
+pub mod checkpoint;
+pub mod progress;
+pub mod stats;
+
+// the side length (in pixels) of the square tiles that the image is rendered in
+const TILE_SIZE: u32 = 32;
+
+#[derive(Debug, Clone)]
+pub struct RenderSettings {
+    pub output_width: u32,
+    pub output_height: u32,
+    pub samples_per_pixel: u32,
+    pub bounce_depth: u32,
+
+    // every sample is generated from this, so rendering the same scene
+    // with the same settings and seed always gives the same image
+    pub seed: u64,
+
+    // stop rendering once this much time has passed, keeping whatever samples were computed
+    pub time_budget: Option<Duration>,
+
+    // periodically save the render in progress, so that it can be resumed if interrupted
+    pub checkpoint: Option<CheckpointSchedule>,
+
+    // scale down samples brighter than this, trading a little energy for fewer fireflies
+    pub sample_clamp: Option<f32>,
+}
+
+impl RenderSettings {
+    pub fn new(
+        output_width: u32,
+        output_height: u32,
+        samples_per_pixel: u32,
+        bounce_depth: u32,
+    ) -> RenderSettings {
+        RenderSettings {
+            output_width,
+            output_height,
+            samples_per_pixel,
+            bounce_depth,
+            seed: 0,
+            time_budget: None,
+            checkpoint: None,
+            sample_clamp: None,
+        }
+    }
+}
+
+pub fn render(scene: &Scene, settings: &RenderSettings) -> ColorMatrix {
+    render_with_progress(
+        scene,
+        settings,
+        &mut |_: &RenderProgress| {},
+        &CancellationToken::new(),
+    )
+}
+
+pub fn render_with_progress(
+    scene: &Scene,
+    settings: &RenderSettings,
+    observer: &mut dyn RenderObserver,
+    cancel: &CancellationToken,
 ) -> ColorMatrix {
-    // create output
-    let mut color_mat = ColorMatrix::new(output_width as usize, output_height as usize);
-
-    // compute pixel values
-    for pixel_x in 0..output_width {
-        for pixel_y in 0..output_height {
-            let mut accumulated_color = Color::from_rgb_u8(0, 0, 0);
-            for _ in 0..samples_per_pixel {
+    let mut checkpoint =
+        Checkpoint::new(settings.seed, settings.output_width, settings.output_height);
+    resume_render(scene, settings, &mut checkpoint, observer, cancel);
+    checkpoint.image()
+}
+
+// Adds samples to a (possibly empty) render until every pixel has `settings.samples_per_pixel`,
+// returning statistics about the work that was done.
+// The samples are seeded from the checkpoint's seed rather than the one in `settings`, so that
+// resuming gives exactly the same result as a render that was never interrupted.
+//
+// The image is rendered in passes of one sample per pixel, each pass going tile by tile.
+// That way, if the render is cancelled (or runs out of time), every pixel has roughly the
+// same number of samples and the checkpoint holds the best image we could make so far.
+pub fn resume_render(
+    scene: &Scene,
+    settings: &RenderSettings,
+    checkpoint: &mut Checkpoint,
+    observer: &mut dyn RenderObserver,
+    cancel: &CancellationToken,
+) -> RenderStats {
+    assert!(
+        checkpoint.width() == settings.output_width
+            && checkpoint.height() == settings.output_height,
+        "checkpoint dimensions don't match the render settings"
+    );
+
+    // start counting from scratch (in case an earlier render on this thread left anything behind)
+    stats::take();
+
+    let start = Instant::now();
+    let mut last_saved = start;
+    let tiles = Tile::cover(settings.output_width, settings.output_height);
+    let first_pass = checkpoint
+        .samples_per_pixel()
+        .min(settings.samples_per_pixel);
+
+    // only count the work left to do, so that the rates and ETA make sense when resuming
+    let samples_total = (0..settings.output_height)
+        .flat_map(|y| (0..settings.output_width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            settings
+                .samples_per_pixel
+                .saturating_sub(checkpoint.sample_count(x, y))
+        })
+        .map(u64::from)
+        .sum();
+    let mut progress = RenderProgress {
+        tiles_completed: 0,
+        tiles_total: tiles.len() as u32 * (settings.samples_per_pixel - first_pass),
+        samples_completed: 0,
+        samples_total,
+        rays_traced: 0,
+        elapsed: Duration::ZERO,
+    };
+
+    // reused for every sample, so that bad samples can be traced back to where they came from
+    let mut path = Vec::with_capacity(settings.bounce_depth as usize);
+    let mut media = vec![];
+
+    'passes: for pass in first_pass..settings.samples_per_pixel {
+        for tile in tiles.iter() {
+            let out_of_time = settings
+                .time_budget
+                .is_some_and(|budget| start.elapsed() >= budget);
+            if cancel.is_cancelled() || out_of_time {
+                break 'passes;
+            }
+
+            for (pixel_x, pixel_y) in tile.pixels() {
+                // this pixel already got this pass's sample before the render was interrupted
+                if checkpoint.sample_count(pixel_x, pixel_y) > pass {
+                    continue;
+                }
+
+                let pixel_index = (pixel_y * settings.output_width + pixel_x) as u64;
+                sampler::seed_for_sample(checkpoint.seed(), pixel_index, pass);
+
                 let ray = scene.camera.ray_for_pixel(pixel_x, pixel_y);
+                stats::record(|stats| stats.camera_rays += 1);
+
+                path.clear();
+                media.clear();
+                let color =
+                    color_for_ray(scene, &ray, settings.bounce_depth, &mut path, &mut media);
 
-                accumulated_color = &accumulated_color + &color_for_ray(&scene, &ray, bounce_depth);
+                let color = if color.is_finite() {
+                    Some(clamp_sample(color, settings.sample_clamp))
+                } else {
+                    stats::record_non_finite_sample(NonFiniteSample {
+                        pixel_x,
+                        pixel_y,
+                        sample_index: pass,
+                        path: path.clone(),
+                    });
+                    // Stand in the pixel's average so far, rather than letting this poison it.
+                    // Without any samples yet there's no average, and black would bias the pixel
+                    // for good, so then it's just left out.
+                    (checkpoint.sample_count(pixel_x, pixel_y) > 0)
+                        .then(|| checkpoint.average(pixel_x, pixel_y))
+                };
+                if let Some(color) = color {
+                    checkpoint.add_sample(pixel_x, pixel_y, &color);
+                }
+                progress.samples_completed += 1;
             }
 
-            // gamma correction -- move to a post processing module at some point
-            let avg_color = (1.0 / samples_per_pixel as f32) * &accumulated_color;
-            // probably also implement a color exponential function
-            let exponent = 1.0 / 2.2;
-            let corrected_color = Color::from_rgb_f32(
-                avg_color.r().powf(exponent),
-                avg_color.g().powf(exponent),
-                avg_color.b().powf(exponent),
-            );
-
-            let mat_entry = color_mat.at_mut(pixel_y as usize, pixel_x as usize);
-            *mat_entry = corrected_color;
+            progress.tiles_completed += 1;
+            progress.rays_traced = stats::rays_traced();
+            progress.elapsed = start.elapsed();
+            observer.on_progress(&progress);
+
+            if let Some(schedule) = &settings.checkpoint {
+                if last_saved.elapsed() >= schedule.interval {
+                    save_checkpoint(checkpoint, schedule);
+                    last_saved = Instant::now();
+                }
+            }
         }
     }
 
-    color_mat
+    // save once more, so that a finished (or cancelled) render can be extended later
+    if let Some(schedule) = &settings.checkpoint {
+        save_checkpoint(checkpoint, schedule);
+    }
+
+    let mut stats = stats::take();
+    stats.elapsed = start.elapsed();
+    stats
 }
 
-fn color_for_ray(scene: &Scene, ray: &Ray, bounce_depth: u32) -> Color {
+// a failed save shouldn't throw away the render itself, so just complain about it
+fn save_checkpoint(checkpoint: &Checkpoint, schedule: &CheckpointSchedule) {
+    if let Err(err) = checkpoint.save(&schedule.path) {
+        eprintln!(
+            "could not save checkpoint to {}: {err}",
+            schedule.path.display()
+        );
+    }
+}
+
+// `path` holds the surfaces that were hit before this ray, and gets the rest of them added to it.
+// `media` holds the absorption coefficients of the objects that the ray is inside of, innermost
+// last: rays going into an object through its surface push its material's absorption, and rays
+// coming out pop it.
+fn color_for_ray(
+    scene: &Scene,
+    ray: &Ray,
+    bounce_depth: u32,
+    path: &mut Vec<PathVertex>,
+    media: &mut Vec<Color>,
+) -> Color {
     if bounce_depth == 0 {
+        stats::record_path_end(PathEnd::Truncated, path.len());
         return Color::from_rgb_u8(0, 0, 0);
     }
 
@@ -53,16 +240,400 @@ fn color_for_ray(scene: &Scene, ray: &Ray, bounce_depth: u32) -> Color {
 
     match closest_intersection {
         Some((ref intersection, object)) => {
-            match object.material.scatter_ray(ray, intersection) {
+            let scattered = object.material.scatter_ray(ray, intersection);
+            path.push(PathVertex {
+                geometry: object.geometry.typetag_name(),
+                material: object.material.typetag_name(),
+                point: intersection.point.clone(),
+                scattered_dir: scattered.as_ref().map(|(ray, _)| ray.dir.clone()),
+                attenuation: scattered.as_ref().map(|(_, color)| color.clone()),
+            });
+
+            // light is absorbed along the way here from the surface it came from
+            let transmittance = match media.last() {
+                Some(absorption) => {
+                    medium::transmittance(absorption, intersection.t * ray.dir.length())
+                }
+                None => Color::from_rgb_f32(1.0, 1.0, 1.0),
+            };
+
+            match scattered {
                 Some((scattered_ray, reflection_color)) => {
-                    reflection_color * &color_for_ray(scene, &scattered_ray, bounce_depth - 1)
+                    stats::record(|stats| stats.scattered_rays += 1);
+
+                    // (rays that carry on to the other side of the surface went through it)
+                    let went_through = Vec3::dot(&ray.dir, &intersection.normal)
+                        * Vec3::dot(&scattered_ray.dir, &intersection.normal)
+                        > 0.0;
+                    if went_through && intersection.is_into_surface {
+                        let absorption = object.material.absorption(intersection);
+                        media.push(absorption.unwrap_or(Color::from_rgb_f32(0.0, 0.0, 0.0)));
+                    } else if went_through {
+                        media.pop();
+                    }
+
+                    &(&transmittance * &reflection_color)
+                        * &color_for_ray(scene, &scattered_ray, bounce_depth - 1, path, media)
                 }
                 // The scattering algorithm decided to absorb the ray, so return black
-                None => Color::from_rgb_u8(0, 0, 0),
+                None => {
+                    stats::record_path_end(PathEnd::Absorbed, path.len());
+                    Color::from_rgb_u8(0, 0, 0)
+                }
             }
         }
         // No intersections, so query the sky for a color
+        // (rays can't get out of closed objects without hitting them, so the sky isn't absorbed)
         // TODO: scene need to expose sky so this can be called from render
-        None => scene.sky.sky_color_for_direction(&ray.dir),
+        None => {
+            stats::record_path_end(PathEnd::Escaped, path.len());
+            scene.sky.sky_color_for_direction(&ray.dir)
+        }
+    }
+}
+
+// scales a sample down so that none of its channels are brighter than `max`, keeping its hue
+fn clamp_sample(color: Color, max: Option<f32>) -> Color {
+    let brightest = color.r().max(color.g()).max(color.b());
+    match max {
+        Some(max) if brightest > max => (max / brightest) * &color,
+        _ => color,
+    }
+}
+
+// a rectangular block of pixels, from (x0, y0) up to but not including (x1, y1)
+struct Tile {
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+}
+
+impl Tile {
+    // splits an image into tiles, going row by row from the top left
+    fn cover(width: u32, height: u32) -> Vec<Tile> {
+        let mut tiles = vec![];
+        for y0 in (0..height).step_by(TILE_SIZE as usize) {
+            for x0 in (0..width).step_by(TILE_SIZE as usize) {
+                tiles.push(Tile {
+                    x0,
+                    y0,
+                    x1: (x0 + TILE_SIZE).min(width),
+                    y1: (y0 + TILE_SIZE).min(height),
+                });
+            }
+        }
+        tiles
+    }
+
+    fn pixels(&self) -> impl Iterator<Item = (u32, u32)> + '_ {
+        (self.y0..self.y1).flat_map(move |y| (self.x0..self.x1).map(move |x| (x, y)))
+    }
+
+    #[cfg(test)]
+    fn pixel_count(&self) -> u64 {
+        (self.x1 - self.x0) as u64 * (self.y1 - self.y0) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{
+        camera::Camera,
+        math::vec3::Vec3,
+        scene::{
+            object::{
+                geometry::{plane::Plane, sphere::Sphere},
+                material::{
+                    lambertian::Lambertian, medium::Absorption, metal::Metal,
+                    translucent::Translucent,
+                },
+                Object,
+            },
+            registry::Registry,
+            sky::Sky,
+            Scene,
+        },
+    };
+
+    fn empty_scene(width: u32, height: u32) -> Scene {
+        let camera = Camera::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            60.0,
+            1.0,
+            0.0,
+            width,
+            height,
+        );
+        let sky_color = Color::from_rgb_f32(0.25, 0.25, 0.25);
+        let sky = Sky::new(sky_color.clone(), sky_color);
+
+        Scene::builder()
+            .camera(camera)
+            .registry(Registry::new())
+            .sky(sky)
+            .build()
+            .unwrap()
+    }
+
+    // something with enough scattering that every sample is different
+    fn noisy_scene(width: u32, height: u32) -> Scene {
+        let mut scene = Scene::builder();
+        scene.registry(Registry::new());
+        scene.add_object(Object {
+            geometry: Arc::new(Sphere::new(1.0, Vec3::new(0.0, 1.0, -3.0))),
+            material: Arc::new(Metal::new(Color::from_rgb_f32(0.8, 0.6, 0.2), 0.5)),
+        });
+        scene.add_object(Object {
+            geometry: Arc::new(Plane::new(
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+            )),
+            material: Arc::new(Lambertian::new(Color::from_rgb_f32(0.5, 0.5, 0.5))),
+        });
+        scene.camera(Camera::new(
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 0.8, -3.0),
+            90.0,
+            3.0,
+            0.1,
+            width,
+            height,
+        ));
+        let sky = Sky::new(
+            Color::from_rgb_f32(1.0, 1.0, 1.0),
+            Color::from_rgb_f32(0.3, 0.5, 1.0),
+        );
+        scene.sky(sky);
+
+        scene.build().unwrap()
+    }
+
+    #[test]
+    fn tiles_cover_image() {
+        let tiles = Tile::cover(70, 40);
+        assert_eq!(tiles.len(), 6);
+        assert_eq!(tiles.iter().map(Tile::pixel_count).sum::<u64>(), 70 * 40);
+    }
+
+    #[test]
+    fn render_sky_and_report_progress() {
+        let scene = empty_scene(40, 10);
+        let settings = RenderSettings::new(40, 10, 2, 5);
+
+        let mut reports = vec![];
+        let mut observer = |progress: &RenderProgress| reports.push(progress.clone());
+        let color_mat =
+            render_with_progress(&scene, &settings, &mut observer, &CancellationToken::new());
+
+        let expected = 0.25f32.powf(1.0 / 2.2);
+        assert_eq!(
+            *color_mat.at(9, 39),
+            Color::from_rgb_f32(expected, expected, expected)
+        );
+
+        let last = reports.last().unwrap();
+        assert_eq!(reports.len(), 4);
+        assert_eq!(last.tiles_completed, last.tiles_total);
+        assert_eq!(last.samples_completed, 800);
+        assert_eq!(last.rays_traced, 800);
+    }
+
+    #[test]
+    fn cancelled_render_stops_early() {
+        let scene = empty_scene(4, 4);
+        let settings = RenderSettings::new(4, 4, 100, 5);
+        let cancel = CancellationToken::new();
+
+        let mut passes = 0;
+        let mut observer = |_: &RenderProgress| {
+            passes += 1;
+            if passes == 3 {
+                cancel.cancel();
+            }
+        };
+        let color_mat = render_with_progress(&scene, &settings, &mut observer, &cancel.clone());
+
+        // the image is still usable, since every pixel got three samples
+        assert_eq!(passes, 3);
+        assert_ne!(*color_mat.at(3, 3), Color::from_rgb_u8(0, 0, 0));
+    }
+
+    #[test]
+    fn resumed_render_matches_uninterrupted_render() {
+        let scene = noisy_scene(40, 10);
+        let settings = RenderSettings::new(40, 10, 3, 5);
+        let uninterrupted = render(&scene, &settings);
+
+        // stop halfway through the second pass, so that pixels have different sample counts
+        let mut checkpoint = Checkpoint::new(settings.seed, 40, 10);
+        let cancel = CancellationToken::new();
+        let mut tiles = 0;
+        let mut observer = |_: &RenderProgress| {
+            tiles += 1;
+            if tiles == 3 {
+                cancel.cancel();
+            }
+        };
+        resume_render(
+            &scene,
+            &settings,
+            &mut checkpoint,
+            &mut observer,
+            &cancel.clone(),
+        );
+        assert_eq!(checkpoint.samples_per_pixel(), 1);
+        assert_eq!(checkpoint.sample_count(0, 0), 2);
+        assert_eq!(checkpoint.sample_count(39, 9), 1);
+
+        let mut no_op = |_: &RenderProgress| {};
+        resume_render(
+            &scene,
+            &settings,
+            &mut checkpoint,
+            &mut no_op,
+            &CancellationToken::new(),
+        );
+
+        assert_eq!(checkpoint.image(), uninterrupted);
+    }
+
+    #[test]
+    fn add_samples_to_finished_render() {
+        let scene = noisy_scene(8, 8);
+        let mut settings = RenderSettings::new(8, 8, 4, 5);
+        let four_samples = render(&scene, &settings);
+
+        settings.samples_per_pixel = 2;
+        let mut checkpoint = Checkpoint::new(settings.seed, 8, 8);
+        let mut no_op = |_: &RenderProgress| {};
+        let cancel = CancellationToken::new();
+        resume_render(&scene, &settings, &mut checkpoint, &mut no_op, &cancel);
+        assert_ne!(checkpoint.image(), four_samples);
+
+        settings.samples_per_pixel = 4;
+        resume_render(&scene, &settings, &mut checkpoint, &mut no_op, &cancel);
+        assert_eq!(checkpoint.image(), four_samples);
+    }
+
+    #[test]
+    fn collect_stats() {
+        let scene = noisy_scene(8, 8);
+        let settings = RenderSettings::new(8, 8, 2, 5);
+        let mut checkpoint = Checkpoint::new(settings.seed, 8, 8);
+        let mut no_op = |_: &RenderProgress| {};
+        let stats = resume_render(
+            &scene,
+            &settings,
+            &mut checkpoint,
+            &mut no_op,
+            &CancellationToken::new(),
+        );
+
+        // every camera ray starts exactly one path, and every ray that's traced (ie. all of them
+        // except the ones scattered on the last bounce) gets tested against both objects
+        let rays = stats.camera_rays + stats.scattered_rays - stats.truncated_paths;
+        assert_eq!(stats.camera_rays, 128);
+        assert_eq!(stats.total_paths(), 128);
+        assert_eq!(stats.path_lengths.iter().sum::<u64>(), 128);
+        assert_eq!(stats.intersection_tests["Sphere"], rays);
+        assert_eq!(stats.intersection_tests["Plane"], rays);
+        assert_eq!(stats.non_finite_samples, 0);
+    }
+
+    #[test]
+    fn replace_and_report_non_finite_samples() {
+        let mut scene = Scene::builder();
+        scene.registry(Registry::new());
+        scene.add_object(Object {
+            geometry: Arc::new(Sphere::new(1.0, Vec3::new(0.0, 0.0, -3.0))),
+            material: Arc::new(Lambertian::new(Color::from_rgb_f32(f32::NAN, 0.5, 0.5))),
+        });
+        scene.camera(Camera::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            10.0,
+            1.0,
+            0.0,
+            4,
+            4,
+        ));
+        let sky_color = Color::from_rgb_f32(0.25, 0.25, 0.25);
+        scene.sky(Sky::new(sky_color.clone(), sky_color));
+        let scene = scene.build().unwrap();
+
+        let settings = RenderSettings::new(4, 4, 2, 5);
+        let mut checkpoint = Checkpoint::new(settings.seed, 4, 4);
+        let mut no_op = |_: &RenderProgress| {};
+        let stats = resume_render(
+            &scene,
+            &settings,
+            &mut checkpoint,
+            &mut no_op,
+            &CancellationToken::new(),
+        );
+
+        assert_eq!(stats.non_finite_samples, 32);
+        assert!(checkpoint.image().at(1, 1).is_finite());
+
+        // with nothing to stand in for them, they're left out rather than counted as black
+        assert_eq!(checkpoint.sample_count(1, 1), 0);
+
+        let report = &stats.non_finite_reports[0];
+        assert_eq!(
+            (report.pixel_x, report.pixel_y, report.sample_index),
+            (0, 0, 0)
+        );
+        assert_eq!(report.path[0].geometry, "Sphere");
+        assert_eq!(report.path[0].material, "Lambertian");
+    }
+
+    #[test]
+    fn clamp_bright_samples() {
+        let color = Color::from_rgb_f32(8.0, 2.0, 1.0);
+        assert_eq!(
+            clamp_sample(color.clone(), Some(4.0)),
+            Color::from_rgb_f32(4.0, 1.0, 0.5)
+        );
+        assert_eq!(clamp_sample(color.clone(), Some(10.0)), color);
+        assert_eq!(clamp_sample(color.clone(), None), color);
+    }
+
+    #[test]
+    fn colored_glass_absorbs_along_the_way_through() {
+        // a ball of "glass" that doesn't bend light, seen through the middle against a white sky
+        let mut scene = Scene::builder();
+        scene.registry(Registry::new());
+        let glass = Translucent::new(1.0)
+            .with_absorption(Absorption::Coefficient(Color::from_rgb_f32(0.5, 0.0, 1.0)));
+        scene.add_object(Object {
+            geometry: Arc::new(Sphere::new(1.0, Vec3::new(0.0, 0.0, -3.0))),
+            material: Arc::new(glass),
+        });
+        scene.camera(Camera::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+            0.1,
+            1.0,
+            0.0,
+            1,
+            1,
+        ));
+        let white = Color::from_rgb_f32(1.0, 1.0, 1.0);
+        scene.sky(Sky::new(white.clone(), white));
+        let scene = scene.build().unwrap();
+
+        let color_mat = render(&scene, &RenderSettings::new(1, 1, 4, 5));
+
+        // two units through the inside (and then gamma corrected)
+        let expected = [(-1.0f32).exp(), 1.0, (-2.0f32).exp()].map(|c| c.powf(1.0 / 2.2));
+        let color = color_mat.at(0, 0);
+        assert!((color.r() - expected[0]).abs() < 1e-3, "{color:?}");
+        assert!((color.g() - expected[1]).abs() < 1e-3, "{color:?}");
+        assert!((color.b() - expected[2]).abs() < 1e-3, "{color:?}");
     }
 }
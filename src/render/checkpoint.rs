@@ -0,0 +1,275 @@
+use std::{
+    fs,
+    io::{self, BufWriter, ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::math::color::{Color, ColorMatrix};
+
+// Everything needed to pick a render back up where it left off: the running per-pixel sums of
+// samples, how many samples each pixel has, and the seed that the samples were generated from.
+// (Since every sample is seeded from the render seed, the pixel and the sample's index, that's
+// all of the sampler state there is.)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    seed: u64,
+    width: usize,
+    sums: Vec<Color>,
+    counts: Vec<u32>,
+}
+
+// where and how often a render in progress should be saved
+#[derive(Debug, Clone)]
+pub struct CheckpointSchedule {
+    pub path: PathBuf,
+    pub interval: Duration,
+}
+
+// identifies checkpoint files, along with a version number for the layout
+const MAGIC: &[u8; 8] = b"RAYSCKPT";
+const VERSION: u32 = 1;
+// the r, g and b sums and the sample count
+const PIXEL_BYTES: u64 = 16;
+
+impl Checkpoint {
+    pub fn new(seed: u64, width: u32, height: u32) -> Checkpoint {
+        let len = width as usize * height as usize;
+        Checkpoint {
+            seed,
+            width: width as usize,
+            sums: vec![Color::from_rgb_u8(0, 0, 0); len],
+            counts: vec![0; len],
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width as u32
+    }
+
+    pub fn height(&self) -> u32 {
+        (self.sums.len() / self.width) as u32
+    }
+
+    // the number of samples that every pixel has
+    pub fn samples_per_pixel(&self) -> u32 {
+        self.counts.iter().copied().min().unwrap_or(0)
+    }
+
+    pub fn sample_count(&self, pixel_x: u32, pixel_y: u32) -> u32 {
+        self.counts[self.index(pixel_x, pixel_y)]
+    }
+
+    // the mean of the samples in a pixel so far, before gamma correction
+    pub fn average(&self, pixel_x: u32, pixel_y: u32) -> Color {
+        let index = self.index(pixel_x, pixel_y);
+        match self.counts[index] {
+            0 => Color::from_rgb_u8(0, 0, 0),
+            count => (1.0 / count as f32) * &self.sums[index],
+        }
+    }
+
+    pub fn add_sample(&mut self, pixel_x: u32, pixel_y: u32, color: &Color) {
+        let index = self.index(pixel_x, pixel_y);
+        self.sums[index] = &self.sums[index] + color;
+        self.counts[index] += 1;
+    }
+
+    fn index(&self, pixel_x: u32, pixel_y: u32) -> usize {
+        pixel_y as usize * self.width + pixel_x as usize
+    }
+
+    // averages the samples in each pixel and gamma corrects the result
+    pub fn image(&self) -> ColorMatrix {
+        let mut color_mat = ColorMatrix::new(self.width, self.height() as usize);
+
+        for (index, (sum, count)) in self.sums.iter().zip(self.counts.iter()).enumerate() {
+            if *count == 0 {
+                continue;
+            }
+
+            // gamma correction -- move to a post processing module at some point
+            let avg_color = (1.0 / *count as f32) * sum;
+            // probably also implement a color exponential function
+            let exponent = 1.0 / 2.2;
+            let corrected_color = Color::from_rgb_f32(
+                avg_color.r().powf(exponent),
+                avg_color.g().powf(exponent),
+                avg_color.b().powf(exponent),
+            );
+
+            *color_mat.at_mut(index / self.width, index % self.width) = corrected_color;
+        }
+
+        color_mat
+    }
+
+    // Layout (all little endian): the magic bytes and version, then the seed (u64),
+    // width and height (u32 each), then for every pixel its r, g and b sums (f32 each)
+    // followed by its sample count (u32).
+    //
+    // The file is written next to its destination and then moved into place, so that an
+    // interrupted save never clobbers the previous checkpoint.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let temp_path = path.with_extension("partial");
+        {
+            let mut writer = BufWriter::new(fs::File::create(&temp_path)?);
+            writer.write_all(MAGIC)?;
+            writer.write_all(&VERSION.to_le_bytes())?;
+            writer.write_all(&self.seed.to_le_bytes())?;
+            writer.write_all(&self.width().to_le_bytes())?;
+            writer.write_all(&self.height().to_le_bytes())?;
+
+            for (sum, count) in self.sums.iter().zip(self.counts.iter()) {
+                writer.write_all(&sum.r().to_le_bytes())?;
+                writer.write_all(&sum.g().to_le_bytes())?;
+                writer.write_all(&sum.b().to_le_bytes())?;
+                writer.write_all(&count.to_le_bytes())?;
+            }
+            writer.flush()?;
+        }
+        fs::rename(temp_path, path)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Checkpoint> {
+        let bytes = fs::read(path)?;
+        let mut reader = ByteReader(&bytes);
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid_data("not a checkpoint file"));
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(invalid_data(&format!(
+                "unsupported checkpoint version {version}"
+            )));
+        }
+
+        let seed = reader.u64()?;
+        let width = reader.u32()?;
+        let height = reader.u32()?;
+        if width == 0 || height == 0 {
+            return Err(invalid_data("checkpoint has no pixels"));
+        }
+
+        // check that the pixels are all there before allocating room for them, since a
+        // corrupt header could ask for any amount
+        let expected = (width as u64 * height as u64).checked_mul(PIXEL_BYTES);
+        if expected.is_none_or(|expected| (reader.0.len() as u64) < expected) {
+            return Err(invalid_data("checkpoint file is truncated"));
+        }
+
+        let mut checkpoint = Checkpoint::new(seed, width, height);
+        for index in 0..checkpoint.sums.len() {
+            let (r, g, b) = (reader.f32()?, reader.f32()?, reader.f32()?);
+            checkpoint.sums[index] = Color::from_rgb_f32(r, g, b);
+            checkpoint.counts[index] = reader.u32()?;
+        }
+
+        Ok(checkpoint)
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+struct ByteReader<'a>(&'a [u8]);
+
+impl<'a> ByteReader<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        if self.0.len() < len {
+            return Err(invalid_data("checkpoint file is truncated"));
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> io::Result<f32> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::*;
+
+    #[test]
+    fn average_samples() {
+        let mut checkpoint = Checkpoint::new(0, 2, 1);
+        checkpoint.add_sample(1, 0, &Color::from_rgb_f32(0.2, 0.0, 1.0));
+        checkpoint.add_sample(1, 0, &Color::from_rgb_f32(0.0, 0.0, 1.0));
+
+        assert_eq!(checkpoint.samples_per_pixel(), 0);
+        assert_eq!(checkpoint.sample_count(1, 0), 2);
+        assert_eq!(
+            *checkpoint.image().at(0, 1),
+            Color::from_rgb_f32(0.1f32.powf(1.0 / 2.2), 0.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn save_and_load() {
+        let mut checkpoint = Checkpoint::new(42, 3, 2);
+        checkpoint.add_sample(0, 0, &Color::from_rgb_f32(0.1, 0.2, 0.3));
+        checkpoint.add_sample(2, 1, &Color::from_rgb_f32(1.0 / 3.0, 0.5, 0.7));
+        checkpoint.add_sample(2, 1, &Color::from_rgb_f32(0.9, 0.8, 0.7));
+
+        let path = env::temp_dir().join("rays_checkpoint_save_and_load.ckpt");
+        checkpoint.save(&path).unwrap();
+        let loaded = Checkpoint::load(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded, checkpoint);
+    }
+
+    #[test]
+    fn reject_other_files() {
+        let path = env::temp_dir().join("rays_checkpoint_reject_other_files.ckpt");
+        fs::write(&path, b"definitely not a checkpoint").unwrap();
+        let result = Checkpoint::load(&path);
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn reject_bad_dimensions() {
+        let header = |width: u32, height: u32| {
+            let mut bytes = MAGIC.to_vec();
+            bytes.extend(VERSION.to_le_bytes());
+            bytes.extend(0u64.to_le_bytes());
+            bytes.extend(width.to_le_bytes());
+            bytes.extend(height.to_le_bytes());
+            bytes
+        };
+
+        // no pixels at all, and far more pixels than there are in the file
+        for (name, bytes) in [
+            ("empty", header(0, 5)),
+            ("huge", header(u32::MAX, u32::MAX)),
+        ] {
+            let path = env::temp_dir().join(format!("rays_checkpoint_reject_{name}.ckpt"));
+            fs::write(&path, bytes).unwrap();
+            let result = Checkpoint::load(&path);
+            fs::remove_file(&path).unwrap();
+
+            assert_eq!(result.unwrap_err().kind(), ErrorKind::InvalidData);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+// a snapshot of how far along a render is
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderProgress {
+    // the image is rendered one sample per pixel at a time, so each tile
+    // is counted once for every sample pass it's rendered in
+    pub tiles_completed: u32,
+    pub tiles_total: u32,
+    pub samples_completed: u64,
+    pub samples_total: u64,
+    pub rays_traced: u64,
+    pub elapsed: Duration,
+}
+
+impl RenderProgress {
+    pub fn fraction_complete(&self) -> f32 {
+        if self.samples_total == 0 {
+            1.0
+        } else {
+            self.samples_completed as f32 / self.samples_total as f32
+        }
+    }
+
+    pub fn rays_per_second(&self) -> f32 {
+        let seconds = self.elapsed.as_secs_f32();
+        if seconds == 0.0 {
+            0.0
+        } else {
+            self.rays_traced as f32 / seconds
+        }
+    }
+
+    // estimated time remaining, assuming samples keep being computed at the same rate
+    pub fn eta(&self) -> Option<Duration> {
+        if self.samples_completed == 0 {
+            return None;
+        }
+
+        let remaining = self.samples_total.saturating_sub(self.samples_completed);
+        Some(
+            self.elapsed
+                .mul_f64(remaining as f64 / self.samples_completed as f64),
+        )
+    }
+}
+
+impl fmt::Display for RenderProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>3.0}% ({}/{} tiles) | {:.2}M rays/s | ",
+            100.0 * self.fraction_complete(),
+            self.tiles_completed,
+            self.tiles_total,
+            self.rays_per_second() / 1e6,
+        )?;
+
+        match self.eta() {
+            Some(eta) => write!(f, "ETA {}", format_duration(eta)),
+            None => write!(f, "ETA --:--"),
+        }
+    }
+}
+
+// formats as m:ss, or h:mm:ss for long durations
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, (seconds / 60) % 60, seconds % 60);
+
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+// gets notified by the renderer every time a tile is finished
+pub trait RenderObserver {
+    fn on_progress(&mut self, progress: &RenderProgress);
+}
+
+impl<F: FnMut(&RenderProgress)> RenderObserver for F {
+    fn on_progress(&mut self, progress: &RenderProgress) {
+        self(progress)
+    }
+}
+
+// a cheaply clonable flag that can be used to stop a render from another thread
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn progress(samples_completed: u64, elapsed_secs: u64) -> RenderProgress {
+        RenderProgress {
+            tiles_completed: 1,
+            tiles_total: 4,
+            samples_completed,
+            samples_total: 100,
+            rays_traced: 500,
+            elapsed: Duration::from_secs(elapsed_secs),
+        }
+    }
+
+    #[test]
+    fn compute_rates_and_eta() {
+        let progress = progress(25, 10);
+        assert_eq!(progress.fraction_complete(), 0.25);
+        assert_eq!(progress.rays_per_second(), 50.0);
+        assert_eq!(progress.eta(), Some(Duration::from_secs(30)));
+    }
+
+    #[test]
+    fn no_eta_before_first_sample() {
+        assert_eq!(progress(0, 0).eta(), None);
+        assert_eq!(progress(0, 0).rays_per_second(), 0.0);
+    }
+
+    #[test]
+    fn format_durations() {
+        assert_eq!(format_duration(Duration::from_secs(75)), "1:15");
+        assert_eq!(format_duration(Duration::from_secs(3725)), "1:02:05");
+    }
+
+    #[test]
+    fn cancel_from_a_clone() {
+        let token = CancellationToken::new();
+        let clone = token.clone();
+        assert!(!token.is_cancelled());
+
+        clone.cancel();
+        assert!(token.is_cancelled());
+    }
+}
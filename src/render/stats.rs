@@ -0,0 +1,252 @@
+use std::{cell::RefCell, collections::BTreeMap, fmt, time::Duration};
+
+use crate::math::{color::Color, vec3::Vec3};
+
+use serde::{Serialize, Serializer};
+
+// Counters describing where the work in a render went. These get collected in a thread local
+// while rendering (so that eg. `Scene::intersect_ray` can record what it does without having
+// to pass anything around), and the renderer hands back the totals when it's done.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct RenderStats {
+    pub camera_rays: u64,
+    pub scattered_rays: u64,
+
+    // keyed by the name of the geometry type that was tested against
+    pub intersection_tests: BTreeMap<&'static str, u64>,
+
+    // `path_lengths[n]` is the number of paths that ended after hitting n surfaces
+    pub path_lengths: Vec<u64>,
+    pub escaped_paths: u64,
+    pub absorbed_paths: u64,
+    // paths that were still going when they ran out of bounces
+    pub truncated_paths: u64,
+
+    // samples that came out as NaN or infinite, along with
+    // the first few of them so they can be investigated
+    pub non_finite_samples: u64,
+    pub non_finite_reports: Vec<NonFiniteSample>,
+
+    #[serde(rename = "elapsed_seconds", serialize_with = "serialize_seconds")]
+    pub elapsed: Duration,
+}
+
+fn serialize_seconds<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_f64(duration.as_secs_f64())
+}
+
+// how many non-finite samples get reported in detail
+const MAX_NON_FINITE_REPORTS: usize = 10;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NonFiniteSample {
+    pub pixel_x: u32,
+    pub pixel_y: u32,
+    pub sample_index: u32,
+    pub path: Vec<PathVertex>,
+}
+
+// a surface that a path hit, and what the material did with it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PathVertex {
+    pub geometry: &'static str,
+    pub material: &'static str,
+    pub point: Vec3,
+    // both `None` if the material absorbed the path
+    pub scattered_dir: Option<Vec3>,
+    pub attenuation: Option<Color>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum PathEnd {
+    // the path reached the sky
+    Escaped,
+    // a material absorbed the path
+    Absorbed,
+    // the path hit the bounce limit
+    Truncated,
+}
+
+thread_local! {
+    static STATS: RefCell<RenderStats> = RefCell::new(RenderStats::default());
+}
+
+pub fn record(f: impl FnOnce(&mut RenderStats)) {
+    STATS.with(|stats| f(&mut stats.borrow_mut()))
+}
+
+pub fn record_intersection_test(geometry_name: &'static str) {
+    record(|stats| *stats.intersection_tests.entry(geometry_name).or_insert(0) += 1)
+}
+
+pub fn record_path_end(end: PathEnd, length: usize) {
+    record(|stats| {
+        match end {
+            PathEnd::Escaped => stats.escaped_paths += 1,
+            PathEnd::Absorbed => stats.absorbed_paths += 1,
+            PathEnd::Truncated => stats.truncated_paths += 1,
+        }
+
+        if stats.path_lengths.len() <= length {
+            stats.path_lengths.resize(length + 1, 0);
+        }
+        stats.path_lengths[length] += 1;
+    })
+}
+
+pub fn record_non_finite_sample(sample: NonFiniteSample) {
+    record(|stats| {
+        stats.non_finite_samples += 1;
+        if stats.non_finite_reports.len() < MAX_NON_FINITE_REPORTS {
+            stats.non_finite_reports.push(sample);
+        }
+    })
+}
+
+pub fn rays_traced() -> u64 {
+    STATS.with(|stats| {
+        let stats = stats.borrow();
+        stats.camera_rays + stats.scattered_rays
+    })
+}
+
+// returns everything recorded on this thread so far, and starts over from zero
+pub fn take() -> RenderStats {
+    STATS.with(|stats| stats.take())
+}
+
+impl RenderStats {
+    pub fn total_paths(&self) -> u64 {
+        self.escaped_paths + self.absorbed_paths + self.truncated_paths
+    }
+}
+
+impl fmt::Display for RenderStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let seconds = self.elapsed.as_secs_f64();
+        let per_second = |count: u64| {
+            if seconds > 0.0 {
+                count as f64 / seconds
+            } else {
+                0.0
+            }
+        };
+        let percent = |count: u64, total: u64| {
+            if total > 0 {
+                100.0 * count as f64 / total as f64
+            } else {
+                0.0
+            }
+        };
+
+        writeln!(f, "render statistics ({:.2}s)", seconds)?;
+        writeln!(f, "  camera rays         {:>14}", self.camera_rays)?;
+        writeln!(f, "  scattered rays      {:>14}", self.scattered_rays)?;
+        writeln!(
+            f,
+            "  rays per second     {:>14.0}",
+            per_second(self.camera_rays + self.scattered_rays)
+        )?;
+
+        writeln!(f, "  intersection tests")?;
+        for (name, count) in self.intersection_tests.iter() {
+            writeln!(f, "    {:<18}{:>14}", name, count)?;
+        }
+
+        let total_paths = self.total_paths();
+        writeln!(f, "  paths               {:>14}", total_paths)?;
+        for (label, count) in [
+            ("escaped", self.escaped_paths),
+            ("absorbed", self.absorbed_paths),
+            ("truncated", self.truncated_paths),
+        ] {
+            writeln!(
+                f,
+                "    {:<18}{:>14} ({:.1}%)",
+                label,
+                count,
+                percent(count, total_paths)
+            )?;
+        }
+
+        writeln!(f, "  path lengths")?;
+        for (length, count) in self.path_lengths.iter().enumerate() {
+            let share = percent(*count, total_paths);
+            writeln!(
+                f,
+                "    {:>2} bounces        {:>14} {}",
+                length,
+                count,
+                "#".repeat((share / 2.0).round() as usize)
+            )?;
+        }
+
+        write!(f, "  non-finite samples  {:>14}", self.non_finite_samples)?;
+        for sample in self.non_finite_reports.iter() {
+            write!(f, "\n{sample}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for NonFiniteSample {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "    pixel ({}, {}), sample {}:",
+            self.pixel_x, self.pixel_y, self.sample_index
+        )?;
+        if self.path.is_empty() {
+            write!(f, " straight to the sky")?;
+        }
+
+        for vertex in self.path.iter() {
+            let point = &vertex.point;
+            write!(
+                f,
+                "\n      {} / {} at ({}, {}, {})",
+                vertex.geometry, vertex.material, point.x, point.y, point.z
+            )?;
+            match (&vertex.scattered_dir, &vertex.attenuation) {
+                (Some(dir), Some(color)) => write!(
+                    f,
+                    " -> dir ({}, {}, {}), attenuation ({}, {}, {})",
+                    dir.x,
+                    dir.y,
+                    dir.z,
+                    color.r(),
+                    color.g(),
+                    color.b()
+                )?,
+                _ => write!(f, " -> absorbed")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_and_take() {
+        take();
+        record(|stats| stats.camera_rays += 2);
+        record_intersection_test("Sphere");
+        record_intersection_test("Sphere");
+        record_intersection_test("Plane");
+        record_path_end(PathEnd::Escaped, 0);
+        record_path_end(PathEnd::Absorbed, 3);
+
+        let stats = take();
+        assert_eq!(stats.camera_rays, 2);
+        assert_eq!(stats.intersection_tests["Sphere"], 2);
+        assert_eq!(stats.intersection_tests["Plane"], 1);
+        assert_eq!(stats.path_lengths, vec![1, 0, 0, 1]);
+        assert_eq!(stats.total_paths(), 2);
+
+        // taking resets the counters
+        assert_eq!(take(), RenderStats::default());
+    }
+}
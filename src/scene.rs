@@ -1,54 +1,99 @@
 use derive_builder::Builder;
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 
-use crate::{camera::Camera, math::ray::Ray};
+use crate::{camera::Camera, math::ray::Ray, render::stats};
 
 use self::{
+    group::Group,
     object::{geometry::Intersection, Object},
+    registry::Registry,
     sky::Sky,
 };
 
+mod file;
+pub mod group;
 pub mod object;
+pub mod registry;
 pub mod sky;
 
-#[derive(Serialize, Deserialize, Builder)]
+// (serialization goes through `file::SceneFile`, which is where registry names get resolved)
+#[derive(Deserialize, Builder)]
+#[serde(try_from = "file::SceneFile")]
 pub struct Scene {
     pub camera: Camera,
 
+    // materials and geometries shared between objects
+    registry: Registry,
+
     #[builder(each = "add_object")]
     objects: Vec<Object>,
 
+    // assemblies of objects that get placed in the scene together
+    #[builder(each = "add_group")]
+    groups: Vec<Group>,
+
     pub sky: Sky,
 }
 
 // the shortest distance a ray can travel before intersections are allowed.
 // helps avoid floating points obnoxiousness
-const RAY_MIN_T: f32 = 0.0001;
+pub(crate) const RAY_MIN_T: f32 = 0.0001;
 
 impl Scene {
     // TODO: something to consider -- it sorta makes sense to name this the same as the method in IntersectRay,
     //   but the return types of those two functions are different. How to reconcile?
     //   Maybe two traits (IntersectRayGeom and IntersectRayObj)?
     pub fn intersect_ray(&self, ray: &Ray) -> Option<(Intersection, &Object)> {
-        let mut closest: Option<(Intersection, &Object)> = None;
-
-        for object in self.objects.iter() {
-            if let Some(intersection) = object.geometry.intersect_ray(ray) {
-                // reject this intersection if its t value is too small or negative
-                if intersection.t < RAY_MIN_T {
-                    continue;
-                }
-
-                match closest {
-                    // Update the closest intersection if a larger t was found
-                    Some((Intersection { t: closest_t, .. }, _)) if intersection.t > closest_t => {}
-                    _ => {
-                        closest = Some((intersection, object));
-                    }
-                };
-            }
+        let (mut intersection, object) = closest_intersection(&self.objects, &self.groups, ray)?;
+
+        // (this is done here, once everything is in world space, rather than by each geometry)
+        intersection.compute_uv_derivatives(ray);
+        Some((intersection, object))
+    }
+
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    // finds a group anywhere in the scene by name, eg. to move it around
+    pub fn group_mut(&mut self, name: &str) -> Option<&mut Group> {
+        self.groups
+            .iter_mut()
+            .find_map(|group| group.find_mut(name))
+    }
+}
+
+// the closest intersection with any of the objects or any of the objects in the groups
+fn closest_intersection<'a>(
+    objects: &'a [Object],
+    groups: &'a [Group],
+    ray: &Ray,
+) -> Option<(Intersection, &'a Object)> {
+    let mut closest: Option<(Intersection, &Object)> = None;
+
+    let object_hits = objects.iter().filter_map(|object| {
+        stats::record_intersection_test(object.geometry.typetag_name());
+        object
+            .geometry
+            .intersect_ray(ray)
+            .map(|intersection| (intersection, object))
+    });
+    let group_hits = groups.iter().filter_map(|group| group.intersect_ray(ray));
+
+    for (intersection, object) in object_hits.chain(group_hits) {
+        // reject this intersection if its t value is too small or negative
+        if intersection.t < RAY_MIN_T {
+            continue;
         }
 
-        closest
+        match closest {
+            // Update the closest intersection if a larger t was found
+            Some((Intersection { t: closest_t, .. }, _)) if intersection.t > closest_t => {}
+            _ => {
+                closest = Some((intersection, object));
+            }
+        };
     }
+
+    closest
 }
@@ -0,0 +1,308 @@
+use std::{collections::BTreeMap, fmt, marker::PhantomData, sync::Arc};
+
+use serde::{
+    de::{self, value::MapAccessDeserializer, Visitor},
+    Deserialize, Deserializer, Serialize, Serializer,
+};
+
+use crate::{camera::Camera, math::transform::Transform};
+
+use super::{
+    group::Group,
+    object::{geometry::IntersectRay, material::ScatterRay, Object},
+    registry::Registry,
+    sky::Sky,
+    Scene,
+};
+
+// How a scene looks on disk. It differs from `Scene` in that objects can refer to registered
+// materials and geometries by name, which get resolved when the file is loaded.
+#[derive(Serialize, Deserialize)]
+pub struct SceneFile {
+    camera: Camera,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    materials: BTreeMap<String, Arc<dyn ScatterRay>>,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    geometries: BTreeMap<String, Arc<dyn IntersectRay>>,
+
+    objects: Vec<ObjectFile>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    groups: Vec<GroupFile>,
+
+    sky: Sky,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ObjectFile {
+    geometry: Entry<dyn IntersectRay>,
+    material: Entry<dyn ScatterRay>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct GroupFile {
+    name: String,
+
+    #[serde(default)]
+    transform: Transform,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    objects: Vec<ObjectFile>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    groups: Vec<GroupFile>,
+}
+
+// either the name of something in the registry or the thing itself
+enum Entry<T: ?Sized> {
+    Handle(String),
+    Inline(Arc<T>),
+}
+
+impl TryFrom<SceneFile> for Scene {
+    type Error = String;
+
+    fn try_from(file: SceneFile) -> Result<Self, Self::Error> {
+        let mut registry = Registry::new();
+        for (name, material) in file.materials {
+            registry.insert_material(&name, material);
+        }
+        for (name, geometry) in file.geometries {
+            registry.insert_geometry(&name, geometry);
+        }
+
+        let objects = file
+            .objects
+            .into_iter()
+            .map(|object| object.resolve(&registry))
+            .collect::<Result<_, _>>()?;
+        let groups = file
+            .groups
+            .into_iter()
+            .map(|group| group.resolve(&registry))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Scene {
+            camera: file.camera,
+            registry,
+            objects,
+            groups,
+            sky: file.sky,
+        })
+    }
+}
+
+impl From<&Scene> for SceneFile {
+    fn from(scene: &Scene) -> Self {
+        let registry = &scene.registry;
+        SceneFile {
+            camera: scene.camera.clone(),
+            materials: registry.materials().clone(),
+            geometries: registry.geometries().clone(),
+            objects: scene
+                .objects
+                .iter()
+                .map(|object| ObjectFile::new(object, registry))
+                .collect(),
+            groups: scene
+                .groups
+                .iter()
+                .map(|group| GroupFile::new(group, registry))
+                .collect(),
+            sky: scene.sky.clone(),
+        }
+    }
+}
+
+impl Serialize for Scene {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SceneFile::from(self).serialize(serializer)
+    }
+}
+
+impl ObjectFile {
+    fn new(object: &Object, registry: &Registry) -> ObjectFile {
+        ObjectFile {
+            geometry: match registry.geometry_name(&object.geometry) {
+                Some(name) => Entry::Handle(name.to_string()),
+                None => Entry::Inline(object.geometry.clone()),
+            },
+            material: match registry.material_name(&object.material) {
+                Some(name) => Entry::Handle(name.to_string()),
+                None => Entry::Inline(object.material.clone()),
+            },
+        }
+    }
+
+    fn resolve(self, registry: &Registry) -> Result<Object, String> {
+        let geometry = match self.geometry {
+            Entry::Handle(name) => registry
+                .geometry(&name)
+                .ok_or_else(|| format!("unknown geometry `{name}`"))?,
+            Entry::Inline(geometry) => geometry,
+        };
+        let material = match self.material {
+            Entry::Handle(name) => registry
+                .material(&name)
+                .ok_or_else(|| format!("unknown material `{name}`"))?,
+            Entry::Inline(material) => material,
+        };
+        Ok(Object { geometry, material })
+    }
+}
+
+impl GroupFile {
+    fn new(group: &Group, registry: &Registry) -> GroupFile {
+        GroupFile {
+            name: group.name.clone(),
+            transform: group.transform.clone(),
+            objects: group
+                .objects()
+                .iter()
+                .map(|object| ObjectFile::new(object, registry))
+                .collect(),
+            groups: group
+                .groups()
+                .iter()
+                .map(|group| GroupFile::new(group, registry))
+                .collect(),
+        }
+    }
+
+    fn resolve(self, registry: &Registry) -> Result<Group, String> {
+        let mut group = Group::new(&self.name, self.transform);
+        for object in self.objects {
+            group.add_object(object.resolve(registry)?);
+        }
+        for child in self.groups {
+            group.add_group(child.resolve(registry)?);
+        }
+        Ok(group)
+    }
+}
+
+impl<T: ?Sized + Serialize> Serialize for Entry<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Entry::Handle(name) => serializer.serialize_str(name),
+            Entry::Inline(value) => value.serialize(serializer),
+        }
+    }
+}
+
+// A string is a handle, anything else has to be the thing itself. This is done by hand rather
+// than with `#[serde(untagged)]` so that mistakes in inline materials get useful error messages.
+impl<'de, T: ?Sized> Deserialize<'de> for Entry<T>
+where
+    Box<T>: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EntryVisitor<T: ?Sized>(PhantomData<Box<T>>);
+
+        impl<'de, T: ?Sized> Visitor<'de> for EntryVisitor<T>
+        where
+            Box<T>: Deserialize<'de>,
+        {
+            type Value = Entry<T>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a registered name or an inline definition")
+            }
+
+            fn visit_str<E: de::Error>(self, name: &str) -> Result<Self::Value, E> {
+                Ok(Entry::Handle(name.to_string()))
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                let value = Box::<T>::deserialize(MapAccessDeserializer::new(map))?;
+                Ok(Entry::Inline(value.into()))
+            }
+        }
+
+        deserializer.deserialize_any(EntryVisitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{ray::Ray, vec3::Vec3};
+
+    const YAML: &str = r#"
+camera:
+  output_width: 4
+  output_height: 4
+  position: { x: 0.0, y: 0.0, z: 0.0 }
+  camera_forward: { x: 0.0, y: 0.0, z: -1.0 }
+  camera_right: { x: 1.0, y: 0.0, z: 0.0 }
+  camera_up: { x: 0.0, y: 1.0, z: 0.0 }
+  aperture_width: 0.0
+materials:
+  glass:
+    Translucent:
+      albedo: { x: 1.0, y: 1.0, z: 1.0 }
+      refractive_index: 1.5
+geometries:
+  ball:
+    Sphere:
+      radius: 0.5
+      center: { x: 0.0, y: 0.0, z: 0.0 }
+      orientation: Outward
+objects:
+- geometry: ball
+  material: glass
+- geometry:
+    Sphere:
+      radius: 1.0
+      center: { x: 0.0, y: 0.0, z: -10.0 }
+      orientation: Outward
+  material: glass
+groups:
+- name: table
+  transform:
+    translation: { x: 0.0, y: 0.0, z: -5.0 }
+  objects:
+  - geometry: ball
+    material:
+      Lambertian:
+        albedo: { x: 0.5, y: 0.5, z: 0.5 }
+sky:
+  nadir: { x: 1.0, y: 1.0, z: 1.0 }
+  zenith: { x: 1.0, y: 1.0, z: 1.0 }
+"#;
+
+    #[test]
+    fn resolve_handles() {
+        let scene: Scene = serde_yaml::from_str(YAML).unwrap();
+
+        let glass = scene.registry.material("glass").unwrap();
+        assert!(Arc::ptr_eq(&scene.objects[0].material, &glass));
+        assert!(Arc::ptr_eq(&scene.objects[1].material, &glass));
+
+        // the group's object uses the shared ball, moved out to z = -5
+        let ray = Ray::new(Vec3::new(0.0, 0.0, -2.0), Vec3::new(0.0, 0.0, -1.0));
+        let (intersection, object) = scene.intersect_ray(&ray).unwrap();
+        assert!((intersection.t - 2.5).abs() < 1e-5);
+        assert_eq!(object.material.typetag_name(), "Lambertian");
+    }
+
+    #[test]
+    fn write_handles_back_out() {
+        let scene: Scene = serde_yaml::from_str(YAML).unwrap();
+        let yaml = serde_yaml::to_string(&scene).unwrap();
+        assert!(yaml.contains("material: glass"));
+        assert!(yaml.contains("geometry: ball"));
+
+        let reloaded: Scene = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(serde_yaml::to_string(&reloaded).unwrap(), yaml);
+    }
+
+    #[test]
+    fn reject_unknown_handles() {
+        let yaml = YAML.replace("material: glass", "material: brass");
+        let error = serde_yaml::from_str::<Scene>(&yaml).err().unwrap();
+        assert!(error.to_string().contains("unknown material `brass`"));
+    }
+}
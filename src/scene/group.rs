@@ -0,0 +1,135 @@
+use crate::math::{ray::Ray, transform::Transform};
+
+use super::{
+    closest_intersection,
+    object::{geometry::Intersection, Object},
+};
+
+// A named collection of objects and other groups, placed in the scene as one unit.
+// Everything in a group is positioned relative to the group's transform, which in turn
+// is relative to the group containing it (if any).
+pub struct Group {
+    pub name: String,
+
+    pub transform: Transform,
+
+    objects: Vec<Object>,
+
+    groups: Vec<Group>,
+}
+
+impl Group {
+    pub fn new(name: &str, transform: Transform) -> Group {
+        Group {
+            name: name.to_string(),
+            transform,
+            objects: vec![],
+            groups: vec![],
+        }
+    }
+
+    pub fn add_object(&mut self, object: Object) -> &mut Self {
+        self.objects.push(object);
+        self
+    }
+
+    pub fn add_group(&mut self, group: Group) -> &mut Self {
+        self.groups.push(group);
+        self
+    }
+
+    pub fn objects(&self) -> &[Object] {
+        &self.objects
+    }
+
+    pub fn groups(&self) -> &[Group] {
+        &self.groups
+    }
+
+    // this group, or the first group inside it with the given name
+    pub fn find_mut(&mut self, name: &str) -> Option<&mut Group> {
+        if self.name == name {
+            return Some(self);
+        }
+        self.groups
+            .iter_mut()
+            .find_map(|group| group.find_mut(name))
+    }
+
+    // Rather than flattening transforms, the ray gets brought into each group's space in turn
+    // on the way down, and the intersection gets brought back out on the way up.
+    pub fn intersect_ray(&self, ray: &Ray) -> Option<(Intersection, &Object)> {
+        let local_ray = self.transform.ray_to_local(ray);
+        let (local, object) = closest_intersection(&self.objects, &self.groups, &local_ray)?;
+
+        Some((local.to_world(&self.transform), object))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::math::{color::Color, quaternion::Quaternion, vec3::Vec3};
+    use crate::scene::object::{geometry::sphere::Sphere, material::lambertian::Lambertian};
+
+    fn assert_close(u: &Vec3, v: &Vec3) {
+        assert!((u - v).length() < 1e-5, "{u:?} != {v:?}");
+    }
+
+    fn ball(radius: f32, center: Vec3) -> Object {
+        Object {
+            geometry: Arc::new(Sphere::new(radius, center)),
+            material: Arc::new(Lambertian::new(Color::from_rgb_u8(128, 128, 128))),
+        }
+    }
+
+    // a "table" at (0, 0, -5), scaled up by 2, with an "item" sitting on a "plate" on top
+    fn table() -> Group {
+        let mut plate = Group::new("plate", Transform::translation(Vec3::new(0.0, 1.0, 0.0)));
+        plate.add_object(ball(0.25, Vec3::new(0.0, 0.25, 0.0)));
+
+        let mut table = Group::new(
+            "table",
+            Transform::new(
+                Vec3::new(0.0, 0.0, -5.0),
+                Quaternion::identity(),
+                Vec3::new(2.0, 2.0, 2.0),
+            ),
+        );
+        table
+            .add_object(ball(0.5, Vec3::new(0.0, 0.0, 0.0)))
+            .add_group(plate);
+        table
+    }
+
+    #[test]
+    fn intersect_nested_groups() {
+        let table = table();
+
+        // straight down from above hits the item first, at world height 2 * (1 + 0.5) = 3
+        let ray = Ray::new(Vec3::new(0.0, 10.0, -5.0), Vec3::new(0.0, -1.0, 0.0));
+        let (intersection, _) = table.intersect_ray(&ray).unwrap();
+        assert!((intersection.t - 7.0).abs() < 1e-5);
+        assert_close(&intersection.point, &Vec3::new(0.0, 3.0, -5.0));
+        assert_close(&intersection.normal, &Vec3::new(0.0, 1.0, 0.0));
+
+        // from the side at the table's height only hits the table itself
+        let ray = Ray::new(Vec3::new(-10.0, 0.0, -5.0), Vec3::new(1.0, 0.0, 0.0));
+        let (intersection, _) = table.intersect_ray(&ray).unwrap();
+        assert_close(&intersection.point, &Vec3::new(-1.0, 0.0, -5.0));
+    }
+
+    #[test]
+    fn move_group_as_a_unit() {
+        let mut table = table();
+        table.find_mut("plate").unwrap().transform =
+            Transform::translation(Vec3::new(3.0, 1.0, 0.0));
+
+        let ray = Ray::new(Vec3::new(6.0, 10.0, -5.0), Vec3::new(0.0, -1.0, 0.0));
+        let (intersection, _) = table.intersect_ray(&ray).unwrap();
+        assert_close(&intersection.point, &Vec3::new(6.0, 3.0, -5.0));
+        assert!(table.find_mut("chair").is_none());
+    }
+}
@@ -1,14 +1,15 @@
-use serde::{Deserialize, Serialize};
+use std::sync::Arc;
 
 use self::{geometry::IntersectRay, material::ScatterRay};
 
 pub mod geometry;
 pub mod material;
+pub mod texture;
 
-type Geometry = Box<dyn IntersectRay>;
-type Material = Box<dyn ScatterRay>;
+// shared so that objects can use the same geometry or material (see `Registry`)
+pub type Geometry = Arc<dyn IntersectRay>;
+pub type Material = Arc<dyn ScatterRay>;
 
-#[derive(Serialize, Deserialize)]
 pub struct Object {
     pub geometry: Geometry,
     pub material: Material,
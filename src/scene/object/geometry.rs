@@ -1,11 +1,26 @@
+use std::f32::consts::PI;
+
 use serde::{Deserialize, Serialize};
 
-use crate::math::{ray::Ray, vec3::Vec3};
+use crate::math::{ray::Ray, transform::Transform, vec3::Vec3};
+use crate::scene::RAY_MIN_T;
 
+pub mod cone;
+pub mod csg;
+pub mod cuboid;
+pub mod cylinder;
+pub mod disk;
+pub mod heightfield;
+pub mod instance;
+pub mod paraboloid;
 pub mod plane;
+pub mod quad;
+pub mod sdf;
 pub mod sphere;
+pub mod torus;
+pub mod voxel_grid;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub struct Intersection {
     // where (in world space) the intersection occurs
     pub point: Vec3,
@@ -20,19 +35,303 @@ pub struct Intersection {
     // at the intersection point
     // (it's easier to compute and store this during intersection computation, rather than later)
     pub is_into_surface: bool,
-    //
-    // TODO: add `object` -- as a reference?
-    //   then spend 3 hours figuring out how lifetimes work, probably
-    //   ... or should it be `geometry`?
+
+    // anything else that the geometry knows about the point, for materials to color by
+    pub attribute: Option<SurfaceAttribute>,
+
+    // where the point is in the surface's own coordinates, which textures are looked up by.
+    // for bounded surfaces these go from 0 to 1, for unbounded ones they're distances
+    pub uv: (f32, f32),
+
+    // how the point moves as u and v increase: tangent to the surface, but not normalized,
+    // and ordered so that their cross product points out of the surface
+    pub dpdu: Vec3,
+    pub dpdv: Vec3,
+
+    // the normal that materials should shade with, which can differ from the true normal above
+    // (eg. to smooth out a surface made of flat pieces). it's flipped along with `normal`
+    pub shading_normal: Vec3,
+
+    // which part of the geometry was hit, for geometries made of several pieces
+    // (eg. the face of a cuboid, or a triangle of a heightfield)
+    pub primitive_index: usize,
+
+    // how u and v change from one pixel to the next, across (x) and down (y) the image, which
+    // says how much of a texture to blur together. these are zero when that isn't known
+    // (eg. for rays that have bounced), and are only filled in by `Scene::intersect_ray`
+    pub duvdx: (f32, f32),
+    pub duvdy: (f32, f32),
+}
+
+// Something that only some geometries know about the points they hit
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SurfaceAttribute {
+    // a measure along some continuous scale (eg. how close a fractal's orbit came to its trap)
+    Value(f32),
+
+    // one of a set of things (eg. the palette entry of a voxel), which the `Palette` material
+    // picks a material by
+    Id(u8),
+}
+
+impl Intersection {
+    // An intersection with nothing known about the surface beyond its normal: no texture
+    // coordinates, and some arbitrary tangents.
+    pub fn new(point: Vec3, normal: Vec3, t: f32, is_into_surface: bool) -> Intersection {
+        let (dpdu, dpdv) = Vec3::tangents(&normal);
+        Intersection {
+            point,
+            shading_normal: normal.clone(),
+            normal,
+            t,
+            is_into_surface,
+            attribute: None,
+            uv: (0.0, 0.0),
+            dpdu,
+            dpdv,
+            primitive_index: 0,
+            duvdx: (0.0, 0.0),
+            duvdy: (0.0, 0.0),
+        }
+    }
+
+    pub fn with_uv(self, uv: (f32, f32), dpdu: Vec3, dpdv: Vec3) -> Intersection {
+        Intersection {
+            uv,
+            dpdu,
+            dpdv,
+            ..self
+        }
+    }
+
+    pub fn with_primitive_index(self, primitive_index: usize) -> Intersection {
+        Intersection {
+            primitive_index,
+            ..self
+        }
+    }
+
+    // Brings an intersection with a ray from `Transform::ray_to_local` back out of the
+    // transform's local space. Since that ray's direction isn't renormalized, t stays the same.
+    pub fn to_world(self, transform: &Transform) -> Intersection {
+        Intersection {
+            point: transform.point(&self.point),
+            normal: transform.normal(&self.normal),
+            shading_normal: transform.normal(&self.shading_normal),
+            dpdu: transform.vector(&self.dpdu),
+            dpdv: transform.vector(&self.dpdv),
+            ..self
+        }
+    }
+
+    pub fn with_attribute(self, attribute: Option<SurfaceAttribute>) -> Intersection {
+        Intersection { attribute, ..self }
+    }
+
+    // the attribute, if it's a value
+    pub fn attribute_value(&self) -> Option<f32> {
+        match self.attribute {
+            Some(SurfaceAttribute::Value(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    // the attribute, if it's an id
+    pub fn attribute_id(&self) -> Option<u8> {
+        match self.attribute {
+            Some(SurfaceAttribute::Id(id)) => Some(id),
+            _ => None,
+        }
+    }
+
+    // Finds `duvdx` and `duvdy` from the ray's differentials (if it has them), by following the
+    // neighboring pixels' rays to the plane tangent to the surface here, and seeing how far along
+    // `dpdu` and `dpdv` they land from this point.
+    pub fn compute_uv_derivatives(&mut self, ray: &Ray) {
+        let Some(differentials) = &ray.differentials else {
+            return;
+        };
+
+        let offset_to_tangent_plane = |origin: &Vec3, dir: &Vec3| {
+            let along_normal = Vec3::dot(&self.normal, dir);
+            if along_normal.abs() < 1e-8 {
+                return None;
+            }
+            let t = Vec3::dot(&self.normal, &(&self.point - origin)) / along_normal;
+            Some(&(origin + &(t * dir)) - &self.point)
+        };
+        let (Some(dpdx), Some(dpdy)) = (
+            offset_to_tangent_plane(&differentials.x_origin, &differentials.x_dir),
+            offset_to_tangent_plane(&differentials.y_origin, &differentials.y_dir),
+        ) else {
+            return;
+        };
+
+        // Solving offset = du * dpdu + dv * dpdv takes two of the three equations; dropping the
+        // one along the normal's biggest component keeps the others from being degenerate.
+        let n = [
+            self.normal.x.abs(),
+            self.normal.y.abs(),
+            self.normal.z.abs(),
+        ];
+        let (a, b) = if n[0] > n[1] && n[0] > n[2] {
+            (1, 2)
+        } else if n[1] > n[2] {
+            (0, 2)
+        } else {
+            (0, 1)
+        };
+        let component = |v: &Vec3, i: usize| [v.x, v.y, v.z][i];
+        let m = [
+            [component(&self.dpdu, a), component(&self.dpdv, a)],
+            [component(&self.dpdu, b), component(&self.dpdv, b)],
+        ];
+        let determinant = m[0][0] * m[1][1] - m[0][1] * m[1][0];
+        if determinant.abs() < 1e-12 {
+            return;
+        }
+        let solve = |offset: &Vec3| {
+            let (oa, ob) = (component(offset, a), component(offset, b));
+            (
+                (m[1][1] * oa - m[0][1] * ob) / determinant,
+                (m[0][0] * ob - m[1][0] * oa) / determinant,
+            )
+        };
+
+        self.duvdx = solve(&dpdx);
+        self.duvdy = solve(&dpdy);
+    }
+
+    // for surfaces whose normal gets flipped after the fact (eg. by CSG)
+    pub fn flipped(self) -> Intersection {
+        Intersection {
+            normal: -&self.normal,
+            shading_normal: -&self.shading_normal,
+            is_into_surface: !self.is_into_surface,
+            ..self
+        }
+    }
 }
 
 #[typetag::serde]
-pub trait IntersectRay {
+pub trait IntersectRay: Send + Sync {
     fn intersect_ray(&self, ray: &Ray) -> Option<Intersection>;
+
+    // Every place the ray crosses the surface (past `RAY_MIN_T`), in order along the ray.
+    // For closed surfaces, whether the first of these goes into the surface says whether
+    // the ray started outside of it. Used by the CSG geometries.
+    //
+    // By default this keeps recasting the ray from each crossing to find the next one,
+    // which works for anything but is worth overriding when all crossings are known at once.
+    fn crossings(&self, ray: &Ray) -> Vec<Intersection> {
+        let mut crossings: Vec<Intersection> = vec![];
+        let mut t_start = 0.0;
+
+        for _ in 0..MAX_RECASTS {
+            let recast = Ray::new(ray.at(t_start), ray.dir.clone());
+            let Some(mut crossing) = self.intersect_ray(&recast) else {
+                break;
+            };
+
+            // the surface the recast ray started on can show up again right away,
+            // in which case the ray gets nudged along past it
+            if crossing.t < RAY_MIN_T {
+                t_start += RAY_MIN_T;
+                continue;
+            }
+
+            crossing.t += t_start;
+            t_start = crossing.t;
+            crossings.push(crossing);
+        }
+
+        crossings
+    }
 }
 
+// a limit on how many times the default `crossings` recasts a ray, in case a surface is
+// crossed an unreasonable number of times (or something goes wrong)
+const MAX_RECASTS: usize = 64;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub enum NormalOrientation {
     Outward,
     Inward,
 }
+
+impl NormalOrientation {
+    pub fn flip(&self) -> NormalOrientation {
+        match self {
+            NormalOrientation::Outward => NormalOrientation::Inward,
+            NormalOrientation::Inward => NormalOrientation::Outward,
+        }
+    }
+
+    // turns a normal pointing out of a surface into one pointing the way this says
+    pub fn orient(&self, outward_normal: Vec3) -> Vec3 {
+        match self {
+            NormalOrientation::Outward => outward_normal,
+            NormalOrientation::Inward => -&outward_normal,
+        }
+    }
+}
+
+// The closest of several possible hits along a ray, each given by its t and the normal pointing
+// out of the surface there. Hits too close to the ray's origin are skipped, so that a ray leaving
+// a surface finds the next surface rather than the one it started on.
+fn nearest_hit(
+    ray: &Ray,
+    candidates: impl IntoIterator<Item = (f32, Vec3)>,
+    orientation: &NormalOrientation,
+) -> Option<Intersection> {
+    let (t, outward_normal) = candidates
+        .into_iter()
+        .filter(|(t, _)| *t >= RAY_MIN_T)
+        .min_by(|(t0, _), (t1, _)| t0.total_cmp(t1))?;
+
+    Some(oriented_hit(ray, t, outward_normal, orientation))
+}
+
+// like `nearest_hit`, but keeping all of the hits, in order along the ray
+fn all_hits(
+    ray: &Ray,
+    candidates: impl IntoIterator<Item = (f32, Vec3)>,
+    orientation: &NormalOrientation,
+) -> Vec<Intersection> {
+    let mut candidates: Vec<(f32, Vec3)> = candidates
+        .into_iter()
+        .filter(|(t, _)| *t >= RAY_MIN_T)
+        .collect();
+    candidates.sort_by(|(t0, _), (t1, _)| t0.total_cmp(t1));
+
+    candidates
+        .into_iter()
+        .map(|(t, outward_normal)| oriented_hit(ray, t, outward_normal, orientation))
+        .collect()
+}
+
+// For surfaces around an axis: how far around the axis (a unit vector) something is, from 0 to 1,
+// counterclockwise when looking down at the axis's tip, given its displacement perpendicular to
+// the axis. Also returns the unit vectors pointing away from the axis and around it there.
+fn around_axis(axis: &Vec3, from_axis: &Vec3) -> (f32, Vec3, Vec3) {
+    let (first, second) = Vec3::tangents(axis);
+    let angle = Vec3::dot(from_axis, &second)
+        .atan2(Vec3::dot(from_axis, &first))
+        .rem_euclid(2.0 * PI);
+
+    let (sin, cos) = angle.sin_cos();
+    let radial = Vec3::lin_comb(vec![(cos, &first), (sin, &second)]);
+    let around = Vec3::cross(axis, &radial);
+    (angle / (2.0 * PI), radial, around)
+}
+
+fn oriented_hit(
+    ray: &Ray,
+    t: f32,
+    outward_normal: Vec3,
+    orientation: &NormalOrientation,
+) -> Intersection {
+    let normal = orientation.orient(outward_normal);
+    let is_into_surface = Vec3::dot(&ray.dir, &normal) < 0.0;
+    Intersection::new(ray.at(t), normal, t, is_into_surface)
+}
@@ -0,0 +1,185 @@
+use std::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::math::{ray::Ray, roots::solve_quadratic, vec3::Vec3};
+use crate::scene::object::geometry::{IntersectRay, Intersection};
+
+use super::{all_hits, around_axis, disk::disk_coords, nearest_hit, NormalOrientation};
+
+// a circular cone with its base centered at `base` and its tip at `apex`,
+// with the base either open or closed off by a flat cap
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cone {
+    base: Vec3,
+    apex: Vec3,
+    radius: f32,
+    capped: bool,
+    orientation: NormalOrientation,
+}
+
+impl Cone {
+    pub fn new(base: Vec3, apex: Vec3, radius: f32, capped: bool) -> Cone {
+        Cone {
+            base,
+            apex,
+            radius,
+            capped,
+            orientation: NormalOrientation::Outward,
+        }
+    }
+
+    pub fn flip_orientation(&mut self) {
+        self.orientation = self.orientation.flip();
+    }
+
+    // On the side, u goes around the axis and v goes from the base to the apex.
+    // The cap is primitive 1, with coordinates like a `Disk`.
+    fn with_surface_coords(&self, hit: Intersection) -> Intersection {
+        let axis = &self.apex - &self.base;
+        let height = axis.length();
+        let axis = (1.0 / height) * &axis;
+
+        // the side's normals all lean towards the apex, and the cap's points away from it
+        let outward = self.orientation.orient(hit.normal.clone());
+        if Vec3::dot(&outward, &axis) < 0.0 {
+            return disk_coords(hit, &self.base, &-&axis, self.radius).with_primitive_index(1);
+        }
+
+        let p = &hit.point - &self.base;
+        let y = Vec3::dot(&p, &axis);
+        let (u, radial, around) = around_axis(&axis, &(&p - &(y * &axis)));
+        let v = y / height;
+
+        // moving up the side goes from the rim straight to the apex
+        let dpdu = (2.0 * PI * self.radius * (1.0 - v)) * &around;
+        let dpdv = &(height * &axis) - &(self.radius * &radial);
+        hit.with_uv((u, v), dpdu, dpdv)
+    }
+
+    // every t where the ray meets the surface, along with the outward normal there
+    fn candidates(&self, ray: &Ray) -> Vec<(f32, Vec3)> {
+        let axis = &self.apex - &self.base;
+        let height = axis.length();
+        let axis = (1.0 / height) * &axis;
+
+        // split the ray's origin (relative to the base) and direction into
+        // their components along the axis and perpendicular to it
+        let origin = &ray.origin - &self.base;
+        let origin_y = Vec3::dot(&origin, &axis);
+        let dir_y = Vec3::dot(&ray.dir, &axis);
+        let origin_perp = &origin - &(origin_y * &axis);
+        let dir_perp = &ray.dir - &(dir_y * &axis);
+
+        // At height y the cone's radius is k * (height - y), so the side is where
+        // |perp|^2 - k^2 (height - y)^2 = 0. The gradient of that is the outward normal.
+        let k_squared = (self.radius / height).powi(2);
+        let origin_below_apex = height - origin_y;
+
+        let mut candidates: Vec<(f32, Vec3)> = solve_quadratic(
+            Vec3::dot(&dir_perp, &dir_perp) - k_squared * dir_y * dir_y,
+            2.0 * (Vec3::dot(&origin_perp, &dir_perp) + k_squared * origin_below_apex * dir_y),
+            Vec3::dot(&origin_perp, &origin_perp) - k_squared * origin_below_apex.powi(2),
+        )
+        .into_iter()
+        .filter_map(|t| {
+            // the equation also describes a second cone above the apex
+            let y = origin_y + t * dir_y;
+            if !(0.0..=height).contains(&y) {
+                return None;
+            }
+            let perp = &origin_perp + &(t * &dir_perp);
+            let normal = &perp + &((k_squared * (height - y)) * &axis);
+            Some((t, normal.normalize()))
+        })
+        .collect();
+
+        if self.capped && dir_y != 0.0 {
+            let t = -origin_y / dir_y;
+            let perp = &origin_perp + &(t * &dir_perp);
+            if Vec3::dot(&perp, &perp) <= self.radius * self.radius {
+                candidates.push((t, -&axis));
+            }
+        }
+
+        candidates
+    }
+}
+
+#[typetag::serde]
+impl IntersectRay for Cone {
+    fn intersect_ray(&self, ray: &Ray) -> Option<Intersection> {
+        nearest_hit(ray, self.candidates(ray), &self.orientation)
+            .map(|hit| self.with_surface_coords(hit))
+    }
+
+    fn crossings(&self, ray: &Ray) -> Vec<Intersection> {
+        all_hits(ray, self.candidates(ray), &self.orientation)
+            .into_iter()
+            .map(|hit| self.with_surface_coords(hit))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a cone with its base on the xz-plane and its tip at (0, 1, 0), with slope 45 degrees
+    fn cone(capped: bool) -> Cone {
+        Cone::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            capped,
+        )
+    }
+
+    fn assert_close(u: &Vec3, v: &Vec3) {
+        assert!((u - v).length() < 1e-5, "{u:?} != {v:?}");
+    }
+
+    #[test]
+    fn ray_hits_side() {
+        let ray = Ray::new(Vec3::new(-3.0, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let intersection = cone(false).intersect_ray(&ray).unwrap();
+
+        assert!((intersection.t - 2.5).abs() < 1e-5);
+        assert_close(&intersection.normal, &Vec3::new(-1.0, 1.0, 0.0).normalize());
+        assert!(intersection.is_into_surface);
+    }
+
+    #[test]
+    fn ray_misses_shadow_cone_above_apex() {
+        let ray = Ray::new(Vec3::new(-3.0, 1.5, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(cone(true).intersect_ray(&ray), None);
+    }
+
+    #[test]
+    fn ray_hits_base_cap() {
+        let ray = Ray::new(Vec3::new(0.5, -2.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+
+        let intersection = cone(true).intersect_ray(&ray).unwrap();
+        assert_close(&intersection.point, &Vec3::new(0.5, 0.0, 0.0));
+        assert_close(&intersection.normal, &Vec3::new(0.0, -1.0, 0.0));
+
+        // without the cap, the ray goes in the bottom and hits the inside of the side
+        let intersection = cone(false).intersect_ray(&ray).unwrap();
+        assert_close(&intersection.point, &Vec3::new(0.5, 0.5, 0.0));
+        assert!(!intersection.is_into_surface);
+    }
+
+    #[test]
+    fn coords_on_side_and_cap() {
+        let ray = Ray::new(Vec3::new(0.25, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let intersection = cone(true).intersect_ray(&ray).unwrap();
+        assert_eq!(intersection.primitive_index, 0);
+        assert!((intersection.uv.1 - 0.75).abs() < 1e-5);
+        let cross = Vec3::cross(&intersection.dpdu, &intersection.dpdv).normalize();
+        assert_close(&cross, &intersection.normal);
+
+        let ray = Ray::new(Vec3::new(0.25, -5.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let intersection = cone(true).intersect_ray(&ray).unwrap();
+        assert_eq!(intersection.primitive_index, 1);
+    }
+}
@@ -0,0 +1,257 @@
+use serde::{Deserialize, Serialize};
+
+use crate::math::ray::Ray;
+use crate::render::stats;
+use crate::scene::object::geometry::{IntersectRay, Intersection as Crossing};
+use crate::scene::object::Geometry;
+
+// Constructive solid geometry: new solids made by combining closed shapes. The space behind each
+// shape's normals counts as inside of it, so these work as expected for outward facing shapes.
+//
+// Each one walks along the crossings of all of its shapes in order, keeping track of which shapes
+// the ray is in, and keeps the crossings where the ray goes in or out of the combined solid.
+
+// everything that's inside of any of the shapes
+#[derive(Serialize, Deserialize)]
+pub struct Union {
+    shapes: Vec<Geometry>,
+}
+
+// everything that's inside of all of the shapes
+#[derive(Serialize, Deserialize)]
+pub struct Intersection {
+    shapes: Vec<Geometry>,
+}
+
+// everything that's inside of `base` but not inside of any of the shapes in `subtract`
+#[derive(Serialize, Deserialize)]
+pub struct Difference {
+    base: Geometry,
+    subtract: Vec<Geometry>,
+}
+
+impl Union {
+    pub fn new(shapes: Vec<Geometry>) -> Union {
+        Union { shapes }
+    }
+}
+
+impl Intersection {
+    pub fn new(shapes: Vec<Geometry>) -> Intersection {
+        Intersection { shapes }
+    }
+}
+
+impl Difference {
+    pub fn new(base: Geometry, subtract: Vec<Geometry>) -> Difference {
+        Difference { base, subtract }
+    }
+}
+
+#[typetag::serde]
+impl IntersectRay for Union {
+    fn intersect_ray(&self, ray: &Ray) -> Option<Crossing> {
+        self.crossings(ray).into_iter().next()
+    }
+
+    fn crossings(&self, ray: &Ray) -> Vec<Crossing> {
+        combine(self.shapes.iter(), ray, |inside| inside.iter().any(|i| *i))
+    }
+}
+
+#[typetag::serde]
+impl IntersectRay for Intersection {
+    fn intersect_ray(&self, ray: &Ray) -> Option<Crossing> {
+        self.crossings(ray).into_iter().next()
+    }
+
+    fn crossings(&self, ray: &Ray) -> Vec<Crossing> {
+        combine(self.shapes.iter(), ray, |inside| inside.iter().all(|i| *i))
+    }
+}
+
+#[typetag::serde]
+impl IntersectRay for Difference {
+    fn intersect_ray(&self, ray: &Ray) -> Option<Crossing> {
+        self.crossings(ray).into_iter().next()
+    }
+
+    fn crossings(&self, ray: &Ray) -> Vec<Crossing> {
+        let shapes = std::iter::once(&self.base).chain(self.subtract.iter());
+        combine(shapes, ray, |inside| {
+            inside[0] && !inside[1..].iter().any(|i| *i)
+        })
+    }
+}
+
+// `is_inside` decides whether a point is inside the combined solid, given whether it's inside of
+// each of the shapes
+fn combine<'a>(
+    shapes: impl Iterator<Item = &'a Geometry>,
+    ray: &Ray,
+    is_inside: impl Fn(&[bool]) -> bool,
+) -> Vec<Crossing> {
+    let shape_crossings: Vec<Vec<Crossing>> = shapes
+        .map(|shape| {
+            stats::record_intersection_test(shape.typetag_name());
+            shape.crossings(ray)
+        })
+        .collect();
+
+    // a ray whose first crossing with a shape comes out of it started inside of it
+    let mut inside: Vec<bool> = shape_crossings
+        .iter()
+        .map(|crossings| {
+            crossings
+                .first()
+                .is_some_and(|first| !first.is_into_surface)
+        })
+        .collect();
+    let mut was_inside = is_inside(&inside);
+
+    let mut all_crossings: Vec<(usize, Crossing)> = shape_crossings
+        .into_iter()
+        .enumerate()
+        .flat_map(|(index, crossings)| crossings.into_iter().map(move |c| (index, c)))
+        .collect();
+    all_crossings.sort_by(|(_, c0), (_, c1)| c0.t.total_cmp(&c1.t));
+
+    let mut combined = vec![];
+    for (index, crossing) in all_crossings {
+        inside[index] = crossing.is_into_surface;
+        let now_inside = is_inside(&inside);
+        if now_inside == was_inside {
+            continue;
+        }
+        was_inside = now_inside;
+
+        // The normal has to point out of the combined solid, which isn't the same as pointing out
+        // of the shape when eg. the ray leaves a subtracted shape and so goes into the difference.
+        if crossing.is_into_surface == now_inside {
+            combined.push(crossing);
+        } else {
+            combined.push(crossing.flipped());
+        }
+    }
+
+    combined
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::math::vec3::Vec3;
+    use crate::scene::object::geometry::{cuboid::Cuboid, cylinder::Cylinder, sphere::Sphere};
+
+    fn sphere(radius: f32, x: f32) -> Geometry {
+        Arc::new(Sphere::new(radius, Vec3::new(x, 0.0, 0.0)))
+    }
+
+    fn ray_along_x() -> Ray {
+        Ray::new(Vec3::new(-10.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0))
+    }
+
+    // the t values, normals' x components, and whether each crossing goes in
+    fn summarize(crossings: Vec<Crossing>) -> Vec<(f32, f32, bool)> {
+        crossings
+            .into_iter()
+            .map(|c| (c.t, c.normal.x, c.is_into_surface))
+            .collect()
+    }
+
+    #[test]
+    fn union_of_overlapping_spheres() {
+        let union = Union::new(vec![sphere(1.0, 0.0), sphere(1.0, 1.0)]);
+
+        assert_eq!(
+            summarize(union.crossings(&ray_along_x())),
+            vec![(9.0, -1.0, true), (12.0, 1.0, false)]
+        );
+    }
+
+    #[test]
+    fn lens_from_intersection() {
+        let lens = Intersection::new(vec![sphere(1.0, 0.0), sphere(1.0, 1.0)]);
+
+        assert_eq!(
+            summarize(lens.crossings(&ray_along_x())),
+            vec![(10.0, -1.0, true), (11.0, 1.0, false)]
+        );
+    }
+
+    #[test]
+    fn hollow_shell_from_difference() {
+        let shell = Difference::new(sphere(1.0, 0.0), vec![sphere(0.5, 0.0)]);
+
+        // the inner surface's normals point into the hollow, out of the shell
+        assert_eq!(
+            summarize(shell.crossings(&ray_along_x())),
+            vec![
+                (9.0, -1.0, true),
+                (9.5, 1.0, false),
+                (10.5, -1.0, true),
+                (11.0, 1.0, false)
+            ]
+        );
+
+        // starting in the hollow, the first thing hit is the shell, going in
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let crossing = shell.intersect_ray(&ray).unwrap();
+        assert_eq!((crossing.t, crossing.is_into_surface), (0.5, true));
+    }
+
+    #[test]
+    fn start_inside_of_solid() {
+        let shell = Difference::new(sphere(1.0, 0.0), vec![sphere(0.5, 0.0)]);
+
+        // from inside the material of the shell
+        let ray = Ray::new(Vec3::new(0.75, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let crossing = shell.intersect_ray(&ray).unwrap();
+        assert_eq!((crossing.t, crossing.is_into_surface), (0.25, false));
+    }
+
+    #[test]
+    fn drilled_block() {
+        let block: Geometry = Arc::new(Cuboid::new(
+            Vec3::new(-1.0, -1.0, -1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+        ));
+        let drill: Geometry = Arc::new(Cylinder::new(
+            Vec3::new(0.0, -2.0, 0.0),
+            Vec3::new(0.0, 2.0, 0.0),
+            0.5,
+            true,
+        ));
+        let part = Difference::new(block, vec![drill]);
+
+        // straight down the hole
+        let ray = Ray::new(Vec3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(part.intersect_ray(&ray), None);
+
+        // across the hole
+        let crossings = summarize(part.crossings(&ray_along_x()));
+        assert_eq!(
+            crossings,
+            vec![
+                (9.0, -1.0, true),
+                (9.5, 1.0, false),
+                (10.5, -1.0, true),
+                (11.0, 1.0, false)
+            ]
+        );
+    }
+
+    #[test]
+    fn default_crossings_recast_the_ray() {
+        // a plane only implements `intersect_ray`
+        use crate::scene::object::geometry::plane::Plane;
+        let plane = Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+
+        assert_eq!(
+            summarize(plane.crossings(&ray_along_x())),
+            vec![(10.0, 1.0, false)]
+        );
+    }
+}
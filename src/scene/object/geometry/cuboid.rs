@@ -0,0 +1,230 @@
+use serde::{Deserialize, Serialize};
+
+use crate::math::{ray::Ray, vec3::Vec3};
+use crate::scene::object::geometry::{IntersectRay, Intersection};
+
+use super::{all_hits, nearest_hit, NormalOrientation};
+
+// an axis-aligned box, given by its minimum and maximum corners
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cuboid {
+    min: Vec3,
+    max: Vec3,
+    orientation: NormalOrientation,
+}
+
+impl Cuboid {
+    // the corners can be any two opposite corners of the box
+    pub fn new(corner: Vec3, opposite_corner: Vec3) -> Cuboid {
+        Cuboid {
+            min: Vec3::new(
+                corner.x.min(opposite_corner.x),
+                corner.y.min(opposite_corner.y),
+                corner.z.min(opposite_corner.z),
+            ),
+            max: Vec3::new(
+                corner.x.max(opposite_corner.x),
+                corner.y.max(opposite_corner.y),
+                corner.z.max(opposite_corner.z),
+            ),
+            orientation: NormalOrientation::Outward,
+        }
+    }
+
+    pub fn flip_orientation(&mut self) {
+        self.orientation = self.orientation.flip();
+    }
+}
+
+pub(super) fn components(v: &Vec3) -> [f32; 3] {
+    [v.x, v.y, v.z]
+}
+
+// the unit vector along an axis, pointing in the positive or negative direction
+pub(super) fn axis_vector(axis: usize, positive: bool) -> Vec3 {
+    let mut components = [0.0; 3];
+    components[axis] = if positive { 1.0 } else { -1.0 };
+    Vec3::new(components[0], components[1], components[2])
+}
+
+// Texture coordinates on a face of a box, given by the axis it's perpendicular to and whether
+// it's on the positive side. u and v go from 0 to 1 along the other two axes, in the order that
+// makes dpdu x dpdv point out of the box. The primitive index says which of the six faces it is.
+pub(super) fn face_coords(
+    hit: Intersection,
+    min: &Vec3,
+    max: &Vec3,
+    axis: usize,
+    positive: bool,
+) -> Intersection {
+    let (p, min, max) = (components(&hit.point), components(min), components(max));
+    let (next, last) = ((axis + 1) % 3, (axis + 2) % 3);
+    let (u_axis, v_axis) = if positive { (next, last) } else { (last, next) };
+
+    let coord = |i: usize| (p[i] - min[i]) / (max[i] - min[i]);
+    let tangent = |i: usize| (max[i] - min[i]) * &axis_vector(i, true);
+
+    hit.with_uv(
+        (coord(u_axis), coord(v_axis)),
+        tangent(u_axis),
+        tangent(v_axis),
+    )
+    .with_primitive_index(2 * axis + positive as usize)
+}
+
+impl Cuboid {
+    fn with_face_coords(&self, hit: Intersection) -> Intersection {
+        // the face is whichever one the (outward) normal points straight out of
+        let outward = components(&self.orientation.orient(hit.normal.clone()));
+        let axis = (0..3)
+            .max_by(|&i, &j| outward[i].abs().total_cmp(&outward[j].abs()))
+            .unwrap();
+        face_coords(hit, &self.min, &self.max, axis, outward[axis] > 0.0)
+    }
+
+    // where the ray goes in and comes out of the box, along with the outward normals there
+    fn candidates(&self, ray: &Ray) -> Vec<(f32, Vec3)> {
+        let origin = components(&ray.origin);
+        let dir = components(&ray.dir);
+        let min = components(&self.min);
+        let max = components(&self.max);
+
+        // The slab method: along each axis, the ray is between the two faces for some interval
+        // of t, and the ray is inside the box where all three of those intervals overlap.
+        // Alongside each end of the overlap, keep track of which face it's on (as an outward normal).
+        let mut t_enter = f32::NEG_INFINITY;
+        let mut t_exit = f32::INFINITY;
+        let mut enter_normal = None;
+        let mut exit_normal = None;
+
+        for axis in 0..3 {
+            if dir[axis] == 0.0 {
+                // parallel to these faces, so either always between them or never
+                if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                    return vec![];
+                }
+                continue;
+            }
+
+            let t_min = (min[axis] - origin[axis]) / dir[axis];
+            let t_max = (max[axis] - origin[axis]) / dir[axis];
+            let (t_near, t_far, near_is_max) = if t_min < t_max {
+                (t_min, t_max, false)
+            } else {
+                (t_max, t_min, true)
+            };
+
+            if t_near > t_enter {
+                t_enter = t_near;
+                enter_normal = Some(axis_vector(axis, near_is_max));
+            }
+            if t_far < t_exit {
+                t_exit = t_far;
+                exit_normal = Some(axis_vector(axis, !near_is_max));
+            }
+        }
+
+        match (enter_normal, exit_normal) {
+            (Some(enter_normal), Some(exit_normal)) if t_enter <= t_exit => {
+                vec![(t_enter, enter_normal), (t_exit, exit_normal)]
+            }
+            _ => vec![],
+        }
+    }
+}
+
+// (a ray starting on or inside the box only hits it on the way out)
+#[typetag::serde]
+impl IntersectRay for Cuboid {
+    fn intersect_ray(&self, ray: &Ray) -> Option<Intersection> {
+        nearest_hit(ray, self.candidates(ray), &self.orientation)
+            .map(|hit| self.with_face_coords(hit))
+    }
+
+    fn crossings(&self, ray: &Ray) -> Vec<Intersection> {
+        all_hits(ray, self.candidates(ray), &self.orientation)
+            .into_iter()
+            .map(|hit| self.with_face_coords(hit))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_cube() -> Cuboid {
+        Cuboid::new(Vec3::new(1.0, 1.0, 1.0), Vec3::new(-1.0, -1.0, -1.0))
+    }
+
+    #[test]
+    fn ray_hits_cuboid_from_outside() {
+        let ray = Ray::new(Vec3::new(0.5, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+
+        let intersection = unit_cube().intersect_ray(&ray).unwrap();
+        assert_eq!(intersection.point, Vec3::new(0.5, 0.0, 1.0));
+        assert_eq!(intersection.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(intersection.t, 4.0);
+        assert!(intersection.is_into_surface);
+    }
+
+    #[test]
+    fn faces_have_their_own_coords() {
+        // the +z face has u along x and v along y
+        let ray = Ray::new(Vec3::new(0.5, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let intersection = unit_cube().intersect_ray(&ray).unwrap();
+        assert_eq!(intersection.uv, (0.75, 0.5));
+        assert_eq!(intersection.dpdu, Vec3::new(2.0, 0.0, 0.0));
+        assert_eq!(intersection.primitive_index, 5);
+
+        // and the -z face has them the other way around
+        let ray = Ray::new(Vec3::new(0.5, 0.0, -5.0), Vec3::new(0.0, 0.0, 1.0));
+        let intersection = unit_cube().intersect_ray(&ray).unwrap();
+        assert_eq!(intersection.uv, (0.5, 0.75));
+        assert_eq!(intersection.primitive_index, 4);
+
+        let cross = Vec3::cross(&intersection.dpdu, &intersection.dpdv).normalize();
+        assert_eq!(cross, intersection.normal);
+    }
+
+    #[test]
+    fn ray_leaves_cuboid_from_inside() {
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(-2.0, 1.0, 0.0));
+        let intersection = unit_cube().intersect_ray(&ray).unwrap();
+
+        assert_eq!(intersection.point, Vec3::new(-1.0, 0.5, 0.0));
+        assert_eq!(intersection.normal, Vec3::new(-1.0, 0.0, 0.0));
+        assert!(!intersection.is_into_surface);
+    }
+
+    #[test]
+    fn ray_starting_on_face_hits_far_side() {
+        // eg. a ray refracted into the box where it hit the top face
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let intersection = unit_cube().intersect_ray(&ray).unwrap();
+
+        assert_eq!(intersection.t, 2.0);
+        assert_eq!(intersection.normal, Vec3::new(0.0, -1.0, 0.0));
+    }
+
+    #[test]
+    fn ray_misses_cuboid() {
+        let ray = Ray::new(Vec3::new(2.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(unit_cube().intersect_ray(&ray), None);
+
+        // pointing away
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(unit_cube().intersect_ray(&ray), None);
+    }
+
+    #[test]
+    fn inward_cuboid_is_a_room() {
+        let mut room = unit_cube();
+        room.flip_orientation();
+
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let intersection = room.intersect_ray(&ray).unwrap();
+        assert_eq!(intersection.normal, Vec3::new(0.0, -1.0, 0.0));
+        assert!(intersection.is_into_surface);
+    }
+}
@@ -0,0 +1,192 @@
+use std::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::math::{ray::Ray, roots::solve_quadratic, vec3::Vec3};
+use crate::scene::object::geometry::{IntersectRay, Intersection};
+
+use super::{all_hits, around_axis, disk::disk_coords, nearest_hit, NormalOrientation};
+
+// a circular cylinder around the segment from `base` to `top`, either open or with flat caps
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cylinder {
+    base: Vec3,
+    top: Vec3,
+    radius: f32,
+    capped: bool,
+    orientation: NormalOrientation,
+}
+
+impl Cylinder {
+    pub fn new(base: Vec3, top: Vec3, radius: f32, capped: bool) -> Cylinder {
+        Cylinder {
+            base,
+            top,
+            radius,
+            capped,
+            orientation: NormalOrientation::Outward,
+        }
+    }
+
+    pub fn flip_orientation(&mut self) {
+        self.orientation = self.orientation.flip();
+    }
+
+    // On the side, u goes around the axis and v goes from the base to the top. The caps are
+    // primitives 1 (base) and 2 (top), with coordinates like a `Disk`.
+    fn with_surface_coords(&self, hit: Intersection) -> Intersection {
+        let axis = &self.top - &self.base;
+        let height = axis.length();
+        let axis = (1.0 / height) * &axis;
+
+        // the side's normals are perpendicular to the axis, and the caps' are along it
+        let outward = self.orientation.orient(hit.normal.clone());
+        let along_axis = Vec3::dot(&outward, &axis);
+        if along_axis < -0.5 {
+            return disk_coords(hit, &self.base, &-&axis, self.radius).with_primitive_index(1);
+        }
+        if along_axis > 0.5 {
+            return disk_coords(hit, &self.top, &axis, self.radius).with_primitive_index(2);
+        }
+
+        let p = &hit.point - &self.base;
+        let y = Vec3::dot(&p, &axis);
+        let (u, _, around) = around_axis(&axis, &(&p - &(y * &axis)));
+        let dpdu = (2.0 * PI * self.radius) * &around;
+        let dpdv = height * &axis;
+        hit.with_uv((u, y / height), dpdu, dpdv)
+    }
+
+    // every t where the ray meets the surface, along with the outward normal there
+    fn candidates(&self, ray: &Ray) -> Vec<(f32, Vec3)> {
+        let axis = &self.top - &self.base;
+        let height = axis.length();
+        let axis = (1.0 / height) * &axis;
+
+        // split the ray's origin (relative to the base) and direction into
+        // their components along the axis and perpendicular to it
+        let origin = &ray.origin - &self.base;
+        let origin_y = Vec3::dot(&origin, &axis);
+        let dir_y = Vec3::dot(&ray.dir, &axis);
+        let origin_perp = &origin - &(origin_y * &axis);
+        let dir_perp = &ray.dir - &(dir_y * &axis);
+
+        // the side is where the perpendicular part has length `radius`
+        let mut candidates: Vec<(f32, Vec3)> = solve_quadratic(
+            Vec3::dot(&dir_perp, &dir_perp),
+            2.0 * Vec3::dot(&origin_perp, &dir_perp),
+            Vec3::dot(&origin_perp, &origin_perp) - self.radius * self.radius,
+        )
+        .into_iter()
+        .filter(|t| (0.0..=height).contains(&(origin_y + t * dir_y)))
+        .map(|t| {
+            let perp = &origin_perp + &(t * &dir_perp);
+            (t, perp.normalize())
+        })
+        .collect();
+
+        if self.capped && dir_y != 0.0 {
+            for (cap_y, outward) in [(0.0, -&axis), (height, axis.clone())] {
+                let t = (cap_y - origin_y) / dir_y;
+                let perp = &origin_perp + &(t * &dir_perp);
+                if Vec3::dot(&perp, &perp) <= self.radius * self.radius {
+                    candidates.push((t, outward));
+                }
+            }
+        }
+
+        candidates
+    }
+}
+
+#[typetag::serde]
+impl IntersectRay for Cylinder {
+    fn intersect_ray(&self, ray: &Ray) -> Option<Intersection> {
+        nearest_hit(ray, self.candidates(ray), &self.orientation)
+            .map(|hit| self.with_surface_coords(hit))
+    }
+
+    fn crossings(&self, ray: &Ray) -> Vec<Intersection> {
+        all_hits(ray, self.candidates(ray), &self.orientation)
+            .into_iter()
+            .map(|hit| self.with_surface_coords(hit))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a pipe along the x-axis from x = -1 to x = 1
+    fn pipe(capped: bool) -> Cylinder {
+        Cylinder::new(
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            0.5,
+            capped,
+        )
+    }
+
+    #[test]
+    fn ray_hits_side() {
+        let ray = Ray::new(Vec3::new(0.25, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+
+        let intersection = pipe(false).intersect_ray(&ray).unwrap();
+        assert_eq!(intersection.point, Vec3::new(0.25, 0.5, 0.0));
+        assert_eq!(intersection.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(intersection.t, 2.5);
+        assert!(intersection.is_into_surface);
+    }
+
+    #[test]
+    fn side_and_cap_coords() {
+        // v goes along the pipe, and dpdu x dpdv points out of it
+        let ray = Ray::new(Vec3::new(0.25, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let intersection = pipe(true).intersect_ray(&ray).unwrap();
+        assert_eq!(intersection.primitive_index, 0);
+        assert_eq!(intersection.uv.1, 0.625);
+        assert_eq!(intersection.dpdv, Vec3::new(2.0, 0.0, 0.0));
+        let cross = Vec3::cross(&intersection.dpdu, &intersection.dpdv).normalize();
+        assert!((&cross - &intersection.normal).length() < 1e-6);
+
+        let ray = Ray::new(Vec3::new(3.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let intersection = pipe(true).intersect_ray(&ray).unwrap();
+        assert_eq!(intersection.primitive_index, 2);
+        assert_eq!(intersection.uv, (0.5, 0.5));
+    }
+
+    #[test]
+    fn ray_through_open_end() {
+        let ray = Ray::new(Vec3::new(-3.0, 0.1, 0.0), Vec3::new(1.0, 0.0, 0.0));
+
+        // goes all the way through without touching the side
+        assert_eq!(pipe(false).intersect_ray(&ray), None);
+
+        let intersection = pipe(true).intersect_ray(&ray).unwrap();
+        assert_eq!(intersection.point, Vec3::new(-1.0, 0.1, 0.0));
+        assert_eq!(intersection.normal, Vec3::new(-1.0, 0.0, 0.0));
+        assert!(intersection.is_into_surface);
+    }
+
+    #[test]
+    fn ray_leaves_from_inside() {
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let intersection = pipe(true).intersect_ray(&ray).unwrap();
+
+        assert_eq!(intersection.point, Vec3::new(0.0, 0.0, 0.5));
+        assert!(!intersection.is_into_surface);
+
+        let mut inward = pipe(true);
+        inward.flip_orientation();
+        let intersection = inward.intersect_ray(&ray).unwrap();
+        assert_eq!(intersection.normal, Vec3::new(0.0, 0.0, -1.0));
+        assert!(intersection.is_into_surface);
+    }
+
+    #[test]
+    fn ray_misses_past_end() {
+        let ray = Ray::new(Vec3::new(1.5, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(pipe(true).intersect_ray(&ray), None);
+    }
+}
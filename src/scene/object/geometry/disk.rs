@@ -0,0 +1,128 @@
+use serde::{Deserialize, Serialize};
+
+use crate::math::{ray::Ray, vec3::Vec3};
+use crate::scene::object::geometry::{IntersectRay, Intersection};
+
+use super::plane::PARALLEL_EPSILON;
+
+// a flat, two sided circle
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(from = "DiskParts")]
+pub struct Disk {
+    center: Vec3,
+    normal: Vec3,
+    radius: f32,
+}
+
+// what a disk looks like in a scene file, where the normal doesn't need to be normalized either
+#[derive(Deserialize)]
+struct DiskParts {
+    center: Vec3,
+    normal: Vec3,
+    radius: f32,
+}
+
+impl Disk {
+    // the normal doesn't need to be normalized
+    pub fn new(center: Vec3, normal: Vec3, radius: f32) -> Disk {
+        Disk {
+            center,
+            normal: normal.normalize(),
+            radius,
+        }
+    }
+}
+
+impl From<DiskParts> for Disk {
+    fn from(parts: DiskParts) -> Self {
+        Disk::new(parts.center, parts.normal, parts.radius)
+    }
+}
+
+// Texture coordinates on a disk with the given unit normal (also used for the caps of cylinders
+// and cones): u and v go from 0 to 1 across the square around it, like an image laid on top.
+pub(super) fn disk_coords(
+    hit: Intersection,
+    center: &Vec3,
+    unit_normal: &Vec3,
+    radius: f32,
+) -> Intersection {
+    let (tangent, bitangent) = Vec3::tangents(unit_normal);
+    let from_center = &hit.point - center;
+    let coord = |axis: &Vec3| 0.5 + 0.5 * Vec3::dot(&from_center, axis) / radius;
+    let uv = (coord(&tangent), coord(&bitangent));
+
+    let diameter = 2.0 * radius;
+    hit.with_uv(uv, diameter * &tangent, diameter * &bitangent)
+}
+
+#[typetag::serde]
+impl IntersectRay for Disk {
+    fn intersect_ray(&self, ray: &Ray) -> Option<Intersection> {
+        let normal_vs_dir = Vec3::dot(&self.normal, &ray.dir);
+        if normal_vs_dir.abs() < PARALLEL_EPSILON {
+            return None;
+        }
+
+        let t = Vec3::dot(&self.normal, &(&self.center - &ray.origin)) / normal_vs_dir;
+        if t <= 0.0 {
+            return None;
+        }
+
+        let point = ray.at(t);
+        let from_center = &point - &self.center;
+        if Vec3::dot(&from_center, &from_center) > self.radius * self.radius {
+            return None;
+        }
+
+        let hit = Intersection::new(point, self.normal.clone(), t, normal_vs_dir < 0.0);
+        Some(disk_coords(hit, &self.center, &self.normal, self.radius))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ray_hits_disk() {
+        let disk = Disk::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 2.0, 0.0), 1.0);
+        let ray = Ray::new(Vec3::new(0.5, 3.0, 0.5), Vec3::new(0.0, -1.0, 0.0));
+
+        let intersection = disk.intersect_ray(&ray).unwrap();
+        assert_eq!(intersection.point, Vec3::new(0.5, 1.0, 0.5));
+        assert_eq!(intersection.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(intersection.t, 2.0);
+        assert!(intersection.is_into_surface);
+
+        // (the tangents for +y are +x and -z)
+        assert_eq!(intersection.uv, (0.75, 0.25));
+        assert_eq!(intersection.dpdu, Vec3::new(2.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn ray_misses_disk() {
+        let disk = Disk::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 1.0);
+
+        // within the disk's bounding square, but not the disk
+        let ray = Ray::new(Vec3::new(0.8, 3.0, 0.8), Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(disk.intersect_ray(&ray), None);
+
+        let ray = Ray::new(Vec3::new(0.0, 3.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(disk.intersect_ray(&ray), None);
+    }
+
+    #[test]
+    fn deserialize_normalizes_normal() {
+        let yaml = "
+center: { x: 0.0, y: 1.0, z: 0.0 }
+normal: { x: 0.0, y: 2.0, z: 0.0 }
+radius: 1.0
+";
+        let disk: Disk = serde_yaml::from_str(yaml).unwrap();
+        let ray = Ray::new(Vec3::new(0.5, 3.0, 0.5), Vec3::new(0.0, -1.0, 0.0));
+        let intersection = disk.intersect_ray(&ray).unwrap();
+        assert_eq!(intersection.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(intersection.uv, (0.75, 0.25));
+    }
+}
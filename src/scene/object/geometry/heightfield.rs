@@ -0,0 +1,498 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::math::{ray::Ray, vec3::Vec3};
+use crate::scene::object::geometry::{IntersectRay, Intersection};
+use crate::scene::RAY_MIN_T;
+
+use super::{oriented_hit, plane::PARALLEL_EPSILON, NormalOrientation};
+
+// Terrain given by a grayscale image: the pixels are spread evenly over `width` (along x) and
+// `depth` (along z) starting from `corner`, and each one is raised above `corner` by its
+// brightness (from 0 to 1) times `height_scale`. Each square between four neighboring pixels is
+// split into two triangles, whose normals point up.
+//
+// Rather than testing every triangle, rays walk down a tree of bounding boxes (a min-max mipmap):
+// each level has the lowest and highest points in 2x2 blocks of the level below.
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "HeightfieldParts")]
+pub struct Heightfield {
+    parts: HeightfieldParts,
+
+    // the heights of the samples in world space, a row at a time (rows go along x)
+    heights: Vec<f32>,
+    columns: usize,
+    rows: usize,
+
+    // levels[0] bounds each cell between four samples, and the last level is a single node
+    levels: Vec<MinMaxLevel>,
+}
+
+// what a heightfield looks like in a scene file.
+// the image path is relative to the directory the renderer is run from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct HeightfieldParts {
+    image: PathBuf,
+    corner: Vec3,
+    width: f32,
+    depth: f32,
+    height_scale: f32,
+}
+
+#[derive(Debug)]
+struct MinMaxLevel {
+    columns: usize,
+    rows: usize,
+    bounds: Vec<(f32, f32)>,
+}
+
+impl MinMaxLevel {
+    fn at(&self, i: usize, j: usize) -> (f32, f32) {
+        self.bounds[j * self.columns + i]
+    }
+
+    // the next level up, where each node covers (up to) 2x2 nodes of this one
+    fn coarsen(&self) -> MinMaxLevel {
+        let columns = self.columns.div_ceil(2);
+        let rows = self.rows.div_ceil(2);
+        let mut bounds = Vec::with_capacity(columns * rows);
+        for j in 0..rows {
+            for i in 0..columns {
+                let children = [(0, 0), (1, 0), (0, 1), (1, 1)]
+                    .into_iter()
+                    .map(|(di, dj)| (2 * i + di, 2 * j + dj))
+                    .filter(|(ci, cj)| *ci < self.columns && *cj < self.rows);
+                bounds.push(children.fold(
+                    (f32::INFINITY, f32::NEG_INFINITY),
+                    |(min, max), (ci, cj)| {
+                        let (child_min, child_max) = self.at(ci, cj);
+                        (min.min(child_min), max.max(child_max))
+                    },
+                ));
+            }
+        }
+        MinMaxLevel {
+            columns,
+            rows,
+            bounds,
+        }
+    }
+}
+
+impl Heightfield {
+    pub fn load(
+        image: impl Into<PathBuf>,
+        corner: Vec3,
+        width: f32,
+        depth: f32,
+        height_scale: f32,
+    ) -> Result<Heightfield, String> {
+        Heightfield::try_from(HeightfieldParts {
+            image: image.into(),
+            corner,
+            width,
+            depth,
+            height_scale,
+        })
+    }
+
+    fn from_image(
+        parts: HeightfieldParts,
+        image: &image::DynamicImage,
+    ) -> Result<Heightfield, String> {
+        let image = image.to_luma32f();
+        let (columns, rows) = (image.width() as usize, image.height() as usize);
+        if columns < 2 || rows < 2 {
+            return Err(format!(
+                "heightfield image {} needs to be at least 2x2 pixels",
+                parts.image.display()
+            ));
+        }
+
+        let heights: Vec<f32> = image
+            .pixels()
+            .map(|pixel| parts.corner.y + pixel.0[0] * parts.height_scale)
+            .collect();
+
+        let mut heightfield = Heightfield {
+            parts,
+            heights,
+            columns,
+            rows,
+            levels: vec![],
+        };
+        heightfield.build_levels();
+        Ok(heightfield)
+    }
+
+    fn build_levels(&mut self) {
+        let (cell_columns, cell_rows) = (self.columns - 1, self.rows - 1);
+        let mut bounds = Vec::with_capacity(cell_columns * cell_rows);
+        for j in 0..cell_rows {
+            for i in 0..cell_columns {
+                let corners = [
+                    self.height(i, j),
+                    self.height(i + 1, j),
+                    self.height(i, j + 1),
+                    self.height(i + 1, j + 1),
+                ];
+                bounds.push((
+                    corners.into_iter().fold(f32::INFINITY, f32::min),
+                    corners.into_iter().fold(f32::NEG_INFINITY, f32::max),
+                ));
+            }
+        }
+
+        let mut levels = vec![MinMaxLevel {
+            columns: cell_columns,
+            rows: cell_rows,
+            bounds,
+        }];
+        while let Some(top) = levels.last().filter(|top| top.columns > 1 || top.rows > 1) {
+            levels.push(top.coarsen());
+        }
+        self.levels = levels;
+    }
+
+    fn height(&self, i: usize, j: usize) -> f32 {
+        self.heights[j * self.columns + i]
+    }
+
+    // where sample (i, j) is in space
+    fn sample_point(&self, i: usize, j: usize) -> Vec3 {
+        Vec3::new(self.sample_x(i), self.height(i, j), self.sample_z(j))
+    }
+
+    fn sample_x(&self, i: usize) -> f32 {
+        self.parts.corner.x + self.parts.width * i as f32 / (self.columns - 1) as f32
+    }
+
+    fn sample_z(&self, j: usize) -> f32 {
+        self.parts.corner.z + self.parts.depth * j as f32 / (self.rows - 1) as f32
+    }
+
+    // The closest hit below `t_max` with the cells under node (i, j) of the given level,
+    // as its t, the upward normal there, and which triangle it's on. Nearer children are visited
+    // first, so that farther ones can often be skipped.
+    fn hit_node(
+        &self,
+        ray: &Ray,
+        level: usize,
+        i: usize,
+        j: usize,
+        t_max: f32,
+    ) -> Option<(f32, Vec3, usize)> {
+        let (min_height, max_height) = self.levels[level].at(i, j);
+
+        // the node covers 2^level cells along each side (or fewer, at the far edges)
+        let span = 1 << level;
+        let min = Vec3::new(self.sample_x(i * span), min_height, self.sample_z(j * span));
+        let max = Vec3::new(
+            self.sample_x(((i + 1) * span).min(self.columns - 1)),
+            max_height,
+            self.sample_z(((j + 1) * span).min(self.rows - 1)),
+        );
+        slab_test(ray, &min, &max, t_max)?;
+
+        if level == 0 {
+            return self.hit_cell(ray, i, j, t_max);
+        }
+
+        let below = &self.levels[level - 1];
+        let mut children: Vec<(usize, usize)> = [(0, 0), (1, 0), (0, 1), (1, 1)]
+            .into_iter()
+            .map(|(di, dj)| (2 * i + di, 2 * j + dj))
+            .filter(|(ci, cj)| *ci < below.columns && *cj < below.rows)
+            .collect();
+
+        // nearer along the ray first, judging by where the children's centers are
+        let half = span / 2;
+        let center_t = |(ci, cj): &(usize, usize)| {
+            let x =
+                self.sample_x(ci * half) + self.sample_x(((ci + 1) * half).min(self.columns - 1));
+            let z = self.sample_z(cj * half) + self.sample_z(((cj + 1) * half).min(self.rows - 1));
+            (0.5 * x - ray.origin.x) * ray.dir.x + (0.5 * z - ray.origin.z) * ray.dir.z
+        };
+        children.sort_by(|a, b| center_t(a).total_cmp(&center_t(b)));
+
+        let mut closest: Option<(f32, Vec3, usize)> = None;
+        for (ci, cj) in children {
+            let limit = closest.as_ref().map_or(t_max, |(t, _, _)| *t);
+            if let Some(hit) = self.hit_node(ray, level - 1, ci, cj, limit) {
+                closest = Some(hit);
+            }
+        }
+        closest
+    }
+
+    // the triangles are numbered a cell at a time, a row at a time
+    fn hit_cell(&self, ray: &Ray, i: usize, j: usize, t_max: f32) -> Option<(f32, Vec3, usize)> {
+        let p00 = self.sample_point(i, j);
+        let p10 = self.sample_point(i + 1, j);
+        let p01 = self.sample_point(i, j + 1);
+        let p11 = self.sample_point(i + 1, j + 1);
+        let first_triangle = 2 * (j * (self.columns - 1) + i);
+
+        [(&p00, &p10, &p11), (&p00, &p11, &p01)]
+            .into_iter()
+            .enumerate()
+            .filter_map(|(k, (a, b, c))| {
+                let (t, normal) = hit_triangle(ray, a, b, c)?;
+                Some((t, normal, first_triangle + k))
+            })
+            .filter(|(t, _, _)| *t < t_max)
+            .min_by(|(t0, _, _), (t1, _, _)| t0.total_cmp(t1))
+    }
+
+    // the normal of the smooth surface through the samples, estimated from the neighboring samples
+    fn sample_normal(&self, i: usize, j: usize) -> Vec3 {
+        let (left, right) = (i.saturating_sub(1), (i + 1).min(self.columns - 1));
+        let (back, front) = (j.saturating_sub(1), (j + 1).min(self.rows - 1));
+        let slope_x = (self.height(right, j) - self.height(left, j))
+            / (self.sample_x(right) - self.sample_x(left));
+        let slope_z = (self.height(i, front) - self.height(i, back))
+            / (self.sample_z(front) - self.sample_z(back));
+        Vec3::new(-slope_x, 1.0, -slope_z).normalize()
+    }
+
+    // The triangles are flat, so the shading normal is blended between the normals at the cell's
+    // corners to hide their edges. u goes along x and v goes against z, so that the image appears
+    // right way up looking down on it with x to the right.
+    fn with_surface_coords(&self, hit: Intersection) -> Intersection {
+        let u = ((hit.point.x - self.parts.corner.x) / self.parts.width).clamp(0.0, 1.0);
+        let w = ((hit.point.z - self.parts.corner.z) / self.parts.depth).clamp(0.0, 1.0);
+
+        // which cell the point is in, and where in it
+        let x = u * (self.columns - 1) as f32;
+        let z = w * (self.rows - 1) as f32;
+        let i = (x as usize).min(self.columns - 2);
+        let j = (z as usize).min(self.rows - 2);
+        let (fx, fz) = (x - i as f32, z - j as f32);
+        let shading_normal = Vec3::lin_comb(vec![
+            ((1.0 - fx) * (1.0 - fz), &self.sample_normal(i, j)),
+            (fx * (1.0 - fz), &self.sample_normal(i + 1, j)),
+            ((1.0 - fx) * fz, &self.sample_normal(i, j + 1)),
+            (fx * fz, &self.sample_normal(i + 1, j + 1)),
+        ])
+        .normalize();
+
+        // the tangents stay in the plane of the triangle, so they rise and fall with it
+        let n = &hit.normal;
+        let width = self.parts.width;
+        let depth = self.parts.depth;
+        let dpdu = Vec3::new(width, -n.x * width / n.y, 0.0);
+        let dpdv = Vec3::new(0.0, n.z * depth / n.y, -depth);
+
+        Intersection {
+            shading_normal,
+            ..hit.with_uv((u, 1.0 - w), dpdu, dpdv)
+        }
+    }
+}
+
+impl TryFrom<HeightfieldParts> for Heightfield {
+    type Error = String;
+
+    fn try_from(parts: HeightfieldParts) -> Result<Self, Self::Error> {
+        let image = image::open(&parts.image).map_err(|err| {
+            format!(
+                "could not read heightfield image {}: {err}",
+                parts.image.display()
+            )
+        })?;
+        Heightfield::from_image(parts, &image)
+    }
+}
+
+// only the parts from the scene file get saved, not the heights read from the image
+impl Serialize for Heightfield {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.parts.serialize(serializer)
+    }
+}
+
+// the range of t (past `RAY_MIN_T` and before `t_max`) where the ray is inside of the box
+fn slab_test(ray: &Ray, min: &Vec3, max: &Vec3, t_max: f32) -> Option<(f32, f32)> {
+    let mut t_enter = RAY_MIN_T;
+    let mut t_exit = t_max;
+    for (origin, dir, min, max) in [
+        (ray.origin.x, ray.dir.x, min.x, max.x),
+        (ray.origin.y, ray.dir.y, min.y, max.y),
+        (ray.origin.z, ray.dir.z, min.z, max.z),
+    ] {
+        if dir == 0.0 {
+            if origin < min || origin > max {
+                return None;
+            }
+            continue;
+        }
+        let t0 = (min - origin) / dir;
+        let t1 = (max - origin) / dir;
+        t_enter = t_enter.max(t0.min(t1));
+        t_exit = t_exit.min(t0.max(t1));
+    }
+    (t_enter <= t_exit).then_some((t_enter, t_exit))
+}
+
+// Möller-Trumbore: solves for t and the barycentric coordinates of the hit all at once.
+// The normal is made to point up.
+fn hit_triangle(ray: &Ray, a: &Vec3, b: &Vec3, c: &Vec3) -> Option<(f32, Vec3)> {
+    let edge_1 = b - a;
+    let edge_2 = c - a;
+    let p = Vec3::cross(&ray.dir, &edge_2);
+    let determinant = Vec3::dot(&edge_1, &p);
+    if determinant.abs() < PARALLEL_EPSILON {
+        return None;
+    }
+
+    let from_a = &ray.origin - a;
+    let u = Vec3::dot(&from_a, &p) / determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = Vec3::cross(&from_a, &edge_1);
+    let v = Vec3::dot(&ray.dir, &q) / determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = Vec3::dot(&edge_2, &q) / determinant;
+    if t < RAY_MIN_T {
+        return None;
+    }
+
+    let normal = Vec3::cross(&edge_1, &edge_2).normalize();
+    let normal = if normal.y < 0.0 { -&normal } else { normal };
+    Some((t, normal))
+}
+
+#[typetag::serde]
+impl IntersectRay for Heightfield {
+    fn intersect_ray(&self, ray: &Ray) -> Option<Intersection> {
+        let top = self.levels.len() - 1;
+        let (t, normal, triangle) = self.hit_node(ray, top, 0, 0, f32::INFINITY)?;
+        let hit = oriented_hit(ray, t, normal, &NormalOrientation::Outward);
+        Some(self.with_surface_coords(hit.with_primitive_index(triangle)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use image::{DynamicImage, GrayImage, Luma};
+
+    use super::*;
+
+    // a 5x5 field over [0, 4] x [0, 4], flat at height 0 except for a peak of height 2 in the middle
+    fn peak() -> Heightfield {
+        let image = GrayImage::from_fn(5, 5, |x, y| Luma([if (x, y) == (2, 2) { 255 } else { 0 }]));
+        let parts = HeightfieldParts {
+            image: PathBuf::from("peak.png"),
+            corner: Vec3::new(0.0, 0.0, 0.0),
+            width: 4.0,
+            depth: 4.0,
+            height_scale: 2.0,
+        };
+        Heightfield::from_image(parts, &DynamicImage::ImageLuma8(image)).unwrap()
+    }
+
+    #[test]
+    fn min_max_levels() {
+        let field = peak();
+        let sizes: Vec<(usize, usize)> = field.levels.iter().map(|l| (l.columns, l.rows)).collect();
+        assert_eq!(sizes, vec![(4, 4), (2, 2), (1, 1)]);
+        assert_eq!(field.levels[2].at(0, 0), (0.0, 2.0));
+        assert_eq!(field.levels[0].at(0, 0), (0.0, 0.0));
+        assert_eq!(field.levels[0].at(1, 1), (0.0, 2.0));
+    }
+
+    #[test]
+    fn ray_hits_peak() {
+        let ray = Ray::new(Vec3::new(2.0, 5.0, 2.0), Vec3::new(0.0, -1.0, 0.0));
+        let intersection = peak().intersect_ray(&ray).unwrap();
+
+        assert!((intersection.t - 3.0).abs() < 1e-5);
+        assert!(intersection.is_into_surface);
+    }
+
+    #[test]
+    fn ray_hits_flat_ground() {
+        let ray = Ray::new(Vec3::new(0.5, 1.0, 3.5), Vec3::new(0.0, -1.0, 0.0));
+        let intersection = peak().intersect_ray(&ray).unwrap();
+
+        assert_eq!(intersection.point, Vec3::new(0.5, 0.0, 3.5));
+        assert_eq!(intersection.normal, Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn coords_on_flat_ground() {
+        // in the first cell of the last row, on its first triangle (the one along x)
+        let ray = Ray::new(Vec3::new(0.75, 1.0, 3.25), Vec3::new(0.0, -1.0, 0.0));
+        let intersection = peak().intersect_ray(&ray).unwrap();
+
+        assert_eq!(intersection.primitive_index, 24);
+        assert_eq!(intersection.uv, (0.1875, 0.1875));
+        assert_eq!(intersection.dpdu, Vec3::new(4.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn shading_normals_are_smooth() {
+        // at the foot of the peak the triangles are flat, but the shading normal leans away from it
+        let ray = Ray::new(Vec3::new(1.1, 1.0, 2.0), Vec3::new(0.0, -1.0, 0.0));
+        let intersection = peak().intersect_ray(&ray).unwrap();
+        assert!(intersection.normal.x < 0.0);
+
+        let ray = Ray::new(Vec3::new(0.9, 1.0, 2.0), Vec3::new(0.0, -1.0, 0.0));
+        let intersection = peak().intersect_ray(&ray).unwrap();
+        assert_eq!(intersection.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert!(intersection.shading_normal.x < 0.0);
+
+        let cross = Vec3::cross(&intersection.dpdu, &intersection.dpdv).normalize();
+        assert_eq!(cross, intersection.normal);
+    }
+
+    #[test]
+    fn ray_hits_nearer_slope_first() {
+        // skimming along x, just above the ground, through the peak
+        let ray = Ray::new(Vec3::new(-1.0, 0.5, 2.0), Vec3::new(1.0, 0.0, 0.0));
+        let intersection = peak().intersect_ray(&ray).unwrap();
+
+        // the slope from x = 1 up to the peak at x = 2 reaches height 0.5 a quarter of the way
+        assert!((intersection.point.x - 1.25).abs() < 1e-5);
+        assert!(intersection.normal.x < 0.0);
+    }
+
+    #[test]
+    fn ray_misses_outside_of_extent() {
+        let ray = Ray::new(Vec3::new(5.0, 1.0, 2.0), Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(peak().intersect_ray(&ray), None);
+
+        let ray = Ray::new(Vec3::new(-1.0, 3.0, 2.0), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(peak().intersect_ray(&ray), None);
+    }
+
+    #[test]
+    fn load_from_scene_file() {
+        let path = env::temp_dir().join("rays_heightfield_load_from_scene_file.png");
+        GrayImage::from_fn(3, 2, |x, _| Luma([x as u8 * 100]))
+            .save(&path)
+            .unwrap();
+
+        let yaml = format!(
+            "image: {}\ncorner: {{ x: 0.0, y: -1.0, z: 0.0 }}\nwidth: 2.0\ndepth: 1.0\nheight_scale: 1.0",
+            path.display()
+        );
+        let field: Heightfield = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!((field.columns, field.rows), (3, 2));
+        assert!((field.height(2, 1) - (200.0 / 255.0 - 1.0)).abs() < 1e-5);
+
+        // and it's saved as it was given
+        let saved = serde_yaml::to_string(&field).unwrap();
+        assert!(saved.contains("height_scale: 1.0"));
+
+        let missing = "image: nowhere.png\ncorner: { x: 0.0, y: 0.0, z: 0.0 }\nwidth: 1.0\ndepth: 1.0\nheight_scale: 1.0";
+        assert!(serde_yaml::from_str::<Heightfield>(missing).is_err());
+    }
+}
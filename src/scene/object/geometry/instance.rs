@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+
+use crate::math::{ray::Ray, transform::Transform};
+use crate::render::stats;
+use crate::scene::object::geometry::{IntersectRay, Intersection};
+
+// A transformed copy of some other geometry. The geometry itself is shared,
+// so placing the same thing in many spots doesn't duplicate it.
+#[derive(Serialize, Deserialize)]
+pub struct Instance {
+    geometry: Arc<dyn IntersectRay>,
+    transform: Transform,
+}
+
+impl Instance {
+    pub fn new(geometry: Arc<dyn IntersectRay>, transform: Transform) -> Instance {
+        Instance {
+            geometry,
+            transform,
+        }
+    }
+}
+
+#[typetag::serde]
+impl IntersectRay for Instance {
+    fn intersect_ray(&self, ray: &Ray) -> Option<Intersection> {
+        // since the local ray's direction isn't renormalized, t means the same thing in both spaces
+        let local_ray = self.transform.ray_to_local(ray);
+
+        stats::record_intersection_test(self.geometry.typetag_name());
+        let local = self.geometry.intersect_ray(&local_ray)?;
+        Some(local.to_world(&self.transform))
+    }
+
+    fn crossings(&self, ray: &Ray) -> Vec<Intersection> {
+        let local_ray = self.transform.ray_to_local(ray);
+
+        stats::record_intersection_test(self.geometry.typetag_name());
+        self.geometry
+            .crossings(&local_ray)
+            .into_iter()
+            .map(|local| local.to_world(&self.transform))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+    use crate::math::{quaternion::Quaternion, vec3::Vec3};
+    use crate::scene::object::geometry::sphere::Sphere;
+
+    fn assert_close(u: &Vec3, v: &Vec3) {
+        assert!((u - v).length() < 1e-5, "{u:?} != {v:?}");
+    }
+
+    #[test]
+    fn intersect_transformed_sphere() {
+        // a unit sphere stretched into an ellipsoid along x, then moved to (0, 0, -5)
+        let sphere: Arc<dyn IntersectRay> = Arc::new(Sphere::new(1.0, Vec3::new(0.0, 0.0, 0.0)));
+        let instance = Instance::new(
+            sphere,
+            Transform::new(
+                Vec3::new(0.0, 0.0, -5.0),
+                Quaternion::identity(),
+                Vec3::new(2.0, 1.0, 1.0),
+            ),
+        );
+
+        let ray = Ray::new(Vec3::new(-5.0, 0.0, -5.0), Vec3::new(1.0, 0.0, 0.0));
+        let intersection = instance.intersect_ray(&ray).unwrap();
+
+        assert!((intersection.t - 3.0).abs() < 1e-5);
+        assert_close(&intersection.point, &Vec3::new(-2.0, 0.0, -5.0));
+        assert_close(&intersection.normal, &Vec3::new(-1.0, 0.0, 0.0));
+        assert!(intersection.is_into_surface);
+    }
+
+    #[test]
+    fn share_geometry_between_instances() {
+        let sphere: Arc<dyn IntersectRay> = Arc::new(Sphere::new(1.0, Vec3::new(0.0, 0.0, 1.0)));
+        let rotated = Instance::new(
+            sphere.clone(),
+            Transform::rotation(Quaternion::from_axis_angle(&Vec3::new(0.0, 1.0, 0.0), PI)),
+        );
+        let moved = Instance::new(sphere, Transform::translation(Vec3::new(0.0, 0.0, -10.0)));
+
+        // the rotation carries the sphere from z = 1 to z = -1
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        assert_close(
+            &rotated.intersect_ray(&ray).unwrap().point,
+            &Vec3::new(0.0, 0.0, 0.0),
+        );
+        assert_close(
+            &moved.intersect_ray(&ray).unwrap().point,
+            &Vec3::new(0.0, 0.0, -8.0),
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use std::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::math::{ray::Ray, roots::solve_quadratic, vec3::Vec3};
+use crate::scene::object::geometry::{IntersectRay, Intersection};
+
+use super::{all_hits, around_axis, nearest_hit, NormalOrientation};
+
+// A bowl shaped paraboloid with its lowest point at `vertex`, opening towards `rim_center`,
+// where it's cut off in a circle with the given radius. The outside of the bowl is "outward".
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Paraboloid {
+    vertex: Vec3,
+    rim_center: Vec3,
+    radius: f32,
+    orientation: NormalOrientation,
+}
+
+impl Paraboloid {
+    pub fn new(vertex: Vec3, rim_center: Vec3, radius: f32) -> Paraboloid {
+        Paraboloid {
+            vertex,
+            rim_center,
+            radius,
+            orientation: NormalOrientation::Outward,
+        }
+    }
+
+    pub fn flip_orientation(&mut self) {
+        self.orientation = self.orientation.flip();
+    }
+
+    // u goes around the axis, and v goes from 0 at the vertex to 1 at the rim
+    // (by distance from the axis, rather than height)
+    fn with_surface_coords(&self, hit: Intersection) -> Intersection {
+        let axis = &self.rim_center - &self.vertex;
+        let height = axis.length();
+        let axis = (1.0 / height) * &axis;
+
+        let p = &hit.point - &self.vertex;
+        let perp = &p - &(Vec3::dot(&p, &axis) * &axis);
+        let (u, radial, around) = around_axis(&axis, &perp);
+        let v = perp.length() / self.radius;
+
+        // the height at v is height * v^2
+        let dpdu = (2.0 * PI * self.radius * v) * &around;
+        let dpdv = &((2.0 * height * v) * &axis) + &(self.radius * &radial);
+        hit.with_uv((u, v), dpdu, dpdv)
+    }
+
+    // every t where the ray meets the surface, along with the outward normal there
+    fn candidates(&self, ray: &Ray) -> Vec<(f32, Vec3)> {
+        let axis = &self.rim_center - &self.vertex;
+        let height = axis.length();
+        let axis = (1.0 / height) * &axis;
+
+        // split the ray's origin (relative to the vertex) and direction into
+        // their components along the axis and perpendicular to it
+        let origin = &ray.origin - &self.vertex;
+        let origin_y = Vec3::dot(&origin, &axis);
+        let dir_y = Vec3::dot(&ray.dir, &axis);
+        let origin_perp = &origin - &(origin_y * &axis);
+        let dir_perp = &ray.dir - &(dir_y * &axis);
+
+        // the surface is |perp|^2 - k y = 0, where k is chosen to put the rim at the right radius.
+        // the gradient of the left side is the outward normal
+        let k = self.radius * self.radius / height;
+
+        let candidates = solve_quadratic(
+            Vec3::dot(&dir_perp, &dir_perp),
+            2.0 * Vec3::dot(&origin_perp, &dir_perp) - k * dir_y,
+            Vec3::dot(&origin_perp, &origin_perp) - k * origin_y,
+        )
+        .into_iter()
+        .filter(|t| origin_y + t * dir_y <= height)
+        .map(|t| {
+            let perp = &origin_perp + &(t * &dir_perp);
+            let normal = &(2.0 * &perp) - &(k * &axis);
+            (t, normal.normalize())
+        });
+
+        candidates.collect()
+    }
+}
+
+#[typetag::serde]
+impl IntersectRay for Paraboloid {
+    fn intersect_ray(&self, ray: &Ray) -> Option<Intersection> {
+        nearest_hit(ray, self.candidates(ray), &self.orientation)
+            .map(|hit| self.with_surface_coords(hit))
+    }
+
+    fn crossings(&self, ray: &Ray) -> Vec<Intersection> {
+        all_hits(ray, self.candidates(ray), &self.orientation)
+            .into_iter()
+            .map(|hit| self.with_surface_coords(hit))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // y = x^2 + z^2, up to y = 1
+    fn bowl() -> Paraboloid {
+        Paraboloid::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 1.0)
+    }
+
+    fn assert_close(u: &Vec3, v: &Vec3) {
+        assert!((u - v).length() < 1e-5, "{u:?} != {v:?}");
+    }
+
+    #[test]
+    fn ray_hits_inside_of_bowl() {
+        let ray = Ray::new(Vec3::new(0.5, 3.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let intersection = bowl().intersect_ray(&ray).unwrap();
+
+        assert_close(&intersection.point, &Vec3::new(0.5, 0.25, 0.0));
+        assert_close(&intersection.normal, &Vec3::new(1.0, -1.0, 0.0).normalize());
+        assert!(!intersection.is_into_surface);
+    }
+
+    #[test]
+    fn ray_hits_outside_of_bowl() {
+        let ray = Ray::new(Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let intersection = bowl().intersect_ray(&ray).unwrap();
+
+        assert_close(&intersection.point, &Vec3::new(0.0, 0.0, 0.0));
+        assert!(intersection.is_into_surface);
+    }
+
+    #[test]
+    fn ray_misses_above_rim() {
+        let ray = Ray::new(Vec3::new(-3.0, 1.5, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(bowl().intersect_ray(&ray), None);
+    }
+}
@@ -4,6 +4,8 @@ use crate::math::ray::Ray;
 use crate::math::vec3::Vec3;
 use crate::scene::object::geometry::{IntersectRay, Intersection};
 
+pub(super) const PARALLEL_EPSILON: f32 = 1e-8;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Plane {
     basepoint: Vec3,
@@ -22,17 +24,29 @@ impl IntersectRay for Plane {
         let normal_vs_displ = Vec3::dot(&self.normal, &(&ray.origin - &self.basepoint));
         let normal_vs_dir = Vec3::dot(&self.normal, &ray.dir);
 
+        // rays (nearly) parallel to the plane never hit it, and dividing
+        // by (nearly) zero below would give infinite or NaN values of t
+        if normal_vs_dir.abs() < PARALLEL_EPSILON {
+            return None;
+        }
+
         // This condition makes planes two sided.
         // For one sided intersections, replace with this:
         //    normal_vs_displ > 0.0 && normal_vs_dir < 0.0
         let t = -normal_vs_displ / normal_vs_dir;
         if t > 0.0 {
-            Some(Intersection {
-                point: ray.at(t),
-                normal: self.normal.clone(),
-                t,
-                is_into_surface: normal_vs_displ > 0.0 && normal_vs_dir < 0.0,
-            })
+            let point = ray.at(t);
+
+            // planes go on forever, so u and v are just distances from the basepoint
+            let (dpdu, dpdv) = Vec3::tangents(&self.normal);
+            let disp = &point - &self.basepoint;
+            let uv = (Vec3::dot(&disp, &dpdu), Vec3::dot(&disp, &dpdv));
+
+            let is_into_surface = normal_vs_displ > 0.0 && normal_vs_dir < 0.0;
+            Some(
+                Intersection::new(point, self.normal.clone(), t, is_into_surface)
+                    .with_uv(uv, dpdu, dpdv),
+            )
         } else {
             None
         }
@@ -43,6 +57,7 @@ impl IntersectRay for Plane {
 mod tests {
 
     use super::*;
+    use crate::math::ray::RayDifferentials;
 
     #[test]
     fn ray_misses_plane() {
@@ -52,19 +67,63 @@ mod tests {
         assert_eq!(plane.intersect_ray(&ray), None)
     }
 
+    #[test]
+    fn ray_parallel_to_plane_misses() {
+        let ray = Ray::new(Vec3::new(0.0, 0.0, -3.0), Vec3::new(0.0, 0.0, 1.0));
+        let plane = Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+
+        assert_eq!(plane.intersect_ray(&ray), None)
+    }
+
     #[test]
     fn ray_hits_plane() {
         let ray = Ray::new(Vec3::new(0.0, 1.0, 3.0), Vec3::new(0.0, 0.0, -1.0));
         let plane = Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
 
-        assert_eq!(
-            plane.intersect_ray(&ray),
-            Some(Intersection {
-                point: Vec3::new(0.0, 1.0, 0.0),
-                normal: Vec3::new(0.0, 0.0, 1.0),
-                t: 3.0,
-                is_into_surface: true
-            })
-        )
+        let intersection = plane.intersect_ray(&ray).unwrap();
+        assert_eq!(intersection.point, Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(intersection.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(intersection.t, 3.0);
+        assert!(intersection.is_into_surface);
+    }
+
+    #[test]
+    fn floor_coords_follow_x_and_z() {
+        let ray = Ray::new(Vec3::new(2.0, 1.0, 3.0), Vec3::new(0.0, -1.0, 0.0));
+        let plane = Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let intersection = plane.intersect_ray(&ray).unwrap();
+
+        assert_eq!(intersection.uv, (2.0, -3.0));
+        assert_eq!(intersection.dpdu, Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(intersection.dpdv, Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn uv_derivatives_from_ray_differentials() {
+        let origin = Vec3::new(0.0, 1.0, 0.0);
+        let ray = Ray::new(origin.clone(), Vec3::new(0.0, -1.0, 0.0)).with_differentials(
+            RayDifferentials {
+                x_origin: origin.clone(),
+                x_dir: Vec3::new(0.1, -1.0, 0.0).normalize(),
+                y_origin: origin.clone(),
+                y_dir: Vec3::new(0.0, -1.0, 0.2).normalize(),
+            },
+        );
+        let plane = Plane::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let mut intersection = plane.intersect_ray(&ray).unwrap();
+        intersection.compute_uv_derivatives(&ray);
+
+        let close =
+            |a: (f32, f32), b: (f32, f32)| (a.0 - b.0).abs() < 1e-5 && (a.1 - b.1).abs() < 1e-5;
+        assert!(
+            close(intersection.duvdx, (0.1, 0.0)),
+            "{:?}",
+            intersection.duvdx
+        );
+        assert!(
+            close(intersection.duvdy, (0.0, -0.2)),
+            "{:?}",
+            intersection.duvdy
+        );
     }
 }
@@ -0,0 +1,125 @@
+use serde::{Deserialize, Serialize};
+
+use crate::math::{ray::Ray, vec3::Vec3};
+use crate::scene::object::geometry::{IntersectRay, Intersection};
+
+use super::plane::PARALLEL_EPSILON;
+
+// A parallelogram with one corner at `corner` and sides `edge_u` and `edge_v`.
+// Like `Plane`, it's two sided; its normal is the direction of `edge_u` x `edge_v`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Quad {
+    corner: Vec3,
+    edge_u: Vec3,
+    edge_v: Vec3,
+}
+
+impl Quad {
+    pub fn new(corner: Vec3, edge_u: Vec3, edge_v: Vec3) -> Quad {
+        Quad {
+            corner,
+            edge_u,
+            edge_v,
+        }
+    }
+}
+
+#[typetag::serde]
+impl IntersectRay for Quad {
+    fn intersect_ray(&self, ray: &Ray) -> Option<Intersection> {
+        let n = Vec3::cross(&self.edge_u, &self.edge_v);
+        let n_vs_dir = Vec3::dot(&n, &ray.dir);
+        if n_vs_dir.abs() < PARALLEL_EPSILON {
+            return None;
+        }
+
+        let t = Vec3::dot(&n, &(&self.corner - &ray.origin)) / n_vs_dir;
+        if t <= 0.0 {
+            return None;
+        }
+
+        // Write the hit point as corner + a * edge_u + b * edge_v; it's on the quad when
+        // both a and b are between 0 and 1. Crossing with the edges isolates each coefficient.
+        let point = ray.at(t);
+        let p = &point - &self.corner;
+        let n_squared = Vec3::dot(&n, &n);
+        let a = Vec3::dot(&n, &Vec3::cross(&p, &self.edge_v)) / n_squared;
+        let b = Vec3::dot(&n, &Vec3::cross(&self.edge_u, &p)) / n_squared;
+        if !(0.0..=1.0).contains(&a) || !(0.0..=1.0).contains(&b) {
+            return None;
+        }
+
+        Some(
+            Intersection::new(point, n.normalize(), t, n_vs_dir < 0.0).with_uv(
+                (a, b),
+                self.edge_u.clone(),
+                self.edge_v.clone(),
+            ),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the unit square in the xy-plane, facing +z
+    fn square() -> Quad {
+        Quad::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+        )
+    }
+
+    #[test]
+    fn ray_hits_quad() {
+        let ray = Ray::new(Vec3::new(0.25, 0.75, 2.0), Vec3::new(0.0, 0.0, -1.0));
+
+        let intersection = square().intersect_ray(&ray).unwrap();
+        assert_eq!(intersection.point, Vec3::new(0.25, 0.75, 0.0));
+        assert_eq!(intersection.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(intersection.t, 2.0);
+        assert!(intersection.is_into_surface);
+
+        // u and v are how far along each edge the point is
+        assert_eq!(intersection.uv, (0.25, 0.75));
+        assert_eq!(intersection.dpdv, Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn ray_hits_back_of_quad() {
+        let ray = Ray::new(Vec3::new(0.5, 0.5, -1.0), Vec3::new(0.0, 0.0, 1.0));
+        let intersection = square().intersect_ray(&ray).unwrap();
+
+        assert_eq!(intersection.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert!(!intersection.is_into_surface);
+    }
+
+    #[test]
+    fn ray_misses_quad() {
+        // passes the plane outside of the square
+        let ray = Ray::new(Vec3::new(1.5, 0.5, 2.0), Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(square().intersect_ray(&ray), None);
+
+        // parallel to it
+        let ray = Ray::new(Vec3::new(0.5, 0.5, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(square().intersect_ray(&ray), None);
+    }
+
+    #[test]
+    fn ray_hits_slanted_parallelogram() {
+        let quad = Quad::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+        );
+
+        // inside the parallelogram but outside its bounding square's left half
+        let ray = Ray::new(Vec3::new(2.5, 0.9, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert!(quad.intersect_ray(&ray).is_some());
+
+        let ray = Ray::new(Vec3::new(0.2, 0.9, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(quad.intersect_ray(&ray), None);
+    }
+}
@@ -0,0 +1,201 @@
+use serde::{Deserialize, Serialize};
+
+use crate::math::{ray::Ray, vec3::Vec3};
+use crate::scene::object::geometry::{IntersectRay, Intersection, SurfaceAttribute};
+
+pub mod combinators;
+pub mod fractals;
+pub mod primitives;
+
+// A signed distance function: how far a point is from a surface, negative inside of it.
+// For sphere tracing to be safe, this shouldn't overestimate the distance.
+#[typetag::serde]
+pub trait DistanceField: Send + Sync {
+    fn distance(&self, p: &Vec3) -> f32;
+
+    // fractals iterate a function at each point, and can say how close that orbit gets to
+    // some trap (eg. the origin), which gets passed on to materials as the intersection's attribute
+    fn orbit_trap(&self, _p: &Vec3) -> Option<f32> {
+        None
+    }
+}
+
+// Geometry given by a distance field, intersected by sphere tracing: stepping along the ray by
+// the distance to the surface, which can't skip past anything, until that distance is tiny.
+#[derive(Serialize, Deserialize)]
+pub struct Sdf {
+    field: Box<dyn DistanceField>,
+
+    // how far along a ray to look before giving up
+    #[serde(default = "default_max_distance")]
+    max_distance: f32,
+
+    // fields that overestimate distances (eg. twisted ones) need smaller steps to not overshoot
+    #[serde(default = "default_step_scale")]
+    step_scale: f32,
+}
+
+fn default_max_distance() -> f32 {
+    100.0
+}
+
+fn default_step_scale() -> f32 {
+    1.0
+}
+
+// how close to the surface counts as on it
+const HIT_DISTANCE: f32 = 1e-4;
+const MAX_STEPS: u32 = 512;
+
+// fractals can be zero in whole regions, so only try so hard to get off of the surface
+const MAX_ESCAPE_STEPS: u32 = 16;
+
+// step size for the finite differences used to compute normals
+const GRADIENT_STEP: f32 = 1e-4;
+
+impl Sdf {
+    pub fn new(field: Box<dyn DistanceField>) -> Sdf {
+        Sdf {
+            field,
+            max_distance: default_max_distance(),
+            step_scale: default_step_scale(),
+        }
+    }
+
+    pub fn with_step_scale(mut self, step_scale: f32) -> Sdf {
+        self.step_scale = step_scale;
+        self
+    }
+
+    pub fn with_max_distance(mut self, max_distance: f32) -> Sdf {
+        self.max_distance = max_distance;
+        self
+    }
+
+    // the gradient of the distance field, which points out of the surface
+    fn normal(&self, p: &Vec3) -> Vec3 {
+        let h = GRADIENT_STEP;
+        let d = |x: f32, y: f32, z: f32| self.field.distance(&Vec3::new(p.x + x, p.y + y, p.z + z));
+        Vec3::new(
+            d(h, 0.0, 0.0) - d(-h, 0.0, 0.0),
+            d(0.0, h, 0.0) - d(0.0, -h, 0.0),
+            d(0.0, 0.0, h) - d(0.0, 0.0, -h),
+        )
+        .normalize()
+    }
+}
+
+#[typetag::serde]
+impl IntersectRay for Sdf {
+    fn intersect_ray(&self, ray: &Ray) -> Option<Intersection> {
+        // distances are measured in space, so steps need to be converted to ray parameters
+        let dir_length = ray.dir.length();
+        let t_max = self.max_distance / dir_length;
+        let mut t = 0.0;
+
+        // a ray leaving a surface starts out right on it, so first get clear of it
+        let mut distance = self.field.distance(&ray.origin);
+        for _ in 0..MAX_ESCAPE_STEPS {
+            if distance.abs() >= HIT_DISTANCE {
+                break;
+            }
+            t += 2.0 * HIT_DISTANCE / dir_length;
+            distance = self.field.distance(&ray.at(t));
+        }
+
+        // rays starting inside of the surface march towards where it's zero from below
+        let side = distance.signum();
+
+        for _ in 0..MAX_STEPS {
+            if t > t_max {
+                return None;
+            }
+
+            let point = ray.at(t);
+            let distance = side * self.field.distance(&point);
+            if distance < HIT_DISTANCE {
+                let normal = self.normal(&point);
+                let is_into_surface = Vec3::dot(&ray.dir, &normal) < 0.0;
+                // (distance fields have no natural texture coordinates)
+                let trap = self.field.orbit_trap(&point);
+                return Some(
+                    Intersection::new(point, normal, t, is_into_surface)
+                        .with_attribute(trap.map(SurfaceAttribute::Value)),
+                );
+            }
+
+            t += self.step_scale * distance / dir_length;
+        }
+
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::primitives::{Capsule, Sphere};
+    use super::*;
+
+    fn assert_close(u: &Vec3, v: &Vec3) {
+        assert!((u - v).length() < 1e-3, "{u:?} != {v:?}");
+    }
+
+    #[test]
+    fn trace_sphere() {
+        let sdf = Sdf::new(Box::new(Sphere::new(Vec3::new(0.0, 0.0, -5.0), 1.0)));
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, -2.0));
+        let intersection = sdf.intersect_ray(&ray).unwrap();
+
+        assert!((intersection.t - 2.0).abs() < 1e-3);
+        assert_close(&intersection.normal, &Vec3::new(0.0, 0.0, 1.0));
+        assert!(intersection.is_into_surface);
+    }
+
+    #[test]
+    fn leave_surface_from_inside() {
+        let sdf = Sdf::new(Box::new(Sphere::new(Vec3::new(0.0, 0.0, 0.0), 1.0)));
+
+        // starting on the near side of the sphere, pointing in
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 1.0), Vec3::new(0.0, 0.0, -1.0));
+        let intersection = sdf.intersect_ray(&ray).unwrap();
+
+        assert_close(&intersection.point, &Vec3::new(0.0, 0.0, -1.0));
+        assert!(!intersection.is_into_surface);
+    }
+
+    #[test]
+    fn miss_field() {
+        let sdf = Sdf::new(Box::new(Capsule::new(
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            0.5,
+        )));
+        let ray = Ray::new(Vec3::new(0.0, 2.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(sdf.intersect_ray(&ray), None);
+    }
+
+    #[test]
+    fn deserialize_field_tree() {
+        let yaml = r#"
+field:
+  SmoothUnion:
+    smoothness: 0.25
+    shapes:
+    - Sphere:
+        center: { x: 0.0, y: 0.0, z: 0.0 }
+        radius: 1.0
+    - Twist:
+        rate: 0.5
+        field:
+          RoundedCuboid:
+            center: { x: 0.0, y: 1.0, z: 0.0 }
+            half_size: { x: 0.5, y: 1.0, z: 0.5 }
+            radius: 0.1
+step_scale: 0.5
+"#;
+        let sdf: Sdf = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(sdf.step_scale, 0.5);
+        assert_eq!(sdf.max_distance, default_max_distance());
+        assert!(sdf.field.distance(&Vec3::new(0.0, 0.0, 0.0)) < 0.0);
+    }
+}
@@ -0,0 +1,221 @@
+use serde::{Deserialize, Serialize};
+
+use crate::math::vec3::Vec3;
+
+use super::DistanceField;
+
+// all of the shapes together, with the seams between them filleted over `smoothness`
+// (a smoothness of zero is a plain union)
+#[derive(Serialize, Deserialize)]
+pub struct SmoothUnion {
+    shapes: Vec<Box<dyn DistanceField>>,
+    #[serde(default)]
+    smoothness: f32,
+}
+
+// `base` with the shapes in `subtract` carved out of it, with the edges of the cuts rounded over
+// `smoothness`
+#[derive(Serialize, Deserialize)]
+pub struct Subtraction {
+    base: Box<dyn DistanceField>,
+    subtract: Vec<Box<dyn DistanceField>>,
+    #[serde(default)]
+    smoothness: f32,
+}
+
+// infinitely many copies of a field, one in each cell of a grid of the given size;
+// a size of zero along an axis means no repetition along it.
+// (the field should fit inside of the cell around the origin for distances to stay correct)
+#[derive(Serialize, Deserialize)]
+pub struct Repetition {
+    field: Box<dyn DistanceField>,
+    period: Vec3,
+}
+
+// a field twisted around the y-axis by `rate` radians per unit of height.
+// this stretches distances, so an `Sdf` using it will usually need a `step_scale` below 1
+#[derive(Serialize, Deserialize)]
+pub struct Twist {
+    field: Box<dyn DistanceField>,
+    rate: f32,
+}
+
+impl SmoothUnion {
+    pub fn new(shapes: Vec<Box<dyn DistanceField>>, smoothness: f32) -> SmoothUnion {
+        SmoothUnion { shapes, smoothness }
+    }
+}
+
+impl Subtraction {
+    pub fn new(
+        base: Box<dyn DistanceField>,
+        subtract: Vec<Box<dyn DistanceField>>,
+        smoothness: f32,
+    ) -> Subtraction {
+        Subtraction {
+            base,
+            subtract,
+            smoothness,
+        }
+    }
+}
+
+impl Repetition {
+    pub fn new(field: Box<dyn DistanceField>, period: Vec3) -> Repetition {
+        Repetition { field, period }
+    }
+
+    // where the point is in its copy of the field
+    fn local(&self, p: &Vec3) -> Vec3 {
+        Vec3::new(
+            wrap(p.x, self.period.x),
+            wrap(p.y, self.period.y),
+            wrap(p.z, self.period.z),
+        )
+    }
+}
+
+impl Twist {
+    pub fn new(field: Box<dyn DistanceField>, rate: f32) -> Twist {
+        Twist { field, rate }
+    }
+
+    // undoes the twist by rotating the point back around the y-axis
+    fn untwist(&self, p: &Vec3) -> Vec3 {
+        let angle = -self.rate * p.y;
+        let (sin, cos) = angle.sin_cos();
+        Vec3::new(cos * p.x - sin * p.z, p.y, sin * p.x + cos * p.z)
+    }
+}
+
+// the polynomial smooth minimum: the same as `min` when `a` and `b` are more than `k` apart,
+// and blended between them (and a bit below both) otherwise
+fn smooth_min(a: f32, b: f32, k: f32) -> f32 {
+    if k <= 0.0 {
+        return a.min(b);
+    }
+    let h = (k - (a - b).abs()).max(0.0) / k;
+    a.min(b) - h * h * k * 0.25
+}
+
+// the position of `x` in the cell of size `period` centered around 0
+fn wrap(x: f32, period: f32) -> f32 {
+    if period == 0.0 {
+        x
+    } else {
+        x - period * (x / period).round()
+    }
+}
+
+#[typetag::serde]
+impl DistanceField for SmoothUnion {
+    fn distance(&self, p: &Vec3) -> f32 {
+        self.shapes
+            .iter()
+            .map(|shape| shape.distance(p))
+            .reduce(|a, b| smooth_min(a, b, self.smoothness))
+            .unwrap_or(f32::INFINITY)
+    }
+
+    // from whichever shape is closest
+    fn orbit_trap(&self, p: &Vec3) -> Option<f32> {
+        self.shapes
+            .iter()
+            .min_by(|a, b| a.distance(p).total_cmp(&b.distance(p)))?
+            .orbit_trap(p)
+    }
+}
+
+#[typetag::serde]
+impl DistanceField for Subtraction {
+    fn distance(&self, p: &Vec3) -> f32 {
+        // outside of a shape is the negative of its distance, and the subtraction is the
+        // intersection of the base with the outsides of everything else
+        self.subtract
+            .iter()
+            .fold(self.base.distance(p), |d, shape| {
+                -smooth_min(-d, shape.distance(p), self.smoothness)
+            })
+    }
+
+    fn orbit_trap(&self, p: &Vec3) -> Option<f32> {
+        self.base.orbit_trap(p)
+    }
+}
+
+#[typetag::serde]
+impl DistanceField for Repetition {
+    fn distance(&self, p: &Vec3) -> f32 {
+        self.field.distance(&self.local(p))
+    }
+
+    fn orbit_trap(&self, p: &Vec3) -> Option<f32> {
+        self.field.orbit_trap(&self.local(p))
+    }
+}
+
+#[typetag::serde]
+impl DistanceField for Twist {
+    fn distance(&self, p: &Vec3) -> f32 {
+        self.field.distance(&self.untwist(p))
+    }
+
+    fn orbit_trap(&self, p: &Vec3) -> Option<f32> {
+        self.field.orbit_trap(&self.untwist(p))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::object::geometry::sdf::primitives::{Cuboid, Sphere};
+
+    fn sphere(x: f32, radius: f32) -> Box<dyn DistanceField> {
+        Box::new(Sphere::new(Vec3::new(x, 0.0, 0.0), radius))
+    }
+
+    #[test]
+    fn smooth_union_fills_in_seams() {
+        let sharp = SmoothUnion::new(vec![sphere(-1.0, 1.0), sphere(1.0, 1.0)], 0.0);
+        let smooth = SmoothUnion::new(vec![sphere(-1.0, 1.0), sphere(1.0, 1.0)], 0.5);
+
+        // where the spheres touch, the smooth union bulges out past them
+        let seam = Vec3::new(0.0, 0.5, 0.0);
+        assert_eq!(sharp.distance(&seam), 1.25f32.sqrt() - 1.0);
+        assert!(smooth.distance(&seam) < 0.0);
+
+        // far away from the seam, nothing changes
+        let p = Vec3::new(3.0, 0.0, 0.0);
+        assert_eq!(smooth.distance(&p), 1.0);
+    }
+
+    #[test]
+    fn subtraction_carves_out_shapes() {
+        let cut = Subtraction::new(sphere(0.0, 1.0), vec![sphere(1.0, 1.0)], 0.0);
+
+        assert!(cut.distance(&Vec3::new(-0.5, 0.0, 0.0)) < 0.0);
+        assert_eq!(cut.distance(&Vec3::new(0.5, 0.0, 0.0)), 0.5);
+    }
+
+    #[test]
+    fn repetition_copies_field() {
+        let grid = Repetition::new(sphere(0.0, 0.5), Vec3::new(2.0, 0.0, 2.0));
+
+        assert_eq!(grid.distance(&Vec3::new(4.0, 0.0, -6.0)), -0.5);
+        assert_eq!(grid.distance(&Vec3::new(4.0, 2.0, -6.0)), 1.5);
+    }
+
+    #[test]
+    fn twist_rotates_with_height() {
+        // a quarter turn over a height of 1
+        let bar = Cuboid::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 2.0, 0.1));
+        let twisted = Twist::new(Box::new(bar), std::f32::consts::FRAC_PI_2);
+
+        // at the bottom, the bar is along x
+        assert!(twisted.distance(&Vec3::new(0.9, 0.0, 0.0)) < 0.0);
+
+        // a unit up, it's along z
+        assert!(twisted.distance(&Vec3::new(0.0, 1.0, 0.9)) < 0.0);
+        assert!(twisted.distance(&Vec3::new(0.9, 1.0, 0.0)) > 0.0);
+    }
+}
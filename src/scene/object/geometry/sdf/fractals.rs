@@ -0,0 +1,295 @@
+use serde::{Deserialize, Serialize};
+
+use crate::math::{quaternion::Quaternion, vec3::Vec3};
+
+use super::primitives::box_distance;
+use super::DistanceField;
+
+// Fractals with distance estimators: each point is iterated through some function until it
+// escapes past `bailout`, and how quickly that happens gives a lower bound for the distance to
+// the set of points that never escape. More iterations give finer detail, but take longer.
+//
+// The orbit traps here are the closest each orbit comes to the origin (after the first step).
+
+// the 3D analogue of the Mandelbrot set: z -> z^power + p, with z^power computed in spherical
+// coordinates. centered at the origin, with a radius around 1.2 for the usual power of 8
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Mandelbulb {
+    #[serde(default = "default_mandelbulb_power")]
+    power: f32,
+    #[serde(default = "default_iterations")]
+    iterations: u32,
+    #[serde(default = "default_mandelbulb_bailout")]
+    bailout: f32,
+}
+
+// the points p for which z -> z^2 + c, starting from z = p, stays bounded, where z is a
+// quaternion. this is a 3D slice of a 4D set, taking z = (p.x, p.y, p.z, 0)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuaternionJulia {
+    c: Quaternion,
+    #[serde(default = "default_iterations")]
+    iterations: u32,
+    #[serde(default = "default_julia_bailout")]
+    bailout: f32,
+}
+
+// a cube from -1 to 1 along each axis, with a cross cut out of the middle of each face, and then
+// the same done to each of the 20 cubes that remain around the edges, and so on
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MengerSponge {
+    #[serde(default = "default_menger_iterations")]
+    iterations: u32,
+}
+
+fn default_iterations() -> u32 {
+    10
+}
+
+fn default_mandelbulb_power() -> f32 {
+    8.0
+}
+
+fn default_mandelbulb_bailout() -> f32 {
+    2.0
+}
+
+fn default_julia_bailout() -> f32 {
+    4.0
+}
+
+fn default_menger_iterations() -> u32 {
+    4
+}
+
+impl Mandelbulb {
+    pub fn new(power: f32, iterations: u32, bailout: f32) -> Mandelbulb {
+        Mandelbulb {
+            power,
+            iterations,
+            bailout,
+        }
+    }
+
+    // the estimated distance, and the orbit trap
+    fn iterate(&self, p: &Vec3) -> (f32, f32) {
+        if let Some(bound) = outside_bailout(p, self.bailout) {
+            return bound;
+        }
+
+        let mut z = p.clone();
+        // the derivative of the length of z with respect to p
+        let mut dr = 1.0;
+        let mut r = z.length();
+        let mut trap = f32::INFINITY;
+
+        for _ in 0..self.iterations {
+            if r > self.bailout {
+                break;
+            }
+
+            if r > 0.0 {
+                let theta = (z.z / r).acos() * self.power;
+                let phi = z.y.atan2(z.x) * self.power;
+                dr = r.powf(self.power - 1.0) * self.power * dr + 1.0;
+
+                let zr = r.powf(self.power);
+                let (sin_theta, cos_theta) = theta.sin_cos();
+                let (sin_phi, cos_phi) = phi.sin_cos();
+                let powered = zr * &Vec3::new(sin_theta * cos_phi, sin_theta * sin_phi, cos_theta);
+                z = &powered + p;
+            } else {
+                z = p.clone();
+                dr = 1.0;
+            }
+
+            r = z.length();
+            trap = trap.min(r);
+        }
+
+        (estimate(r, dr), trap)
+    }
+}
+
+impl QuaternionJulia {
+    pub fn new(c: Quaternion, iterations: u32, bailout: f32) -> QuaternionJulia {
+        QuaternionJulia {
+            c,
+            iterations,
+            bailout,
+        }
+    }
+
+    // the estimated distance, and the orbit trap
+    fn iterate(&self, p: &Vec3) -> (f32, f32) {
+        if let Some(bound) = outside_bailout(p, self.bailout) {
+            return bound;
+        }
+
+        let mut z = Quaternion::new(p.x, p.y, p.z, 0.0);
+        let mut dr = 1.0;
+        let mut r = z.length();
+        let mut trap = f32::INFINITY;
+
+        for _ in 0..self.iterations {
+            if r > self.bailout {
+                break;
+            }
+
+            // |z^2|' = 2|z||z'|
+            dr *= 2.0 * r;
+            z = &(&z * &z) + &self.c;
+
+            r = z.length();
+            trap = trap.min(r);
+        }
+
+        (estimate(r, dr), trap)
+    }
+}
+
+impl MengerSponge {
+    pub fn new(iterations: u32) -> MengerSponge {
+        MengerSponge { iterations }
+    }
+
+    // the distance, and the orbit trap
+    fn iterate(&self, p: &Vec3) -> (f32, f32) {
+        let mut distance = box_distance(p, &Vec3::new(1.0, 1.0, 1.0));
+        let mut trap = f32::INFINITY;
+
+        // at each level, cut out crosses from the copies of the cube, which are `scale` times smaller
+        let mut scale = 1.0;
+        for _ in 0..self.iterations {
+            // where the point is in its copy of the cube, from -1 to 1 along each axis
+            let cell = |x: f32| (x * scale).rem_euclid(2.0) - 1.0;
+            let a = Vec3::new(cell(p.x), cell(p.y), cell(p.z));
+            scale *= 3.0;
+
+            // the cross is three bars through the middle third of the cube
+            let r = Vec3::new(
+                (1.0 - 3.0 * a.x.abs()).abs(),
+                (1.0 - 3.0 * a.y.abs()).abs(),
+                (1.0 - 3.0 * a.z.abs()).abs(),
+            );
+            let bars = r.x.max(r.y).min(r.y.max(r.z)).min(r.z.max(r.x));
+            distance = distance.max((bars - 1.0) / scale);
+
+            trap = trap.min(a.length());
+        }
+
+        (distance, trap)
+    }
+}
+
+// Everything outside of the bailout sphere escapes right away, so the distance to that sphere is a
+// safe step, and a better one than the estimate (which overestimates far away). Close to the
+// sphere this gives up, so that rays don't stop at it as if it were the surface.
+fn outside_bailout(p: &Vec3, bailout: f32) -> Option<(f32, f32)> {
+    let r = p.length();
+    (r - bailout > BAILOUT_MARGIN).then_some((r - bailout, r))
+}
+
+const BAILOUT_MARGIN: f32 = 0.1;
+
+// The distance estimate for an escape-time fractal, given the length of z after iterating and its
+// derivative. Points that never escape end up at or below zero.
+fn estimate(r: f32, dr: f32) -> f32 {
+    if r <= 0.0 {
+        return 0.0;
+    }
+    0.5 * r * r.ln() / dr
+}
+
+#[typetag::serde]
+impl DistanceField for Mandelbulb {
+    fn distance(&self, p: &Vec3) -> f32 {
+        self.iterate(p).0
+    }
+
+    fn orbit_trap(&self, p: &Vec3) -> Option<f32> {
+        Some(self.iterate(p).1)
+    }
+}
+
+#[typetag::serde]
+impl DistanceField for QuaternionJulia {
+    fn distance(&self, p: &Vec3) -> f32 {
+        self.iterate(p).0
+    }
+
+    fn orbit_trap(&self, p: &Vec3) -> Option<f32> {
+        Some(self.iterate(p).1)
+    }
+}
+
+#[typetag::serde]
+impl DistanceField for MengerSponge {
+    fn distance(&self, p: &Vec3) -> f32 {
+        self.iterate(p).0
+    }
+
+    fn orbit_trap(&self, p: &Vec3) -> Option<f32> {
+        Some(self.iterate(p).1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::ray::Ray;
+    use crate::scene::object::geometry::{sdf::Sdf, IntersectRay};
+
+    #[test]
+    fn mandelbulb_bounds() {
+        let bulb = Mandelbulb::new(8.0, 10, 2.0);
+
+        // the origin is in the set, and far away points are at least as far as the bailout sphere
+        assert!(bulb.distance(&Vec3::new(0.0, 0.0, 0.0)) <= 0.0);
+        assert_eq!(bulb.distance(&Vec3::new(0.0, 5.0, 0.0)), 3.0);
+
+        // the surface crosses the x-axis a bit inside of the unit sphere
+        let sdf = Sdf::new(Box::new(bulb));
+        let ray = Ray::new(Vec3::new(5.0, 0.0, 0.0), Vec3::new(-1.0, 0.0, 0.0));
+        let intersection = sdf.intersect_ray(&ray).unwrap();
+        assert!((0.8..1.0).contains(&intersection.point.x));
+        assert!(intersection.attribute_value().is_some());
+    }
+
+    #[test]
+    fn julia_set_with_zero_c_is_unit_ball() {
+        // with c = 0, z -> z^2 stays bounded exactly when |z| <= 1
+        let julia = QuaternionJulia::new(Quaternion::new(0.0, 0.0, 0.0, 0.0), 20, 4.0);
+
+        assert!(julia.distance(&Vec3::new(0.5, 0.0, 0.0)) <= 0.0);
+        assert!(julia.distance(&Vec3::new(0.0, 1.5, 0.0)) > 0.0);
+
+        let sdf = Sdf::new(Box::new(julia));
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 5.0), Vec3::new(0.0, 0.0, -1.0));
+        let intersection = sdf.intersect_ray(&ray).unwrap();
+        assert!((intersection.point.z - 1.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn menger_sponge_has_holes() {
+        let sponge = MengerSponge::new(3);
+
+        // the middle of each face is cut out all the way through
+        assert!(sponge.distance(&Vec3::new(0.0, 0.0, 0.0)) > 0.0);
+        assert!(sponge.distance(&Vec3::new(0.0, 0.0, 0.9)) > 0.0);
+
+        // but the corners are solid
+        assert!(sponge.distance(&Vec3::new(0.95, 0.95, 0.95)) < 0.0);
+
+        // and outside, it's the same as the cube
+        assert!((sponge.distance(&Vec3::new(0.9, 0.9, 3.0)) - 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn deserialize_with_defaults() {
+        let bulb: Mandelbulb = serde_yaml::from_str("iterations: 6").unwrap();
+        assert_eq!(bulb.power, 8.0);
+        assert_eq!(bulb.iterations, 6);
+        assert_eq!(bulb.bailout, 2.0);
+    }
+}
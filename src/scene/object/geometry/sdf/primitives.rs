@@ -0,0 +1,194 @@
+use serde::{Deserialize, Serialize};
+
+use crate::math::vec3::Vec3;
+
+use super::DistanceField;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Sphere {
+    center: Vec3,
+    radius: f32,
+}
+
+// an axis-aligned box, given by its center and half of its width, height and depth
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Cuboid {
+    center: Vec3,
+    half_size: Vec3,
+}
+
+// a box with its edges and corners rounded off with the given radius,
+// without growing past `half_size`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RoundedCuboid {
+    center: Vec3,
+    half_size: Vec3,
+    radius: f32,
+}
+
+// the points within `radius` of the segment between `start` and `end`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Capsule {
+    start: Vec3,
+    end: Vec3,
+    radius: f32,
+}
+
+// a ring around the y-axis through `center`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Torus {
+    center: Vec3,
+    major_radius: f32,
+    minor_radius: f32,
+}
+
+impl Sphere {
+    pub fn new(center: Vec3, radius: f32) -> Sphere {
+        Sphere { center, radius }
+    }
+}
+
+impl Cuboid {
+    pub fn new(center: Vec3, half_size: Vec3) -> Cuboid {
+        Cuboid { center, half_size }
+    }
+}
+
+impl RoundedCuboid {
+    pub fn new(center: Vec3, half_size: Vec3, radius: f32) -> RoundedCuboid {
+        RoundedCuboid {
+            center,
+            half_size,
+            radius,
+        }
+    }
+}
+
+impl Capsule {
+    pub fn new(start: Vec3, end: Vec3, radius: f32) -> Capsule {
+        Capsule { start, end, radius }
+    }
+}
+
+impl Torus {
+    pub fn new(center: Vec3, major_radius: f32, minor_radius: f32) -> Torus {
+        Torus {
+            center,
+            major_radius,
+            minor_radius,
+        }
+    }
+}
+
+// the distance to a box centered at the origin
+pub(super) fn box_distance(p: &Vec3, half_size: &Vec3) -> f32 {
+    // how far outside of each pair of faces the point is (negative when between them)
+    let q = Vec3::new(
+        p.x.abs() - half_size.x,
+        p.y.abs() - half_size.y,
+        p.z.abs() - half_size.z,
+    );
+    let outside = Vec3::new(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).length();
+    let inside = q.x.max(q.y).max(q.z).min(0.0);
+    outside + inside
+}
+
+#[typetag::serde]
+impl DistanceField for Sphere {
+    fn distance(&self, p: &Vec3) -> f32 {
+        (p - &self.center).length() - self.radius
+    }
+}
+
+#[typetag::serde]
+impl DistanceField for Cuboid {
+    fn distance(&self, p: &Vec3) -> f32 {
+        box_distance(&(p - &self.center), &self.half_size)
+    }
+}
+
+#[typetag::serde]
+impl DistanceField for RoundedCuboid {
+    fn distance(&self, p: &Vec3) -> f32 {
+        // a smaller box, inflated by the radius
+        let r = self.radius;
+        let shrunk = Vec3::new(
+            self.half_size.x - r,
+            self.half_size.y - r,
+            self.half_size.z - r,
+        );
+        box_distance(&(p - &self.center), &shrunk) - r
+    }
+}
+
+#[typetag::serde]
+impl DistanceField for Capsule {
+    fn distance(&self, p: &Vec3) -> f32 {
+        // the distance to the closest point on the segment
+        let from_start = p - &self.start;
+        let segment = &self.end - &self.start;
+        let h = (Vec3::dot(&from_start, &segment) / Vec3::dot(&segment, &segment)).clamp(0.0, 1.0);
+        (&from_start - &(h * &segment)).length() - self.radius
+    }
+}
+
+#[typetag::serde]
+impl DistanceField for Torus {
+    fn distance(&self, p: &Vec3) -> f32 {
+        let p = p - &self.center;
+        let from_axis = (p.x * p.x + p.z * p.z).sqrt() - self.major_radius;
+        (from_axis * from_axis + p.y * p.y).sqrt() - self.minor_radius
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_distance(field: &dyn DistanceField, p: Vec3, expected: f32) {
+        let distance = field.distance(&p);
+        assert!(
+            (distance - expected).abs() < 1e-5,
+            "{distance} != {expected}"
+        );
+    }
+
+    #[test]
+    fn cuboid_distances() {
+        let cuboid = Cuboid::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 2.0, 3.0));
+        assert_distance(&cuboid, Vec3::new(3.0, 0.0, 0.0), 2.0);
+        assert_distance(&cuboid, Vec3::new(0.0, 1.5, 0.0), -0.5);
+        // off a corner
+        assert_distance(&cuboid, Vec3::new(4.0, 6.0, 3.0), 5.0);
+    }
+
+    #[test]
+    fn rounded_cuboid_distances() {
+        let cuboid = RoundedCuboid::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0), 0.5);
+        // faces stay where they are
+        assert_distance(&cuboid, Vec3::new(2.0, 0.0, 0.0), 1.0);
+        // corners get rounded off
+        let corner = 0.5 + 0.5 / 3.0f32.sqrt();
+        assert_distance(
+            &cuboid,
+            Vec3::new(1.0, 1.0, 1.0),
+            (3.0f32 * 0.25).sqrt() - 0.5,
+        );
+        assert!(cuboid.distance(&Vec3::new(corner, corner, corner)).abs() < 1e-5);
+    }
+
+    #[test]
+    fn capsule_distances() {
+        let capsule = Capsule::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 2.0, 0.0), 0.5);
+        assert_distance(&capsule, Vec3::new(1.0, 1.0, 0.0), 0.5);
+        assert_distance(&capsule, Vec3::new(0.0, 4.0, 0.0), 1.5);
+    }
+
+    #[test]
+    fn torus_distances() {
+        let torus = Torus::new(Vec3::new(0.0, 1.0, 0.0), 2.0, 0.5);
+        assert_distance(&torus, Vec3::new(2.0, 1.0, 0.0), -0.5);
+        assert_distance(&torus, Vec3::new(0.0, 1.0, 0.0), 1.5);
+        assert_distance(&torus, Vec3::new(0.0, 3.0, 2.0), 1.5);
+    }
+}
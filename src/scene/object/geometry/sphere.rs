@@ -1,9 +1,11 @@
+use std::f32::consts::PI;
+
 use serde::{Deserialize, Serialize};
 
-use crate::math::{ray::Ray, vec3::Vec3};
+use crate::math::{ray::Ray, roots::solve_quadratic, vec3::Vec3};
 use crate::scene::object::geometry::{IntersectRay, Intersection};
 
-use super::NormalOrientation;
+use super::{all_hits, NormalOrientation};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Sphere {
@@ -21,10 +23,23 @@ impl Sphere {
         }
     }
     pub fn flip_orientation(&mut self) {
-        self.orientation = match self.orientation {
-            NormalOrientation::Outward => NormalOrientation::Inward,
-            NormalOrientation::Inward => NormalOrientation::Outward,
-        };
+        self.orientation = self.orientation.flip();
+    }
+
+    // u goes around the y-axis (counterclockwise seen from above, starting at +x),
+    // and v goes from 0 at the bottom to 1 at the top
+    fn with_surface_coords(&self, hit: Intersection) -> Intersection {
+        let p = &hit.point - &self.center;
+        let phi = (-p.z).atan2(p.x).rem_euclid(2.0 * PI);
+        let theta = (p.y / self.radius).clamp(-1.0, 1.0).acos();
+
+        // the distance from the y-axis
+        let rho = p.x.hypot(p.z);
+        let (sin_phi, cos_phi) = phi.sin_cos();
+        let dpdu = Vec3::new(2.0 * PI * p.z, 0.0, -2.0 * PI * p.x);
+        let dpdv = PI * &Vec3::new(-p.y * cos_phi, rho, p.y * sin_phi);
+
+        hit.with_uv((phi / (2.0 * PI), 1.0 - theta / PI), dpdu, dpdv)
     }
 }
 
@@ -69,16 +84,28 @@ impl IntersectRay for Sphere {
                 NormalOrientation::Inward => ((&self.center) - &point).normalize(),
             };
 
-            Some(Intersection {
-                point,
-                normal,
-                t,
-                is_into_surface,
-            })
+            let hit = Intersection::new(point, normal, t, is_into_surface);
+            Some(self.with_surface_coords(hit))
         } else {
             None
         }
     }
+
+    fn crossings(&self, ray: &Ray) -> Vec<Intersection> {
+        let disp = &ray.origin - &self.center;
+        let candidates = solve_quadratic(
+            Vec3::dot(&ray.dir, &ray.dir),
+            2.0 * Vec3::dot(&ray.dir, &disp),
+            Vec3::dot(&disp, &disp) - self.radius.powi(2),
+        )
+        .into_iter()
+        .map(|t| (t, (1.0 / self.radius) * &(&ray.at(t) - &self.center)));
+
+        all_hits(ray, candidates, &self.orientation)
+            .into_iter()
+            .map(|hit| self.with_surface_coords(hit))
+            .collect()
+    }
 }
 
 #[cfg(test)]
@@ -93,15 +120,33 @@ mod tests {
             center: Vec3::new(0.0, 0.0, 0.0),
             orientation: NormalOrientation::Outward,
         };
-        assert_eq!(
-            sphere.intersect_ray(&ray),
-            Some(Intersection {
-                point: Vec3::new(0.0, 0.0, -1.0),
-                normal: Vec3::new(0.0, 0.0, -1.0),
-                t: 2.0,
-                is_into_surface: true
-            })
-        )
+        let intersection = sphere.intersect_ray(&ray).unwrap();
+        assert_eq!(intersection.point, Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(intersection.normal, Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(intersection.shading_normal, intersection.normal);
+        assert_eq!(intersection.t, 2.0);
+        assert!(intersection.is_into_surface);
+    }
+
+    #[test]
+    fn surface_coords_on_sphere() {
+        let sphere = Sphere::new(2.0, Vec3::new(0.0, 0.0, 5.0));
+
+        // hitting the side facing -z, halfway up, which is a quarter of the way around
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 0.0, 1.0));
+        let intersection = sphere.intersect_ray(&ray).unwrap();
+        let (u, v) = intersection.uv;
+        assert!((u - 0.25).abs() < 1e-6 && (v - 0.5).abs() < 1e-6);
+
+        // the tangents have the lengths of the circles they go around, and face the right way
+        assert!((&intersection.dpdu - &Vec3::new(-4.0 * PI, 0.0, 0.0)).length() < 1e-4);
+        assert!((&intersection.dpdv - &Vec3::new(0.0, 2.0 * PI, 0.0)).length() < 1e-4);
+        let cross = Vec3::cross(&intersection.dpdu, &intersection.dpdv).normalize();
+        assert!((&cross - &intersection.normal).length() < 1e-5);
+
+        // the top of the sphere is at v = 1
+        let ray = Ray::new(Vec3::new(0.0, 10.0, 5.0), Vec3::new(0.0, -1.0, 0.0));
+        assert!((sphere.intersect_ray(&ray).unwrap().uv.1 - 1.0).abs() < 1e-6);
     }
 
     #[test]
@@ -0,0 +1,197 @@
+use std::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::math::{ray::Ray, roots::solve_quartic, vec3::Vec3};
+use crate::scene::object::geometry::{IntersectRay, Intersection};
+
+use super::{all_hits, around_axis, nearest_hit, NormalOrientation};
+
+// A ring around `axis` (which doesn't need to be normalized): the points at distance
+// `minor_radius` from the circle of radius `major_radius` around `center`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Torus {
+    center: Vec3,
+    axis: Vec3,
+    major_radius: f32,
+    minor_radius: f32,
+    orientation: NormalOrientation,
+}
+
+impl Torus {
+    pub fn new(center: Vec3, axis: Vec3, major_radius: f32, minor_radius: f32) -> Torus {
+        Torus {
+            center,
+            axis,
+            major_radius,
+            minor_radius,
+            orientation: NormalOrientation::Outward,
+        }
+    }
+
+    pub fn flip_orientation(&mut self) {
+        self.orientation = self.orientation.flip();
+    }
+
+    // u goes around the axis, and v goes around the tube, starting from its outermost edge
+    // and heading towards the tip of the axis first
+    fn with_surface_coords(&self, hit: Intersection) -> Intersection {
+        let axis = self.axis.normalize();
+        let p = &hit.point - &self.center;
+        let y = Vec3::dot(&p, &axis);
+        let perp = &p - &(y * &axis);
+        let (u, radial, around) = around_axis(&axis, &perp);
+
+        // the angle around the circle through the middle of the tube
+        let tube_angle = y
+            .atan2(perp.length() - self.major_radius)
+            .rem_euclid(2.0 * PI);
+        let (sin, cos) = tube_angle.sin_cos();
+
+        let dpdu = (2.0 * PI * perp.length()) * &around;
+        let dpdv =
+            (2.0 * PI * self.minor_radius) * &Vec3::lin_comb(vec![(-sin, &radial), (cos, &axis)]);
+        hit.with_uv((u, tube_angle / (2.0 * PI)), dpdu, dpdv)
+    }
+
+    // every t where the ray meets the surface, along with the outward normal there
+    fn candidates(&self, ray: &Ray) -> Vec<(f32, Vec3)> {
+        let axis = self.axis.normalize();
+
+        // Quartics are solved with a lot less precision than quadratics, so first move the ray's
+        // origin up to the torus' bounding sphere (when it's outside of it) to keep numbers small.
+        let bound = (self.major_radius + self.minor_radius) as f64;
+        let disp = &ray.origin - &self.center;
+        let dd = Vec3::dot(&ray.dir, &ray.dir) as f64;
+        let od = Vec3::dot(&disp, &ray.dir) as f64;
+        let oo = Vec3::dot(&disp, &disp) as f64;
+        let bound_disc = od * od - dd * (oo - bound * bound);
+        if bound_disc < 0.0 {
+            return vec![];
+        }
+        let t_start = ((-od - bound_disc.sqrt()) / dd).max(0.0);
+
+        let origin = [disp.x, disp.y, disp.z].map(|c| c as f64);
+        let dir = [ray.dir.x, ray.dir.y, ray.dir.z].map(|c| c as f64);
+        let axis64 = [axis.x, axis.y, axis.z].map(|c| c as f64);
+        let dot = |u: &[f64; 3], v: &[f64; 3]| u[0] * v[0] + u[1] * v[1] + u[2] * v[2];
+        let origin: [f64; 3] = [0, 1, 2].map(|i| origin[i] + t_start * dir[i]);
+
+        // With p = o + t d relative to the center and y = p . axis, the torus is
+        //    (|p|^2 + R^2 - r^2)^2 = 4 R^2 (|p|^2 - y^2)
+        // Writing |p|^2 = A t^2 + B t + C and expanding gives a quartic in t.
+        let major_sq = (self.major_radius as f64).powi(2);
+        let minor_sq = (self.minor_radius as f64).powi(2);
+        let a = dd;
+        let b = 2.0 * dot(&origin, &dir);
+        let c = dot(&origin, &origin);
+        let origin_y = dot(&origin, &axis64);
+        let dir_y = dot(&dir, &axis64);
+        let k = c + major_sq - minor_sq;
+
+        let roots = solve_quartic(
+            a * a,
+            2.0 * a * b,
+            b * b + 2.0 * a * k - 4.0 * major_sq * (a - dir_y * dir_y),
+            2.0 * b * k - 4.0 * major_sq * (b - 2.0 * origin_y * dir_y),
+            k * k - 4.0 * major_sq * (c - origin_y * origin_y),
+        );
+
+        let candidates = roots.into_iter().map(|t| {
+            let t = (t + t_start) as f32;
+
+            // the gradient of the equation above, with the common factor of 4 dropped
+            let p = &ray.at(t) - &self.center;
+            let y = Vec3::dot(&p, &axis);
+            let p_sq = Vec3::dot(&p, &p);
+            let major_sq = self.major_radius.powi(2);
+            let normal = Vec3::lin_comb(vec![
+                (
+                    p_sq + major_sq - self.minor_radius.powi(2) - 2.0 * major_sq,
+                    &p,
+                ),
+                (2.0 * major_sq * y, &axis),
+            ]);
+            (t, normal.normalize())
+        });
+
+        candidates.collect()
+    }
+}
+
+#[typetag::serde]
+impl IntersectRay for Torus {
+    fn intersect_ray(&self, ray: &Ray) -> Option<Intersection> {
+        nearest_hit(ray, self.candidates(ray), &self.orientation)
+            .map(|hit| self.with_surface_coords(hit))
+    }
+
+    fn crossings(&self, ray: &Ray) -> Vec<Intersection> {
+        all_hits(ray, self.candidates(ray), &self.orientation)
+            .into_iter()
+            .map(|hit| self.with_surface_coords(hit))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a ring around the y-axis with radius 2, made of a tube with radius 0.5
+    fn ring() -> Torus {
+        Torus::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 3.0, 0.0), 2.0, 0.5)
+    }
+
+    fn assert_close(u: &Vec3, v: &Vec3) {
+        assert!((u - v).length() < 1e-4, "{u:?} != {v:?}");
+    }
+
+    #[test]
+    fn ray_hits_outside_of_ring() {
+        let ray = Ray::new(Vec3::new(-10.0, 0.0, 0.0), Vec3::new(1.0, 0.0, 0.0));
+        let intersection = ring().intersect_ray(&ray).unwrap();
+
+        assert!((intersection.t - 7.5).abs() < 1e-4);
+        assert_close(&intersection.normal, &Vec3::new(-1.0, 0.0, 0.0));
+        assert!(intersection.is_into_surface);
+    }
+
+    #[test]
+    fn ray_through_hole_misses() {
+        let ray = Ray::new(Vec3::new(0.0, 10.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(ring().intersect_ray(&ray), None);
+    }
+
+    #[test]
+    fn ray_hits_top_of_tube() {
+        let ray = Ray::new(Vec3::new(2.0, 10.0, 0.0), Vec3::new(0.0, -2.0, 0.0));
+        let intersection = ring().intersect_ray(&ray).unwrap();
+
+        assert_close(&intersection.point, &Vec3::new(2.0, 0.5, 0.0));
+        assert_close(&intersection.normal, &Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn ray_leaves_tube_from_inside() {
+        let ray = Ray::new(Vec3::new(0.0, 0.0, 2.0), Vec3::new(0.0, 0.0, 1.0));
+        let intersection = ring().intersect_ray(&ray).unwrap();
+
+        assert_close(&intersection.point, &Vec3::new(0.0, 0.0, 2.5));
+        assert!(!intersection.is_into_surface);
+    }
+
+    #[test]
+    fn coords_on_top_of_tube() {
+        let ray = Ray::new(Vec3::new(2.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let intersection = ring().intersect_ray(&ray).unwrap();
+
+        // a quarter of the way around the tube, starting from the outside
+        let (u, v) = intersection.uv;
+        assert!(u.abs() < 1e-5 && (v - 0.25).abs() < 1e-5);
+        assert_close(&intersection.dpdv, &Vec3::new(-PI, 0.0, 0.0));
+
+        let cross = Vec3::cross(&intersection.dpdu, &intersection.dpdv).normalize();
+        assert_close(&cross, &intersection.normal);
+    }
+}
@@ -0,0 +1,473 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::math::{ray::Ray, vec3::Vec3};
+use crate::scene::object::geometry::{IntersectRay, Intersection, SurfaceAttribute};
+use crate::scene::RAY_MIN_T;
+
+use super::cuboid::{axis_vector, components, face_coords};
+use super::{oriented_hit, NormalOrientation};
+
+pub mod vox;
+
+// A grid of solid cubes, each either empty or filled with one of 255 palette entries
+// (which the `Palette` material can map to materials, or to a .vox file's own colors). The grid
+// starts at `corner` and has `size` voxels along each axis, each `voxel_size` wide.
+//
+// Rays walk through the grid a voxel at a time (with a 3D DDA), and stop at the first face between
+// two voxels that aren't the same.
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "VoxelGridParts")]
+pub struct VoxelGrid {
+    parts: VoxelGridParts,
+    size: [usize; 3],
+    voxels: Voxels,
+}
+
+// what a voxel grid looks like in a scene file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct VoxelGridParts {
+    corner: Vec3,
+    #[serde(default = "default_voxel_size")]
+    voxel_size: f32,
+    // written like the geometries and materials, as a map with the variant's name
+    #[serde(with = "serde_yaml::with::singleton_map")]
+    voxels: VoxelSource,
+}
+
+fn default_voxel_size() -> f32 {
+    1.0
+}
+
+// palette indices are 1 to 255, and 0 is empty
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum VoxelSource {
+    // a MagicaVoxel file (relative to the directory the renderer is run from)
+    Vox(PathBuf),
+
+    // every voxel, with x changing fastest, then y, then z
+    Dense {
+        size: [usize; 3],
+        indices: Vec<u8>,
+    },
+
+    // only the filled voxels
+    Sparse {
+        size: [usize; 3],
+        voxels: Vec<([usize; 3], u8)>,
+    },
+}
+
+// mostly empty grids are stored sparsely, to not take up memory for all of that empty space
+#[derive(Debug)]
+enum Voxels {
+    Dense(Vec<u8>),
+    Sparse(HashMap<[usize; 3], u8>),
+}
+
+impl VoxelGrid {
+    pub fn load_vox(
+        path: impl Into<PathBuf>,
+        corner: Vec3,
+        voxel_size: f32,
+    ) -> Result<VoxelGrid, String> {
+        VoxelGrid::try_from(VoxelGridParts {
+            corner,
+            voxel_size,
+            voxels: VoxelSource::Vox(path.into()),
+        })
+    }
+
+    pub fn dense(
+        corner: Vec3,
+        voxel_size: f32,
+        size: [usize; 3],
+        indices: Vec<u8>,
+    ) -> Result<VoxelGrid, String> {
+        VoxelGrid::try_from(VoxelGridParts {
+            corner,
+            voxel_size,
+            voxels: VoxelSource::Dense { size, indices },
+        })
+    }
+
+    pub fn sparse(
+        corner: Vec3,
+        voxel_size: f32,
+        size: [usize; 3],
+        voxels: Vec<([usize; 3], u8)>,
+    ) -> Result<VoxelGrid, String> {
+        VoxelGrid::try_from(VoxelGridParts {
+            corner,
+            voxel_size,
+            voxels: VoxelSource::Sparse { size, voxels },
+        })
+    }
+
+    fn from_filled(
+        parts: VoxelGridParts,
+        size: [usize; 3],
+        filled: Vec<([usize; 3], u8)>,
+    ) -> Result<VoxelGrid, String> {
+        let total = voxel_count(size)?;
+        if let Some((cell, _)) = filled
+            .iter()
+            .find(|(cell, _)| (0..3).any(|a| cell[a] >= size[a]))
+        {
+            return Err(format!(
+                "voxel {cell:?} is outside of a grid of size {size:?}"
+            ));
+        }
+
+        let voxels = if filled.len() * 8 < total {
+            Voxels::Sparse(
+                filled
+                    .into_iter()
+                    .filter(|(_, index)| *index != 0)
+                    .collect(),
+            )
+        } else {
+            let mut indices = vec![0; total];
+            for (cell, index) in filled {
+                indices[cell[0] + size[0] * (cell[1] + size[1] * cell[2])] = index;
+            }
+            Voxels::Dense(indices)
+        };
+
+        Ok(VoxelGrid {
+            parts,
+            size,
+            voxels,
+        })
+    }
+
+    // A hit on a face of one of the voxels, given by the axis it's perpendicular to and whether
+    // it's on the voxel's positive side. Texture coordinates are per face, like a `Cuboid`'s,
+    // and the primitive index says which voxel it is (counting along x, then y, then z).
+    fn voxel_hit(
+        &self,
+        hit: Intersection,
+        cell: [usize; 3],
+        axis: usize,
+        positive: bool,
+        palette_index: u8,
+    ) -> Intersection {
+        let voxel_size = self.parts.voxel_size;
+        let min = &self.parts.corner
+            + &(voxel_size * &Vec3::new(cell[0] as f32, cell[1] as f32, cell[2] as f32));
+        let max = &min + &Vec3::new(voxel_size, voxel_size, voxel_size);
+
+        let primitive_index = cell[0] + self.size[0] * (cell[1] + self.size[1] * cell[2]);
+        face_coords(hit, &min, &max, axis, positive)
+            .with_primitive_index(primitive_index)
+            .with_attribute(Some(SurfaceAttribute::Id(palette_index)))
+    }
+
+    // the palette index at a cell, where 0 is empty
+    fn at(&self, cell: [usize; 3]) -> u8 {
+        match &self.voxels {
+            Voxels::Dense(indices) => {
+                indices[cell[0] + self.size[0] * (cell[1] + self.size[1] * cell[2])]
+            }
+            Voxels::Sparse(voxels) => voxels.get(&cell).copied().unwrap_or(0),
+        }
+    }
+}
+
+impl TryFrom<VoxelGridParts> for VoxelGrid {
+    type Error = String;
+
+    fn try_from(parts: VoxelGridParts) -> Result<Self, Self::Error> {
+        match &parts.voxels {
+            VoxelSource::Vox(path) => {
+                let model = vox::load(path)?;
+                VoxelGrid::from_filled(parts, model.size, model.voxels)
+            }
+            VoxelSource::Dense { size, indices } => {
+                let size = *size;
+                let total = voxel_count(size)?;
+                if indices.len() != total {
+                    return Err(format!(
+                        "a dense grid of size {size:?} needs {total} voxels, not {}",
+                        indices.len()
+                    ));
+                }
+                let filled = indices
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, index)| **index != 0)
+                    .map(|(i, index)| {
+                        let cell = [
+                            i % size[0],
+                            (i / size[0]) % size[1],
+                            i / (size[0] * size[1]),
+                        ];
+                        (cell, *index)
+                    })
+                    .collect();
+                VoxelGrid::from_filled(parts, size, filled)
+            }
+            VoxelSource::Sparse { size, voxels } => {
+                let (size, voxels) = (*size, voxels.clone());
+                VoxelGrid::from_filled(parts, size, voxels)
+            }
+        }
+    }
+}
+
+// how many voxels there are in a grid, which has to have at least one along each axis
+fn voxel_count(size: [usize; 3]) -> Result<usize, String> {
+    if size.contains(&0) {
+        return Err(format!("a voxel grid can't have a size of {size:?}"));
+    }
+    size[0]
+        .checked_mul(size[1])
+        .and_then(|count| count.checked_mul(size[2]))
+        .ok_or(format!("a voxel grid of size {size:?} is too big"))
+}
+
+// only the parts from the scene file get saved
+impl Serialize for VoxelGrid {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.parts.serialize(serializer)
+    }
+}
+
+#[typetag::serde]
+impl IntersectRay for VoxelGrid {
+    fn intersect_ray(&self, ray: &Ray) -> Option<Intersection> {
+        let origin = components(&ray.origin);
+        let dir = components(&ray.dir);
+        let corner = components(&self.parts.corner);
+        let voxel_size = self.parts.voxel_size;
+
+        // first, clip the ray to the grid's bounds (the slab method, like `Cuboid`),
+        // keeping track of which face it came in through
+        let mut t_enter = RAY_MIN_T;
+        let mut t_exit = f32::INFINITY;
+        let mut enter_axis = None;
+        for axis in 0..3 {
+            let min = corner[axis];
+            let max = corner[axis] + voxel_size * self.size[axis] as f32;
+            if dir[axis] == 0.0 {
+                if origin[axis] < min || origin[axis] > max {
+                    return None;
+                }
+                continue;
+            }
+            let t0 = (min - origin[axis]) / dir[axis];
+            let t1 = (max - origin[axis]) / dir[axis];
+            if t0.min(t1) > t_enter {
+                t_enter = t0.min(t1);
+                enter_axis = Some(axis);
+            }
+            t_exit = t_exit.min(t0.max(t1));
+        }
+        if t_enter > t_exit {
+            return None;
+        }
+
+        // the cell the ray starts in, nudged inside in case it's right on the boundary
+        let start = ray.at(t_enter + (t_exit - t_enter).min(RAY_MIN_T) * 0.5);
+        let start = components(&start);
+        let mut cell = [0; 3];
+        for axis in 0..3 {
+            let i = ((start[axis] - corner[axis]) / voxel_size).floor();
+            cell[axis] = (i.max(0.0) as usize).min(self.size[axis] - 1);
+        }
+
+        // A ray that starts outside of the grid starts out in empty space, but one that starts
+        // inside starts in whatever voxel it's in. Either way, it stops at the first voxel that's
+        // different from that.
+        let (starting_index, mut t, mut axis) = match enter_axis {
+            Some(axis) => (0, t_enter, axis),
+            None => (self.at(cell), RAY_MIN_T, 0),
+        };
+
+        let step: [isize; 3] = dir.map(|d| if d > 0.0 { 1 } else { -1 });
+        let mut t_next = [f32::INFINITY; 3];
+        let mut t_delta = [f32::INFINITY; 3];
+        for a in 0..3 {
+            if dir[a] != 0.0 {
+                let boundary =
+                    corner[a] + voxel_size * (cell[a] as f32 + (step[a] > 0) as u8 as f32);
+                t_next[a] = (boundary - origin[a]) / dir[a];
+                t_delta[a] = voxel_size / dir[a].abs();
+            }
+        }
+
+        loop {
+            // (when the ray comes in from outside, this first check is at the face it came through)
+            let index = self.at(cell);
+            if index != starting_index && t >= RAY_MIN_T {
+                // the face belongs to the voxel being entered, or the one being left if it's empty
+                let (index, voxel, positive) = if index != 0 {
+                    (index, cell, step[axis] < 0)
+                } else {
+                    let mut previous = cell;
+                    previous[axis] = previous[axis].wrapping_add_signed(-step[axis]);
+                    (starting_index, previous, step[axis] > 0)
+                };
+                let outward = axis_vector(axis, positive);
+                let hit = oriented_hit(ray, t, outward, &NormalOrientation::Outward);
+                return Some(self.voxel_hit(hit, voxel, axis, positive, index));
+            }
+
+            axis = (0..3)
+                .min_by(|a, b| t_next[*a].total_cmp(&t_next[*b]))
+                .unwrap();
+            t = t_next[axis];
+            t_next[axis] += t_delta[axis];
+
+            match cell[axis].checked_add_signed(step[axis]) {
+                Some(next) if next < self.size[axis] => cell[axis] = next,
+                // leaving the grid, which is a surface only if the ray was inside of a voxel
+                _ if starting_index != 0 => {
+                    let positive = step[axis] > 0;
+                    let outward = axis_vector(axis, positive);
+                    let hit = oriented_hit(ray, t, outward, &NormalOrientation::Outward);
+                    return Some(self.voxel_hit(hit, cell, axis, positive, starting_index));
+                }
+                _ => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a 3x3x3 grid of unit voxels from the origin, with the middle one and the one above it filled
+    fn grid() -> VoxelGrid {
+        VoxelGrid::sparse(
+            Vec3::new(0.0, 0.0, 0.0),
+            1.0,
+            [3, 3, 3],
+            vec![([1, 1, 1], 5), ([1, 2, 1], 7)],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn hit_from_outside() {
+        let ray = Ray::new(Vec3::new(1.5, 1.5, 10.0), Vec3::new(0.0, 0.0, -2.0));
+        let intersection = grid().intersect_ray(&ray).unwrap();
+
+        assert_eq!(intersection.point, Vec3::new(1.5, 1.5, 2.0));
+        assert_eq!(intersection.normal, Vec3::new(0.0, 0.0, 1.0));
+        assert_eq!(intersection.t, 4.0);
+        assert!(intersection.is_into_surface);
+        assert_eq!(intersection.attribute_id(), Some(5));
+
+        // the middle voxel, and the middle of its face
+        assert_eq!(intersection.primitive_index, 13);
+        assert_eq!(intersection.uv, (0.5, 0.5));
+    }
+
+    #[test]
+    fn hit_on_grid_boundary() {
+        // the top voxel is right at the top of the grid
+        let ray = Ray::new(Vec3::new(1.5, 5.0, 1.5), Vec3::new(0.0, -1.0, 0.0));
+        let intersection = grid().intersect_ray(&ray).unwrap();
+
+        assert_eq!(intersection.point, Vec3::new(1.5, 3.0, 1.5));
+        assert_eq!(intersection.normal, Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(intersection.attribute_id(), Some(7));
+        assert_eq!(intersection.primitive_index, 16);
+    }
+
+    #[test]
+    fn walk_diagonally() {
+        let ray = Ray::new(Vec3::new(0.6, 0.5, 0.2), Vec3::new(1.0, 1.0, 1.0));
+        let intersection = grid().intersect_ray(&ray).unwrap();
+
+        // crosses into x = 1, then y = 1, and finally z = 1, where the filled voxel is
+        assert_eq!(intersection.normal, Vec3::new(0.0, 0.0, -1.0));
+        assert!((intersection.t - 0.8).abs() < 1e-6);
+        assert_eq!(intersection.attribute_id(), Some(5));
+    }
+
+    #[test]
+    fn leave_voxel_from_inside() {
+        let ray = Ray::new(Vec3::new(1.5, 1.5, 1.5), Vec3::new(1.0, 0.0, 0.0));
+        let intersection = grid().intersect_ray(&ray).unwrap();
+
+        assert_eq!(intersection.point, Vec3::new(2.0, 1.5, 1.5));
+        assert_eq!(intersection.normal, Vec3::new(1.0, 0.0, 0.0));
+        assert!(!intersection.is_into_surface);
+        assert_eq!(intersection.attribute_id(), Some(5));
+        assert_eq!(intersection.primitive_index, 13);
+    }
+
+    #[test]
+    fn start_on_face() {
+        // eg. scattered off of the side of the middle voxel
+        let ray = Ray::new(Vec3::new(2.0, 1.5, 1.5), Vec3::new(1.0, 0.2, 0.0));
+        assert_eq!(grid().intersect_ray(&ray), None);
+
+        // and refracted into it
+        let ray = Ray::new(Vec3::new(2.0, 1.5, 1.5), Vec3::new(-1.0, 0.0, 0.0));
+        let intersection = grid().intersect_ray(&ray).unwrap();
+        assert_eq!(intersection.point, Vec3::new(1.0, 1.5, 1.5));
+        assert!(!intersection.is_into_surface);
+    }
+
+    #[test]
+    fn miss_grid() {
+        let ray = Ray::new(Vec3::new(0.5, 0.5, 10.0), Vec3::new(0.0, 0.0, -1.0));
+        assert_eq!(grid().intersect_ray(&ray), None);
+
+        let ray = Ray::new(Vec3::new(5.0, 5.0, 5.0), Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(grid().intersect_ray(&ray), None);
+    }
+
+    #[test]
+    fn dense_and_sparse_agree() {
+        let mut indices = vec![0; 27];
+        indices[1 + 3 * (1 + 3)] = 5;
+        indices[1 + 3 * (2 + 3)] = 7;
+        let dense = VoxelGrid::dense(Vec3::new(0.0, 0.0, 0.0), 1.0, [3, 3, 3], indices).unwrap();
+
+        let ray = Ray::new(Vec3::new(1.5, 5.0, 1.5), Vec3::new(0.0, -1.0, 0.0));
+        assert_eq!(dense.intersect_ray(&ray), grid().intersect_ray(&ray));
+
+        assert!(VoxelGrid::dense(Vec3::new(0.0, 0.0, 0.0), 1.0, [3, 3, 3], vec![0; 5]).is_err());
+
+        // only mostly empty grids get stored sparsely
+        assert!(matches!(dense.voxels, Voxels::Sparse(_)));
+        let full = VoxelGrid::dense(Vec3::new(0.0, 0.0, 0.0), 1.0, [2, 1, 1], vec![1, 2]).unwrap();
+        assert!(matches!(full.voxels, Voxels::Dense(_)));
+    }
+
+    #[test]
+    fn deserialize_sparse_grid() {
+        let yaml = "
+corner: { x: 0.0, y: 0.0, z: 0.0 }
+voxels:
+  Sparse:
+    size: [2, 2, 2]
+    voxels:
+    - [[0, 0, 0], 3]
+";
+        let grid: VoxelGrid = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(grid.parts.voxel_size, 1.0);
+        assert_eq!(grid.at([0, 0, 0]), 3);
+        assert_eq!(grid.at([1, 0, 0]), 0);
+
+        let out_of_bounds = yaml.replace("[[0, 0, 0], 3]", "[[2, 0, 0], 3]");
+        assert!(serde_yaml::from_str::<VoxelGrid>(&out_of_bounds).is_err());
+    }
+
+    #[test]
+    fn reject_bad_sizes() {
+        let corner = Vec3::new(0.0, 0.0, 0.0);
+        assert!(VoxelGrid::dense(corner.clone(), 1.0, [0, 3, 3], vec![]).is_err());
+        assert!(VoxelGrid::sparse(corner.clone(), 1.0, [3, 0, 3], vec![]).is_err());
+
+        let huge = [usize::MAX, 2, 1];
+        assert!(VoxelGrid::sparse(corner, 1.0, huge, vec![]).is_err());
+    }
+}
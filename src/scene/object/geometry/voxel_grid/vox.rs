@@ -0,0 +1,190 @@
+use std::fs;
+use std::path::Path;
+
+use crate::math::color::Color;
+
+// The first model in a MagicaVoxel .vox file, along with its palette.
+//
+// MagicaVoxel's z axis points up, so models are turned to have y point up instead
+// (vox (x, y, z) becomes (x, z, size.y - 1 - y), to stay right handed).
+pub struct VoxModel {
+    pub size: [usize; 3],
+    pub voxels: Vec<([usize; 3], u8)>,
+
+    // the color of each palette index from 1 to 255, starting with 1
+    pub palette: Vec<Color>,
+}
+
+pub fn load(path: &Path) -> Result<VoxModel, String> {
+    let bytes =
+        fs::read(path).map_err(|err| format!("could not read {}: {err}", path.display()))?;
+    parse(&bytes).map_err(|err| format!("could not load {}: {err}", path.display()))
+}
+
+// The format is a header followed by a MAIN chunk, whose children are the chunks with the data.
+// Each chunk has a four letter id, the sizes of its contents and of its children, and then those.
+pub fn parse(bytes: &[u8]) -> Result<VoxModel, String> {
+    if bytes.len() < 8 || &bytes[0..4] != b"VOX " {
+        return Err("not a .vox file".to_string());
+    }
+
+    let mut reader = Reader { bytes, position: 8 };
+    let (id, content_size, _) = reader.chunk_header()?;
+    if id != *b"MAIN" {
+        return Err("missing MAIN chunk".to_string());
+    }
+    reader.skip(content_size)?;
+
+    let mut size = None;
+    let mut voxels = None;
+    let mut palette = None;
+
+    while reader.position < bytes.len() {
+        let (id, content_size, children_size) = reader.chunk_header()?;
+        let content_end = reader.position + content_size;
+
+        match &id {
+            // later models (and the scene graph that places them) are left out
+            b"SIZE" if size.is_none() => {
+                let (x, y, z) = (reader.u32()?, reader.u32()?, reader.u32()?);
+                size = Some([x as usize, z as usize, y as usize]);
+            }
+            b"XYZI" if voxels.is_none() => {
+                // (the file's y axis is the grid's z axis)
+                let [_, _, size_y] = size.ok_or("XYZI chunk before SIZE chunk")?;
+                let count = reader.u32()? as usize;
+                let mut filled = Vec::with_capacity(count);
+                for _ in 0..count {
+                    let [x, y, z, index] = reader.array::<4>()?;
+                    let (x, y, z) = (x as usize, y as usize, z as usize);
+                    if y >= size_y {
+                        return Err(format!("voxel ({x}, {y}, {z}) is outside of the model"));
+                    }
+                    filled.push(([x, z, size_y - 1 - y], index));
+                }
+                voxels = Some(filled);
+            }
+            b"RGBA" => {
+                // the last entry is unused, since index 0 is empty
+                let mut colors = Vec::with_capacity(255);
+                for _ in 0..255 {
+                    let [r, g, b, _] = reader.array::<4>()?;
+                    colors.push(Color::from_rgb_u8(r, g, b));
+                }
+                palette = Some(colors);
+            }
+            _ => {}
+        }
+
+        reader.position = content_end;
+        reader.skip(children_size)?;
+    }
+
+    Ok(VoxModel {
+        size: size.ok_or("missing SIZE chunk")?,
+        voxels: voxels.ok_or("missing XYZI chunk")?,
+        // files without a palette use MagicaVoxel's default one, which isn't worth including here
+        palette: palette.unwrap_or_else(|| vec![Color::from_rgb_u8(200, 200, 200); 255]),
+    })
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let slice = self
+            .bytes
+            .get(self.position..self.position + N)
+            .ok_or("unexpected end of file")?;
+        self.position += N;
+        Ok(slice.try_into().unwrap())
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.array::<4>()?))
+    }
+
+    fn skip(&mut self, count: usize) -> Result<(), String> {
+        if self.position + count > self.bytes.len() {
+            return Err("unexpected end of file".to_string());
+        }
+        self.position += count;
+        Ok(())
+    }
+
+    fn chunk_header(&mut self) -> Result<([u8; 4], usize, usize), String> {
+        let id = self.array::<4>()?;
+        let content_size = self.u32()? as usize;
+        let children_size = self.u32()? as usize;
+        Ok((id, content_size, children_size))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    fn chunk(id: &[u8; 4], content: &[u8], children: &[u8]) -> Vec<u8> {
+        let mut bytes = id.to_vec();
+        bytes.extend((content.len() as u32).to_le_bytes());
+        bytes.extend((children.len() as u32).to_le_bytes());
+        bytes.extend(content);
+        bytes.extend(children);
+        bytes
+    }
+
+    // a 2x3x4 model with two voxels, and (optionally) a palette where 1 is green and 3 is red
+    pub(crate) fn vox_file(with_palette: bool) -> Vec<u8> {
+        let size: Vec<u8> = [2u32, 3, 4].iter().flat_map(|n| n.to_le_bytes()).collect();
+        let mut xyzi = 2u32.to_le_bytes().to_vec();
+        xyzi.extend([0, 0, 0, 1]);
+        xyzi.extend([1, 2, 3, 9]);
+
+        let mut children = chunk(b"SIZE", &size, &[]);
+        children.extend(chunk(b"XYZI", &xyzi, &[]));
+        if with_palette {
+            let mut rgba = vec![0; 256 * 4];
+            rgba[0..4].copy_from_slice(&[0, 255, 0, 255]);
+            rgba[8..12].copy_from_slice(&[255, 0, 0, 255]);
+            children.extend(chunk(b"RGBA", &rgba, &[]));
+        }
+        // something newer versions write, which should be skipped
+        children.extend(chunk(b"nTRN", &[1, 2, 3], &[]));
+
+        let mut bytes = b"VOX ".to_vec();
+        bytes.extend(150u32.to_le_bytes());
+        bytes.extend(chunk(b"MAIN", &[], &children));
+        bytes
+    }
+
+    #[test]
+    fn parse_model() {
+        let model = parse(&vox_file(true)).unwrap();
+
+        // z is up in the file
+        assert_eq!(model.size, [2, 4, 3]);
+        assert_eq!(model.voxels, vec![([0, 0, 2], 1), ([1, 3, 0], 9)]);
+
+        assert_eq!(model.palette.len(), 255);
+        assert_eq!(model.palette[0], Color::from_rgb_u8(0, 255, 0));
+        assert_eq!(model.palette[2], Color::from_rgb_u8(255, 0, 0));
+    }
+
+    #[test]
+    fn parse_without_palette() {
+        let model = parse(&vox_file(false)).unwrap();
+        assert_eq!(model.palette[0], Color::from_rgb_u8(200, 200, 200));
+    }
+
+    #[test]
+    fn reject_bad_files() {
+        assert!(parse(b"PNG whatever").is_err());
+
+        let mut truncated = vox_file(true);
+        truncated.truncate(60);
+        assert!(parse(&truncated).is_err());
+    }
+}
@@ -1,13 +1,30 @@
 use super::geometry::Intersection;
 use crate::math::{color::Color, ray::Ray};
 
+pub mod coated;
+pub mod conductor;
+pub mod fresnel;
 pub mod lambertian;
+pub mod medium;
 pub mod metal;
+pub mod microfacet;
+pub mod mix;
+pub mod normal_map;
+pub mod orbit_trap;
+pub mod palette;
+pub mod principled;
+pub mod rough_dielectric;
 pub mod translucent;
 
 #[typetag::serde]
-pub trait ScatterRay {
+pub trait ScatterRay: Send + Sync {
     // QUESTION: Should this trait know about Intersection? or should it take intersection info as input directly?
-    fn scatter_ray(&self, incoming_ray: &Ray, intersection: &Intersection)
-        -> Option<(Ray, &Color)>;
+    fn scatter_ray(&self, incoming_ray: &Ray, intersection: &Intersection) -> Option<(Ray, Color)>;
+
+    // For materials that light can go into: the absorption coefficient of the inside (see
+    // `medium`). The renderer keeps track of which objects a ray is inside of, and absorbs light
+    // along the way from where it went in to where it comes out.
+    fn absorption(&self, _intersection: &Intersection) -> Option<Color> {
+        None
+    }
 }
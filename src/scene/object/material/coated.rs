@@ -0,0 +1,164 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    math::{color::Color, ray::Ray, sampler, vec3::Vec3},
+    scene::object::{geometry::Intersection, texture::Textured, Material},
+};
+
+use super::{
+    fresnel,
+    microfacet::{self, Frame, Ggx},
+    ScatterRay,
+};
+
+// A clear layer (like varnish, car paint's clear coat or a glaze) over any other material.
+// The coat reflects light off of its top by its Fresnel reflectance, smoothly or roughly, and
+// the rest goes through it to the base. What the base sends back has to get out through the coat
+// again, which reflects some of it back in (where it's lost), so coated things get darker and
+// more saturated, like wet or varnished ones do. `tint` colors the light that goes through.
+#[derive(Serialize, Deserialize)]
+pub struct Coated {
+    base: Material,
+    #[serde(default = "default_refractive_index")]
+    refractive_index: Textured<f32>,
+    #[serde(default = "default_roughness")]
+    roughness: Textured<f32>,
+    #[serde(default = "default_tint")]
+    tint: Textured<Color>,
+}
+
+fn default_refractive_index() -> Textured<f32> {
+    Textured::Constant(1.5)
+}
+
+fn default_roughness() -> Textured<f32> {
+    Textured::Constant(0.0)
+}
+
+fn default_tint() -> Textured<Color> {
+    Textured::Constant(Color::from_rgb_f32(1.0, 1.0, 1.0))
+}
+
+impl Coated {
+    pub fn new(base: Material, roughness: impl Into<Textured<f32>>) -> Coated {
+        Coated {
+            base,
+            refractive_index: default_refractive_index(),
+            roughness: roughness.into(),
+            tint: default_tint(),
+        }
+    }
+}
+
+#[typetag::serde]
+impl ScatterRay for Coated {
+    fn scatter_ray(&self, incoming_ray: &Ray, intersection: &Intersection) -> Option<(Ray, Color)> {
+        let eta = self.refractive_index.at(intersection);
+
+        // work on the side the ray came from
+        let (wo, frame, true_normal) = Frame::facing(incoming_ray, intersection);
+        let wo_local = frame.to_local(&wo);
+
+        // Reflecting off of the coat is picked by its reflectance, which cancels out of the
+        // weight (as for `RoughDielectric`).
+        let coat = Ggx::new(self.roughness.at(intersection), 0.0);
+        let mut rng = sampler::rng();
+        let h = coat.sample_visible_normal(&wo_local, rng.gen(), rng.gen());
+        if rng.gen::<f32>() < fresnel::dielectric(Vec3::dot(&wo_local, &h), eta) {
+            let wi = microfacet::reflect(&wo_local, &h);
+            let dir = frame.to_world(&wi);
+            if wi.z <= 0.0 || Vec3::dot(&dir, &true_normal) <= 0.0 {
+                return None;
+            }
+            let weight = coat.g2(&wo_local, &wi) / coat.g1(&wo_local);
+            return Some((
+                Ray::new(intersection.point.clone(), dir),
+                Color::from_rgb_f32(weight, weight, weight),
+            ));
+        }
+
+        // Otherwise the base scatters the light. (The coat is thin and flat underneath, so
+        // bending on the way in and back out cancels out.) Then it has to get back out.
+        let (ray, color) = self.base.scatter_ray(incoming_ray, intersection)?;
+        let cos_out = Vec3::dot(&ray.dir.normalize(), &frame.z).abs();
+        let out = 1.0 - fresnel::dielectric(cos_out, eta);
+        let weight = &(out * &color) * &self.tint.at(intersection);
+        Some((ray, weight))
+    }
+
+    fn absorption(&self, intersection: &Intersection) -> Option<Color> {
+        self.base.absorption(intersection)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::scene::object::material::lambertian::Lambertian;
+
+    fn floor_hit() -> Intersection {
+        Intersection::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            true,
+        )
+    }
+
+    fn diffuse(gray: f32) -> Material {
+        Arc::new(Lambertian::new(Color::from_rgb_f32(gray, gray, gray)))
+    }
+
+    #[test]
+    fn coat_reflects_by_fresnel() {
+        // over black, only the coat reflects anything: 4% head on
+        let coated = Coated::new(diffuse(0.0), 0.0);
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let reflected = (0..4000)
+            .filter_map(|_| coated.scatter_ray(&ray, &floor_hit()))
+            .filter(|(scattered, color)| color.r() > 0.0 && scattered.dir.normalize().y > 0.999)
+            .count();
+        assert!((100..=230).contains(&reflected), "{reflected} reflected");
+
+        // and the same from the back of the surface (eg. the underside of a quad)
+        let ray = Ray::new(Vec3::new(0.0, -1.0, 0.0), Vec3::new(0.0, 1.0, 0.0));
+        let back_hit = Intersection::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            false,
+        );
+        let reflected = (0..4000)
+            .filter_map(|_| coated.scatter_ray(&ray, &back_hit))
+            .filter(|(scattered, color)| color.r() > 0.0 && scattered.dir.normalize().y < -0.999)
+            .count();
+        assert!((100..=230).contains(&reflected), "{reflected} reflected");
+    }
+
+    #[test]
+    fn base_is_darkened_by_getting_out() {
+        let coated = Coated::new(diffuse(1.0), 0.2);
+        let ray = Ray::new(Vec3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+        // (the rough coat can occasionally reflect into the floor, which gets dropped)
+        for (scattered, color) in (0..200).filter_map(|_| coated.scatter_ray(&ray, &floor_hit())) {
+            assert!(scattered.dir.y > 0.0);
+            assert!(color.r() <= 1.0);
+        }
+    }
+
+    #[test]
+    fn deserialize() {
+        let yaml = r#"
+base:
+  Lambertian:
+    albedo: { x: 0.6, y: 0.3, z: 0.1 }
+roughness: 0.1
+tint: { x: 1.0, y: 0.9, z: 0.7 }
+"#;
+        let coated: Coated = serde_yaml::from_str(yaml).unwrap();
+        assert!(matches!(coated.refractive_index, Textured::Constant(eta) if eta == 1.5));
+    }
+}
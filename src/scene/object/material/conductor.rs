@@ -0,0 +1,286 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    math::{color::Color, ray::Ray, sampler, vec3::Vec3},
+    scene::object::{geometry::Intersection, texture::Textured},
+};
+
+use super::{
+    fresnel,
+    microfacet::{self, Frame, Ggx},
+    ScatterRay,
+};
+
+// A physically based metal: a surface of microscopic mirror facets (see `microfacet`), which
+// reflect light by the metal's complex index of refraction. Unlike `Metal`'s fuzz, rough
+// reflections here keep their energy up to grazing angles, and the color comes from the metal
+// itself (eg. gold turning whiter towards its edges).
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "ConductorParts")]
+pub struct Conductor {
+    #[serde(with = "serde_yaml::with::singleton_map")]
+    ior: ComplexIor,
+    roughness: Textured<f32>,
+    anisotropy: Textured<f32>,
+}
+
+// what a conductor looks like in a scene file, before its index of refraction is checked
+#[derive(Deserialize)]
+struct ConductorParts {
+    #[serde(with = "serde_yaml::with::singleton_map")]
+    ior: ComplexIor,
+    #[serde(default = "default_roughness")]
+    roughness: Textured<f32>,
+    #[serde(default = "default_anisotropy")]
+    anisotropy: Textured<f32>,
+}
+
+// The index of refraction `eta` + i `k`, which is either one of the presets, given for the red,
+// green and blue channels, or measured at a list of wavelengths (in nanometers, in any order).
+// Measurements are looked up at a wavelength for each channel, interpolating between them.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ComplexIor {
+    Gold,
+    Silver,
+    Copper,
+    Aluminium,
+    Iron,
+    Rgb { eta: Color, k: Color },
+    Spectrum(Vec<SpectralSample>),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SpectralSample {
+    pub wavelength: f32,
+    pub n: f32,
+    pub k: f32,
+}
+
+// where the channels are sampled from spectra
+const CHANNEL_WAVELENGTHS: [f32; 3] = [650.0, 550.0, 450.0];
+
+fn default_roughness() -> Textured<f32> {
+    Textured::Constant(0.0)
+}
+
+fn default_anisotropy() -> Textured<f32> {
+    Textured::Constant(0.0)
+}
+
+impl ComplexIor {
+    // (eta, k) for each channel
+    pub fn eta_k(&self) -> (Color, Color) {
+        // Measurements (from refractiveindex.info) at the channel wavelengths: for gold, silver
+        // and copper from Johnson and Christy (1972), for aluminium and iron from Rakić (1995)
+        // and Johnson and Christy (1974)
+        let rgb = |eta: [f32; 3], k: [f32; 3]| {
+            (
+                Color::from_rgb_f32(eta[0], eta[1], eta[2]),
+                Color::from_rgb_f32(k[0], k[1], k[2]),
+            )
+        };
+        match self {
+            ComplexIor::Gold => rgb([0.166, 0.433, 1.404], [3.15, 2.455, 1.883]),
+            ComplexIor::Silver => rgb([0.140, 0.120, 0.130], [4.152, 3.338, 2.588]),
+            ComplexIor::Copper => rgb([0.214, 1.019, 1.241], [3.670, 2.577, 2.400]),
+            ComplexIor::Aluminium => rgb([1.490, 0.958, 0.620], [7.821, 6.690, 5.470]),
+            ComplexIor::Iron => rgb([2.870, 2.880, 2.550], [3.350, 3.050, 2.900]),
+            ComplexIor::Rgb { eta, k } => (eta.clone(), k.clone()),
+            ComplexIor::Spectrum(samples) => {
+                let [r, g, b] =
+                    CHANNEL_WAVELENGTHS.map(|wavelength| sample_spectrum(samples, wavelength));
+                rgb([r.0, g.0, b.0], [r.1, g.1, b.1])
+            }
+        }
+    }
+}
+
+// (n, k) at a wavelength, clamped to the ends of the measurements.
+// no measurements at all is a mirror (as near to perfect as stays finite in `fresnel::conductor`)
+fn sample_spectrum(samples: &[SpectralSample], wavelength: f32) -> (f32, f32) {
+    let below = samples
+        .iter()
+        .filter(|s| s.wavelength <= wavelength)
+        .max_by(|a, b| a.wavelength.total_cmp(&b.wavelength));
+    let above = samples
+        .iter()
+        .filter(|s| s.wavelength >= wavelength)
+        .min_by(|a, b| a.wavelength.total_cmp(&b.wavelength));
+
+    match (below, above) {
+        (Some(below), Some(above)) if above.wavelength > below.wavelength => {
+            let t = (wavelength - below.wavelength) / (above.wavelength - below.wavelength);
+            (
+                below.n + t * (above.n - below.n),
+                below.k + t * (above.k - below.k),
+            )
+        }
+        (Some(sample), _) | (_, Some(sample)) => (sample.n, sample.k),
+        (None, None) => (1.0, 1e4),
+    }
+}
+
+impl Conductor {
+    pub fn new(ior: ComplexIor, roughness: impl Into<Textured<f32>>) -> Conductor {
+        Conductor {
+            ior,
+            roughness: roughness.into(),
+            anisotropy: default_anisotropy(),
+        }
+    }
+}
+
+impl TryFrom<ConductorParts> for Conductor {
+    type Error = String;
+
+    fn try_from(parts: ConductorParts) -> Result<Self, Self::Error> {
+        if parts.ior == ComplexIor::Spectrum(vec![]) {
+            return Err("a spectrum needs at least one measurement".to_string());
+        }
+        Ok(Conductor {
+            ior: parts.ior,
+            roughness: parts.roughness,
+            anisotropy: parts.anisotropy,
+        })
+    }
+}
+
+#[typetag::serde]
+impl ScatterRay for Conductor {
+    fn scatter_ray(&self, incoming_ray: &Ray, intersection: &Intersection) -> Option<(Ray, Color)> {
+        // metals are opaque, so reflect off of whichever side the ray came from
+        let (wo, frame, true_normal) = Frame::facing(incoming_ray, intersection);
+
+        let ggx = Ggx::new(
+            self.roughness.at(intersection),
+            self.anisotropy.at(intersection),
+        );
+        let wo = frame.to_local(&wo);
+        let mut rng = sampler::rng();
+        let h = ggx.sample_visible_normal(&wo, rng.gen(), rng.gen());
+        let wi = microfacet::reflect(&wo, &h);
+
+        // Light that a facet sends back into the surface (only at high roughness) is lost,
+        // since other facets would have to reflect it again.
+        let dir = frame.to_world(&wi);
+        if wi.z <= 0.0 || Vec3::dot(&dir, &true_normal) <= 0.0 {
+            return None;
+        }
+
+        // Sampling visible facets means that the facets' density and the probability of picking
+        // them cancel out, leaving the reflectance and the part of the masking that's left over.
+        let (eta, k) = self.ior.eta_k();
+        let reflectance = fresnel::conductor(Vec3::dot(&wo, &h), &eta, &k);
+        let weight = ggx.g2(&wo, &wi) / ggx.g1(&wo);
+
+        Some((
+            Ray::new(intersection.point.clone(), dir),
+            weight * &reflectance,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn floor_hit() -> Intersection {
+        Intersection::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            true,
+        )
+    }
+
+    #[test]
+    fn smooth_metal_is_a_tinted_mirror() {
+        let gold = Conductor::new(ComplexIor::Gold, 0.0);
+        let ray = Ray::new(Vec3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+        let (scattered, color) = gold.scatter_ray(&ray, &floor_hit()).unwrap();
+
+        assert!((&scattered.dir - &Vec3::new(1.0, 1.0, 0.0).normalize()).length() < 1e-2);
+
+        // gold reflects red more than blue
+        assert!(color.r() > 0.9 && color.b() < 0.5, "{color:?}");
+    }
+
+    #[test]
+    fn rough_reflections_stay_above_the_surface() {
+        let iron = Conductor::new(ComplexIor::Iron, 0.9);
+        let grazing = Ray::new(Vec3::new(-1.0, 0.05, 0.0), Vec3::new(1.0, -0.05, 0.0));
+        for _ in 0..200 {
+            if let Some((scattered, color)) = iron.scatter_ray(&grazing, &floor_hit()) {
+                assert!(scattered.dir.y > 0.0);
+                assert!(color.r() <= 1.0 && color.r() >= 0.0);
+            }
+        }
+    }
+
+    #[test]
+    fn presets_reflect_like_their_metals() {
+        let head_on = |ior: ComplexIor| {
+            let (eta, k) = ior.eta_k();
+            fresnel::conductor(1.0, &eta, &k)
+        };
+
+        let silver = head_on(ComplexIor::Silver);
+        assert!(silver.r() > 0.9 && silver.b() > 0.85);
+
+        let copper = head_on(ComplexIor::Copper);
+        assert!(copper.r() > copper.g() && copper.g() > 0.4);
+
+        let iron = head_on(ComplexIor::Iron);
+        assert!(iron.r() > 0.5 && iron.r() < 0.6);
+    }
+
+    #[test]
+    fn spectra_are_sampled_per_channel() {
+        let samples = vec![
+            SpectralSample {
+                wavelength: 700.0,
+                n: 1.0,
+                k: 4.0,
+            },
+            SpectralSample {
+                wavelength: 500.0,
+                n: 2.0,
+                k: 2.0,
+            },
+        ];
+        let (eta, k) = ComplexIor::Spectrum(samples).eta_k();
+
+        // red is interpolated, green too, and blue is past the end
+        assert!((eta.r() - 1.25).abs() < 1e-5 && (k.r() - 3.5).abs() < 1e-5);
+        assert!((eta.g() - 1.75).abs() < 1e-5);
+        assert_eq!((eta.b(), k.b()), (2.0, 2.0));
+    }
+
+    #[test]
+    fn deserialize() {
+        let yaml = "ior: Gold\nroughness: 0.3";
+        let conductor: Conductor = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(conductor.ior, ComplexIor::Gold);
+
+        let yaml = r#"
+ior:
+  Spectrum:
+  - { wavelength: 450.0, n: 1.0, k: 2.0 }
+  - { wavelength: 650.0, n: 0.5, k: 3.0 }
+anisotropy: 0.5
+"#;
+        let conductor: Conductor = serde_yaml::from_str(yaml).unwrap();
+        assert!(matches!(conductor.ior, ComplexIor::Spectrum(ref samples) if samples.len() == 2));
+    }
+
+    #[test]
+    fn empty_spectrum() {
+        assert!(serde_yaml::from_str::<Conductor>("ior: { Spectrum: [] }").is_err());
+
+        // and when made in code, it's a mirror rather than NaN
+        let (eta, k) = ComplexIor::Spectrum(vec![]).eta_k();
+        let reflectance = fresnel::conductor(0.5, &eta, &k);
+        assert!(reflectance.r().is_finite() && reflectance.r() > 0.99);
+    }
+}
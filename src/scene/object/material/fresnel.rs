@@ -0,0 +1,99 @@
+use crate::math::color::Color;
+
+// How much light reflects off of a smooth surface, rather than going into it, depending on the
+// cosine of the angle between the incoming light and the normal. `conductor` and `dielectric` are
+// the exact equations for unpolarized light, while `schlick` is a cheap approximation.
+
+// For metals, whose index of refraction is complex: `eta` + i `k` for each channel (relative
+// to the medium outside, eg. air). Metals absorb everything that isn't reflected.
+pub fn conductor(cos_theta: f32, eta: &Color, k: &Color) -> Color {
+    let channel = |eta: f32, k: f32| {
+        let cos2 = cos_theta.clamp(0.0, 1.0).powi(2);
+        let sin2 = 1.0 - cos2;
+        let (eta2, k2) = (eta * eta, k * k);
+
+        let t0 = eta2 - k2 - sin2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta2 * k2).sqrt();
+        let t1 = a2_plus_b2 + cos2;
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * cos_theta.clamp(0.0, 1.0) * a;
+        let rs = (t1 - t2) / (t1 + t2);
+
+        let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+        let t4 = t2 * sin2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+
+        0.5 * (rp + rs)
+    };
+    Color::from_rgb_f32(
+        channel(eta.r(), k.r()),
+        channel(eta.g(), k.g()),
+        channel(eta.b(), k.b()),
+    )
+}
+
+// For glass and the like: `eta` is the index of refraction on the far side of the surface,
+// relative to this side's (so it's below 1 going from glass into air). Past the critical angle,
+// everything is reflected.
+pub fn dielectric(cos_theta: f32, eta: f32) -> f32 {
+    let cos_i = cos_theta.clamp(0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+
+    let rs = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let rp = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    0.5 * (rs * rs + rp * rp)
+}
+
+// Schlick's approximation, from the reflectance head on: this is what artist-facing parameters
+// like `Principled`'s specular are defined by, since any color can be used as a reflectance
+pub fn schlick(cos_theta: f32, head_on: &Color) -> Color {
+    let grazing = (1.0 - cos_theta.clamp(0.0, 1.0)).powi(5);
+    let channel = |c: f32| c + (1.0 - c) * grazing;
+    Color::from_rgb_f32(
+        channel(head_on.r()),
+        channel(head_on.g()),
+        channel(head_on.b()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conductor_reflectance() {
+        let eta = Color::from_rgb_f32(0.2, 1.0, 2.0);
+        let k = Color::from_rgb_f32(3.0, 2.0, 0.0);
+        let head_on = conductor(1.0, &eta, &k);
+
+        // at normal incidence it's ((n - 1)^2 + k^2) / ((n + 1)^2 + k^2)
+        let expected = |n: f32, k: f32| ((n - 1.0).powi(2) + k * k) / ((n + 1.0).powi(2) + k * k);
+        assert!((head_on.r() - expected(0.2, 3.0)).abs() < 1e-5);
+        assert!((head_on.g() - expected(1.0, 2.0)).abs() < 1e-5);
+        assert!((head_on.b() - expected(2.0, 0.0)).abs() < 1e-5);
+
+        // and everything reflects at grazing angles
+        let grazing = conductor(0.0, &eta, &k);
+        assert!((grazing.r() - 1.0).abs() < 1e-5);
+        assert!((grazing.b() - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn dielectric_reflectance() {
+        // 4% off of glass head on, and everything at grazing angles
+        assert!((dielectric(1.0, 1.5) - 0.04).abs() < 1e-5);
+        assert!((dielectric(0.0, 1.5) - 1.0).abs() < 1e-5);
+
+        // going out of the glass, everything past about 42 degrees reflects
+        assert_eq!(dielectric(0.7, 1.0 / 1.5), 1.0);
+        assert!(dielectric(0.8, 1.0 / 1.5) < 1.0);
+
+        // and the reflectance is the same both ways through the surface
+        let cos_t = (1.0f32 - (1.0 - 0.9f32.powi(2)) / 1.5f32.powi(2)).sqrt();
+        assert!((dielectric(0.9, 1.5) - dielectric(cos_t, 1.0 / 1.5)).abs() < 1e-5);
+    }
+}
@@ -2,19 +2,21 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     math::{color::Color, ray::Ray, vec3::Vec3},
-    scene::object::geometry::Intersection,
+    scene::object::{geometry::Intersection, texture::Textured},
 };
 
 use super::ScatterRay;
 
 #[derive(Serialize, Deserialize)]
 pub struct Lambertian {
-    albedo: Color,
+    albedo: Textured<Color>,
 }
 
 impl Lambertian {
-    pub fn new(albedo: Color) -> Lambertian {
-        Lambertian { albedo }
+    pub fn new(albedo: impl Into<Textured<Color>>) -> Lambertian {
+        Lambertian {
+            albedo: albedo.into(),
+        }
     }
 }
 
@@ -24,17 +26,22 @@ impl ScatterRay for Lambertian {
         &self,
         _incoming_ray: &Ray,
         intersection: &Intersection,
-    ) -> Option<(Ray, &Color)> {
-        let random_unit = Vec3::random_unit_vector();
+    ) -> Option<(Ray, Color)> {
+        Some((diffuse_ray(intersection), self.albedo.at(intersection)))
+    }
+}
 
-        let mut scatter_dir = &intersection.normal + &random_unit;
+// a ray scattered in a random direction, weighted towards the normal (shared with other
+// materials that scatter diffusely)
+pub(super) fn diffuse_ray(intersection: &Intersection) -> Ray {
+    let random_unit = Vec3::random_unit_vector();
 
-        // reject scattered vectors that are too close to zero
-        if scatter_dir.is_small() {
-            scatter_dir = intersection.normal.clone();
-        }
+    let mut scatter_dir = &intersection.shading_normal + &random_unit;
 
-        let new_ray = Ray::new(intersection.point.clone(), scatter_dir);
-        Some((new_ray, &self.albedo))
+    // reject scattered vectors that are too close to zero
+    if scatter_dir.is_small() {
+        scatter_dir = intersection.shading_normal.clone();
     }
+
+    Ray::new(intersection.point.clone(), scatter_dir)
 }
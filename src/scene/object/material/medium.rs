@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+
+use crate::math::color::Color;
+
+// How much the inside of something (like colored glass) absorbs light going through it.
+// In scene files it's either the absorption coefficient itself (the fraction absorbed per unit
+// of distance, for each channel), or the color that white light turns after some distance,
+// which is easier to pick: eg.
+//    absorption:
+//      ColorAtDistance: { color: { x: 0.9, y: 0.5, z: 0.4 }, distance: 2.0 }
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Absorption {
+    Coefficient(Color),
+    ColorAtDistance { color: Color, distance: f32 },
+}
+
+impl Absorption {
+    pub fn coefficient(&self) -> Color {
+        match self {
+            Absorption::Coefficient(coefficient) => coefficient.clone(),
+            Absorption::ColorAtDistance { color, distance } => {
+                // (black would need an infinite coefficient, so it's just very dark)
+                let channel = |c: f32| -c.clamp(1e-6, 1.0).ln() / distance;
+                Color::from_rgb_f32(channel(color.r()), channel(color.g()), channel(color.b()))
+            }
+        }
+    }
+}
+
+// how much light is left after going `distance` through something (Beer-Lambert's law)
+pub fn transmittance(coefficient: &Color, distance: f32) -> Color {
+    let channel = |c: f32| (-c * distance).exp();
+    Color::from_rgb_f32(
+        channel(coefficient.r()),
+        channel(coefficient.g()),
+        channel(coefficient.b()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn color_at_distance() {
+        let absorption = Absorption::ColorAtDistance {
+            color: Color::from_rgb_f32(1.0, 0.5, 0.25),
+            distance: 2.0,
+        };
+        let coefficient = absorption.coefficient();
+        assert_eq!(coefficient.r(), 0.0);
+
+        let after = transmittance(&coefficient, 2.0);
+        assert!((after.g() - 0.5).abs() < 1e-6);
+        assert!((after.b() - 0.25).abs() < 1e-6);
+
+        // and twice as far absorbs twice as much
+        let further = transmittance(&coefficient, 4.0);
+        assert!((further.g() - 0.25).abs() < 1e-6);
+    }
+}
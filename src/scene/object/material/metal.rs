@@ -2,31 +2,30 @@ use serde::{Deserialize, Serialize};
 
 use crate::{
     math::{color::Color, ray::Ray, vec3::Vec3},
-    scene::object::geometry::Intersection,
+    scene::object::{geometry::Intersection, texture::Textured},
 };
 
 use super::ScatterRay;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct Metal {
-    albedo: Color,
-    fuzz: f32,
+    albedo: Textured<Color>,
+    fuzz: Textured<f32>,
 }
 
 impl Metal {
-    pub fn new(albedo: Color, fuzz: f32) -> Metal {
-        Metal { albedo, fuzz }
+    pub fn new(albedo: impl Into<Textured<Color>>, fuzz: impl Into<Textured<f32>>) -> Metal {
+        Metal {
+            albedo: albedo.into(),
+            fuzz: fuzz.into(),
+        }
     }
 }
 
 #[typetag::serde]
 impl ScatterRay for Metal {
-    fn scatter_ray(
-        &self,
-        incoming_ray: &Ray,
-        intersection: &Intersection,
-    ) -> Option<(Ray, &Color)> {
-        let reflect_direction = Vec3::reflect(&incoming_ray.dir, &intersection.normal);
+    fn scatter_ray(&self, incoming_ray: &Ray, intersection: &Intersection) -> Option<(Ray, Color)> {
+        let reflect_direction = Vec3::reflect(&incoming_ray.dir, &intersection.shading_normal);
 
         // generate a random vector with length < 1 to use to displace the reflection vector
         let random_subunit = Vec3::random_subunit_vector();
@@ -34,16 +33,17 @@ impl ScatterRay for Metal {
         // FIXME: we should probably normalize ray direction vectors? because if not, it means
         //   we displacing shorted reflected direction vectors by a greater angle (on average)
         //   than long ones. Maybe that isn't a problem though
-        let displaced_reflection = &(self.fuzz * &random_subunit) + &reflect_direction;
+        let displaced_reflection =
+            &(self.fuzz.at(intersection) * &random_subunit) + &reflect_direction;
 
         // absorb this ray if the scattered ray points opposite (negative dot product)
-        // the normal of the surface
+        // the true normal of the surface (rather than the shading normal), since it'd go into it
         if Vec3::dot(&displaced_reflection, &intersection.normal) < 0.0 {
             None
         } else {
             Some((
                 Ray::new(intersection.point.clone(), displaced_reflection),
-                &self.albedo,
+                self.albedo.at(intersection),
             ))
         }
     }
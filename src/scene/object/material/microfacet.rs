@@ -0,0 +1,256 @@
+use std::f32::consts::PI;
+
+use crate::{
+    math::{ray::Ray, vec3::Vec3},
+    scene::object::geometry::Intersection,
+};
+
+// Shared pieces for materials whose surfaces are made of tiny mirror-like facets, tilted at
+// random by an amount that depends on the roughness. This uses the GGX (Trowbridge-Reitz)
+// distribution of facet normals, with Smith's model of facets hiding each other.
+//
+// Everything here works in a local frame where the surface normal is +z (see `Frame`), with
+// directions pointing away from the surface.
+
+// A frame around a shading normal, with x along the surface's u direction (so that anisotropic
+// roughness lines up with the texture coordinates).
+pub struct Frame {
+    pub x: Vec3,
+    pub y: Vec3,
+    pub z: Vec3,
+}
+
+impl Frame {
+    pub fn new(unit_normal: &Vec3, dpdu: &Vec3) -> Frame {
+        let along_u = dpdu - &(Vec3::dot(dpdu, unit_normal) * unit_normal);
+        let x = if along_u.is_small() {
+            Vec3::tangents(unit_normal).0
+        } else {
+            along_u.normalize()
+        };
+        let y = Vec3::cross(unit_normal, &x);
+        Frame {
+            x,
+            y,
+            z: unit_normal.clone(),
+        }
+    }
+
+    // For working on the side of the surface that a ray came from: the direction back along the
+    // ray, the frame around the shading normal on that side, and the true normal on that side.
+    pub fn facing(incoming_ray: &Ray, intersection: &Intersection) -> (Vec3, Frame, Vec3) {
+        let wo = Vec3::negative(&incoming_ray.dir.normalize());
+        let facing = |normal: &Vec3| {
+            if Vec3::dot(&wo, normal) < 0.0 {
+                Vec3::negative(normal)
+            } else {
+                normal.clone()
+            }
+        };
+        let frame = Frame::new(&facing(&intersection.shading_normal), &intersection.dpdu);
+        let true_normal = facing(&intersection.normal);
+        (wo, frame, true_normal)
+    }
+
+    pub fn to_local(&self, v: &Vec3) -> Vec3 {
+        Vec3::new(
+            Vec3::dot(v, &self.x),
+            Vec3::dot(v, &self.y),
+            Vec3::dot(v, &self.z),
+        )
+    }
+
+    pub fn to_world(&self, v: &Vec3) -> Vec3 {
+        Vec3::lin_comb(vec![(v.x, &self.x), (v.y, &self.y), (v.z, &self.z)])
+    }
+}
+
+// the roughness of the distribution along x and y
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ggx {
+    alpha_x: f32,
+    alpha_y: f32,
+}
+
+// below this the distribution is so sharp that its math stops working in floats
+const MIN_ALPHA: f32 = 1e-4;
+
+impl Ggx {
+    // Roughness goes from 0 (a mirror) to 1, and is squared to look about linear. Anisotropy
+    // goes from 0 (the same in every direction) to 1 (much smoother along v than along u),
+    // following Disney's parameterization.
+    pub fn new(roughness: f32, anisotropy: f32) -> Ggx {
+        let alpha = roughness.clamp(0.0, 1.0).powi(2);
+        let aspect = (1.0 - 0.9 * anisotropy.clamp(0.0, 1.0)).sqrt();
+        Ggx {
+            alpha_x: (alpha / aspect).max(MIN_ALPHA),
+            alpha_y: (alpha * aspect).max(MIN_ALPHA),
+        }
+    }
+
+    // the density of facets facing in the direction `h` (per unit of solid angle, projected
+    // onto the surface)
+    pub fn d(&self, h: &Vec3) -> f32 {
+        if h.z <= 0.0 {
+            return 0.0;
+        }
+        let e = (h.x / self.alpha_x).powi(2) + (h.y / self.alpha_y).powi(2) + h.z.powi(2);
+        1.0 / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+
+    // Smith's auxiliary function, which the masking terms are built from
+    fn lambda(&self, w: &Vec3) -> f32 {
+        if w.z == 0.0 {
+            return f32::INFINITY;
+        }
+        let tan_squared =
+            ((self.alpha_x * w.x).powi(2) + (self.alpha_y * w.y).powi(2)) / w.z.powi(2);
+        0.5 * ((1.0 + tan_squared).sqrt() - 1.0)
+    }
+
+    // the fraction of facets that can be seen from `w`
+    pub fn g1(&self, w: &Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    // the fraction of facets that can be seen from both `wo` and `wi`, taking into account that
+    // facets which are visible from one are likely to be visible from the other
+    pub fn g2(&self, wo: &Vec3, wi: &Vec3) -> f32 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+
+    // Picks a facet normal from among those visible from `wo` (which needs to be above the
+    // surface), given two uniform random numbers. This is Heitz's method from "Sampling the GGX
+    // Distribution of Visible Normals" (2018): it stretches the view so that the roughness is 1,
+    // where the visible facets are easy to sample, and then stretches the result back.
+    pub fn sample_visible_normal(&self, wo: &Vec3, u1: f32, u2: f32) -> Vec3 {
+        let v = Vec3::new(self.alpha_x * wo.x, self.alpha_y * wo.y, wo.z).normalize();
+
+        // a frame around the stretched view direction
+        let length_squared = v.x * v.x + v.y * v.y;
+        let t1 = if length_squared > 0.0 {
+            (1.0 / length_squared.sqrt()) * &Vec3::new(-v.y, v.x, 0.0)
+        } else {
+            Vec3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = Vec3::cross(&v, &t1);
+
+        // a point on a disk, squashed where the hemisphere is seen side-on
+        let r = u1.sqrt();
+        let phi = 2.0 * PI * u2;
+        let p1 = r * phi.cos();
+        let s = 0.5 * (1.0 + v.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).max(0.0).sqrt() + s * r * phi.sin();
+        let p3 = (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt();
+
+        let n = Vec3::lin_comb(vec![(p1, &t1), (p2, &t2), (p3, &v)]);
+        Vec3::new(self.alpha_x * n.x, self.alpha_y * n.y, n.z.max(0.0)).normalize()
+    }
+}
+
+// the direction `w` reflects into off of a facet with normal `h`
+pub fn reflect(w: &Vec3, h: &Vec3) -> Vec3 {
+    &((2.0 * Vec3::dot(w, h)) * h) - w
+}
+
+// The direction `w` refracts into through a facet with normal `h` (on the same side as `w`),
+// where `eta` is the index of refraction on the far side relative to `w`'s side. There isn't one
+// past the critical angle.
+pub fn refract(w: &Vec3, h: &Vec3, eta: f32) -> Option<Vec3> {
+    let cos_i = Vec3::dot(w, h);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return None;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    Some(&((-1.0 / eta) * w) + &((cos_i / eta - cos_t) * h))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the points of a grid over the hemisphere, with the solid angle each one covers
+    fn hemisphere() -> impl Iterator<Item = (Vec3, f32)> {
+        let steps = 400;
+        let (d_theta, d_phi) = (0.5 * PI / steps as f32, 2.0 * PI / steps as f32);
+        (0..steps).flat_map(move |i| {
+            let theta = (i as f32 + 0.5) * d_theta;
+            (0..steps).map(move |j| {
+                let phi = (j as f32 + 0.5) * d_phi;
+                let w = Vec3::new(
+                    theta.sin() * phi.cos(),
+                    theta.sin() * phi.sin(),
+                    theta.cos(),
+                );
+                (w, theta.sin() * d_theta * d_phi)
+            })
+        })
+    }
+
+    #[test]
+    fn distribution_covers_the_surface_once() {
+        // the projected areas of all of the facets add up to the area of the surface
+        for ggx in [Ggx::new(0.3, 0.0), Ggx::new(0.7, 0.0), Ggx::new(0.5, 0.8)] {
+            let total: f32 = hemisphere().map(|(h, area)| ggx.d(&h) * h.z * area).sum();
+            assert!((total - 1.0).abs() < 0.02, "{ggx:?} covers {total}");
+        }
+    }
+
+    #[test]
+    fn masking() {
+        let ggx = Ggx::new(0.5, 0.0);
+        let straight_down = Vec3::new(0.0, 0.0, 1.0);
+        assert_eq!(ggx.g1(&straight_down), 1.0);
+
+        let grazing = Vec3::new(1.0, 0.0, 0.05).normalize();
+        assert!(ggx.g1(&grazing) < 0.5);
+        assert!(ggx.g2(&grazing, &straight_down) <= ggx.g1(&grazing));
+    }
+
+    #[test]
+    fn sampled_normals_face_the_viewer() {
+        let ggx = Ggx::new(0.8, 0.5);
+        let wo = Vec3::new(0.6, -0.3, 0.2).normalize();
+        for i in 0..20 {
+            for j in 0..20 {
+                let h = ggx.sample_visible_normal(&wo, i as f32 / 20.0, j as f32 / 20.0);
+                assert!((h.length() - 1.0).abs() < 1e-4);
+                assert!(h.z >= 0.0);
+                assert!(Vec3::dot(&wo, &h) >= -1e-4);
+            }
+        }
+
+        // and smooth surfaces just have the normal
+        let smooth = Ggx::new(0.0, 0.0);
+        let h = smooth.sample_visible_normal(&wo, 0.3, 0.7);
+        assert!(h.z > 0.999);
+    }
+
+    #[test]
+    fn refraction_bends_towards_the_normal() {
+        let h = Vec3::new(0.0, 0.0, 1.0);
+        let w = Vec3::new(0.5, 0.0, 0.75f32.sqrt());
+        let refracted = refract(&w, &h, 1.5).unwrap();
+
+        // Snell's law: sin(theta_t) = sin(theta_i) / eta, on the far side
+        assert!((refracted.length() - 1.0).abs() < 1e-5);
+        assert!((refracted.x + 0.5 / 1.5).abs() < 1e-5);
+        assert!(refracted.z < 0.0);
+
+        // and total internal reflection going the other way
+        let steep = Vec3::new(0.8, 0.0, 0.6);
+        assert_eq!(refract(&steep, &h, 1.0 / 1.5), None);
+    }
+
+    #[test]
+    fn frame_follows_u() {
+        let frame = Frame::new(&Vec3::new(0.0, 1.0, 0.0), &Vec3::new(2.0, 1.0, 0.0));
+        assert_eq!(frame.x, Vec3::new(1.0, 0.0, 0.0));
+        assert_eq!(frame.y, Vec3::new(0.0, 0.0, -1.0));
+
+        let v = Vec3::new(0.3, -0.2, 0.9);
+        let round_trip = frame.to_world(&frame.to_local(&v));
+        assert!((&round_trip - &v).length() < 1e-6);
+    }
+}
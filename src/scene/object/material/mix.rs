@@ -0,0 +1,156 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    math::{color::Color, ray::Ray, sampler, shaping::lerp},
+    scene::object::{geometry::Intersection, texture::Textured, Material},
+};
+
+use super::ScatterRay;
+
+// Blends two materials by a factor from 0 (all `first`) to 1 (all `second`), which can be a
+// texture to use as a mask (eg. rust patches on painted metal). Each ray scatters off of one of
+// them, picked at random by the factor, which on average is the same as blending their
+// reflections by it.
+#[derive(Serialize, Deserialize)]
+pub struct Mix {
+    first: Material,
+    second: Material,
+    factor: Textured<f32>,
+}
+
+impl Mix {
+    pub fn new(first: Material, second: Material, factor: impl Into<Textured<f32>>) -> Mix {
+        Mix {
+            first,
+            second,
+            factor: factor.into(),
+        }
+    }
+}
+
+#[typetag::serde]
+impl ScatterRay for Mix {
+    fn scatter_ray(&self, incoming_ray: &Ray, intersection: &Intersection) -> Option<(Ray, Color)> {
+        let factor = self.factor.at(intersection);
+        let material = if sampler::rng().gen::<f32>() < factor {
+            &self.second
+        } else {
+            &self.first
+        };
+        material.scatter_ray(incoming_ray, intersection)
+    }
+
+    // the insides get blended too, with nothing inside counting as no absorption
+    fn absorption(&self, intersection: &Intersection) -> Option<Color> {
+        let first = self.first.absorption(intersection);
+        let second = self.second.absorption(intersection);
+        if first.is_none() && second.is_none() {
+            return None;
+        }
+
+        let none = Color::from_rgb_f32(0.0, 0.0, 0.0);
+        let factor = self.factor.at(intersection).clamp(0.0, 1.0);
+        Some(lerp(
+            factor,
+            &first.unwrap_or(none.clone()),
+            &second.unwrap_or(none),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::math::vec3::Vec3;
+    use crate::scene::object::{
+        material::lambertian::Lambertian,
+        texture::gradient::{Gradient, GradientAxis},
+    };
+
+    fn red() -> Color {
+        Color::from_rgb_f32(1.0, 0.0, 0.0)
+    }
+
+    fn blue() -> Color {
+        Color::from_rgb_f32(0.0, 0.0, 1.0)
+    }
+
+    fn red_and_blue(factor: Textured<f32>) -> Mix {
+        Mix::new(
+            Arc::new(Lambertian::new(red())),
+            Arc::new(Lambertian::new(blue())),
+            factor,
+        )
+    }
+
+    fn hit_at_u(u: f32) -> Intersection {
+        Intersection {
+            uv: (u, 0.0),
+            ..Intersection::new(
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                1.0,
+                true,
+            )
+        }
+    }
+
+    // how many out of 1000 rays scatter off of `second`
+    fn second_count(mix: &Mix, hit: &Intersection) -> usize {
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        (0..1000)
+            .filter_map(|_| mix.scatter_ray(&ray, hit))
+            .filter(|(_, color)| *color == blue())
+            .count()
+    }
+
+    #[test]
+    fn factor_picks_between_materials() {
+        assert_eq!(second_count(&red_and_blue(0.0.into()), &hit_at_u(0.0)), 0);
+        assert_eq!(
+            second_count(&red_and_blue(1.0.into()), &hit_at_u(0.0)),
+            1000
+        );
+
+        let quarter = second_count(&red_and_blue(0.25.into()), &hit_at_u(0.0));
+        assert!((180..=320).contains(&quarter), "{quarter}");
+    }
+
+    #[test]
+    fn mask_textures() {
+        let mask = Gradient::new(
+            vec![
+                Color::from_rgb_f32(0.0, 0.0, 0.0),
+                Color::from_rgb_f32(1.0, 1.0, 1.0),
+            ],
+            GradientAxis::U,
+        );
+        let mix = red_and_blue(Textured::Texture(Box::new(mask)));
+        assert_eq!(second_count(&mix, &hit_at_u(0.0)), 0);
+        assert_eq!(second_count(&mix, &hit_at_u(1.0)), 1000);
+    }
+
+    #[test]
+    fn deserialize() {
+        let yaml = r#"
+first:
+  Lambertian:
+    albedo: { x: 1.0, y: 0.0, z: 0.0 }
+second:
+  Translucent:
+    albedo: { x: 1.0, y: 1.0, z: 1.0 }
+    refractive_index: 1.5
+    absorption:
+      Coefficient: { x: 2.0, y: 0.0, z: 0.0 }
+factor: 0.5
+"#;
+        let mix: Mix = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            mix.absorption(&hit_at_u(0.0)),
+            Some(Color::from_rgb_f32(1.0, 0.0, 0.0))
+        );
+    }
+}
@@ -0,0 +1,268 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    math::{color::Color, ray::Ray, vec3::Vec3},
+    scene::object::{
+        geometry::Intersection,
+        texture::{Texture, Textured},
+        Material,
+    },
+};
+
+use super::ScatterRay;
+
+// Tilts the shading normal of another material by a normal map: a texture whose colors are
+// directions in the surface's tangent space, with red along u, green along v and blue out of the
+// surface (mapped from -1..1 to 0..1, so flat is (0.5, 0.5, 1)). Image normal maps need
+// `color_space: Linear`, since their colors aren't meant to be seen.
+#[derive(Serialize, Deserialize)]
+pub struct NormalMap {
+    material: Material,
+    map: Box<dyn Texture>,
+}
+
+// Tilts the shading normal of another material as if the surface were pushed out along its
+// normal by a height texture (the average of its channels, times `scale`). Heights are in the
+// same units as the surface's texture coordinates.
+#[derive(Serialize, Deserialize)]
+pub struct BumpMap {
+    material: Material,
+    height: Textured<f32>,
+    #[serde(default = "default_scale")]
+    scale: f32,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+impl NormalMap {
+    pub fn new(material: Material, map: Box<dyn Texture>) -> NormalMap {
+        NormalMap { material, map }
+    }
+
+    fn normal_at(&self, intersection: &Intersection) -> Vec3 {
+        let color = self.map.color(intersection);
+        let [x, y, z] = [color.r(), color.g(), color.b()].map(|c| 2.0 * c - 1.0);
+
+        // (the cross product gives a second tangent pointing along v, even when dpdv isn't
+        // quite perpendicular to dpdu)
+        let normal = &intersection.shading_normal;
+        let tangent = intersection.dpdu.normalize();
+        let bitangent = Vec3::cross(normal, &tangent);
+        Vec3::lin_comb(vec![(x, &tangent), (y, &bitangent), (z, normal)]).normalize()
+    }
+}
+
+impl BumpMap {
+    pub fn new(material: Material, height: impl Into<Textured<f32>>, scale: f32) -> BumpMap {
+        BumpMap {
+            material,
+            height: height.into(),
+            scale,
+        }
+    }
+
+    // Finds how fast the height changes along u and v with finite differences, over about a
+    // pixel's footprint when that's known. Moving the surface by the height bends its tangents by
+    // those slopes along the normal, and the new normal is their cross product.
+    fn normal_at(&self, intersection: &Intersection) -> Vec3 {
+        let step = |derivatives: [f32; 2]| {
+            let step = 0.5 * (derivatives[0].abs() + derivatives[1].abs());
+            if step > 0.0 {
+                step
+            } else {
+                0.0005
+            }
+        };
+        let du = step([intersection.duvdx.0, intersection.duvdy.0]);
+        let dv = step([intersection.duvdx.1, intersection.duvdy.1]);
+
+        let (u, v) = intersection.uv;
+        let height_at = |uv: (f32, f32), point: Vec3| {
+            let shifted = Intersection {
+                uv,
+                point,
+                ..intersection.clone()
+            };
+            self.scale * self.height.at(&shifted)
+        };
+        let height = height_at(intersection.uv, intersection.point.clone());
+        let height_du = height_at(
+            (u + du, v),
+            &intersection.point + &(du * &intersection.dpdu),
+        );
+        let height_dv = height_at(
+            (u, v + dv),
+            &intersection.point + &(dv * &intersection.dpdv),
+        );
+
+        let normal = &intersection.shading_normal;
+        let dpdu = &intersection.dpdu + &(((height_du - height) / du) * normal);
+        let dpdv = &intersection.dpdv + &(((height_dv - height) / dv) * normal);
+
+        // dpdu x dpdv points out of the surface, which might be the other way from the normal
+        let bumped = Vec3::cross(&dpdu, &dpdv).normalize();
+        if Vec3::dot(&bumped, normal) < 0.0 {
+            Vec3::negative(&bumped)
+        } else {
+            bumped
+        }
+    }
+}
+
+// Scatters with `material` as if the surface had a different shading normal. A tilted normal
+// can send rays through the actual surface (eg. a diffuse bounce going into the floor) or keep
+// ones that should go through on the near side. Those get mirrored across the surface, so that
+// they end up on the side that the material meant them to.
+fn scatter_with_normal(
+    material: &Material,
+    incoming_ray: &Ray,
+    intersection: &Intersection,
+    shading_normal: Vec3,
+) -> Option<(Ray, Color)> {
+    if !shading_normal.x.is_finite() || shading_normal.is_small() {
+        return material.scatter_ray(incoming_ray, intersection);
+    }
+
+    let tilted = Intersection {
+        shading_normal,
+        ..intersection.clone()
+    };
+    let (mut ray, color) = material.scatter_ray(incoming_ray, &tilted)?;
+
+    let true_side = Vec3::dot(&ray.dir, &intersection.normal);
+    let shading_side = Vec3::dot(&ray.dir, &tilted.shading_normal);
+    if true_side * shading_side < 0.0 {
+        ray.dir = &ray.dir - &((2.0 * true_side) * &intersection.normal);
+    }
+    Some((ray, color))
+}
+
+#[typetag::serde]
+impl ScatterRay for NormalMap {
+    fn scatter_ray(&self, incoming_ray: &Ray, intersection: &Intersection) -> Option<(Ray, Color)> {
+        let normal = self.normal_at(intersection);
+        scatter_with_normal(&self.material, incoming_ray, intersection, normal)
+    }
+
+    fn absorption(&self, intersection: &Intersection) -> Option<Color> {
+        self.material.absorption(intersection)
+    }
+}
+
+#[typetag::serde]
+impl ScatterRay for BumpMap {
+    fn scatter_ray(&self, incoming_ray: &Ray, intersection: &Intersection) -> Option<(Ray, Color)> {
+        let normal = self.normal_at(intersection);
+        scatter_with_normal(&self.material, incoming_ray, intersection, normal)
+    }
+
+    fn absorption(&self, intersection: &Intersection) -> Option<Color> {
+        self.material.absorption(intersection)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::scene::object::{
+        material::{lambertian::Lambertian, metal::Metal},
+        texture::{constant::Constant, gradient::Gradient, gradient::GradientAxis},
+    };
+
+    // a hit on a floor, where u goes along x and v along -z
+    fn floor_hit() -> Intersection {
+        Intersection::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            true,
+        )
+        .with_uv(
+            (0.5, 0.5),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(0.0, 0.0, -1.0),
+        )
+    }
+
+    fn mirror() -> Material {
+        Arc::new(Metal::new(Color::from_rgb_f32(1.0, 1.0, 1.0), 0.0))
+    }
+
+    fn assert_close(a: &Vec3, b: &Vec3) {
+        assert!((a - b).length() < 1e-4, "{a:?} isn't close to {b:?}");
+    }
+
+    #[test]
+    fn flat_normal_map_changes_nothing() {
+        let flat = Constant::new(Color::from_rgb_f32(0.5, 0.5, 1.0));
+        let material = NormalMap::new(mirror(), Box::new(flat));
+        assert_close(&material.normal_at(&floor_hit()), &Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn normal_map_tilts_along_tangents() {
+        // pointing halfway along u
+        let tilted = Constant::new(Color::from_rgb_f32(1.0, 0.5, 1.0));
+        let material = NormalMap::new(mirror(), Box::new(tilted));
+        let expected = Vec3::new(1.0, 1.0, 0.0).normalize();
+        assert_close(&material.normal_at(&floor_hit()), &expected);
+
+        // and halfway along v, which is -z here
+        let tilted = Constant::new(Color::from_rgb_f32(0.5, 1.0, 1.0));
+        let material = NormalMap::new(mirror(), Box::new(tilted));
+        let expected = Vec3::new(0.0, 1.0, -1.0).normalize();
+        assert_close(&material.normal_at(&floor_hit()), &expected);
+    }
+
+    #[test]
+    fn bumps_tilt_away_from_the_slope() {
+        // rising by 1 as u goes from 0 to 1 (so a 45 degree slope)
+        let ramp = Gradient::new(
+            vec![
+                Color::from_rgb_f32(0.0, 0.0, 0.0),
+                Color::from_rgb_f32(1.0, 1.0, 1.0),
+            ],
+            GradientAxis::U,
+        );
+        let material = BumpMap::new(mirror(), Textured::Texture(Box::new(ramp)), 1.0);
+        let expected = Vec3::new(-1.0, 1.0, 0.0).normalize();
+        assert_close(&material.normal_at(&floor_hit()), &expected);
+
+        // and flat heights don't tilt at all
+        let material = BumpMap::new(mirror(), 0.3, 1.0);
+        assert_close(&material.normal_at(&floor_hit()), &Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn scattered_rays_stay_above_the_surface() {
+        // tilted so far that lots of diffuse bounces would go into the floor
+        let tilted = Constant::new(Color::from_rgb_f32(1.0, 0.5, 0.6));
+        let diffuse: Material = Arc::new(Lambertian::new(Color::from_rgb_f32(0.5, 0.5, 0.5)));
+        let material = NormalMap::new(diffuse, Box::new(tilted));
+        let ray = Ray::new(Vec3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+
+        for _ in 0..100 {
+            let (scattered, _) = material.scatter_ray(&ray, &floor_hit()).unwrap();
+            assert!(Vec3::dot(&scattered.dir, &Vec3::new(0.0, 1.0, 0.0)) >= 0.0);
+        }
+    }
+
+    #[test]
+    fn deserialize() {
+        let yaml = r#"
+BumpMap:
+  material:
+    Lambertian:
+      albedo: { x: 0.5, y: 0.5, z: 0.5 }
+  height:
+    Marble:
+      colors: []
+"#;
+        let material: Box<dyn ScatterRay> = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(material.typetag_name(), "BumpMap");
+    }
+}
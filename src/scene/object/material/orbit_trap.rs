@@ -0,0 +1,105 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    math::{color::Color, ray::Ray},
+    scene::object::{
+        geometry::Intersection,
+        texture::{gradient::blend, Textured},
+    },
+};
+
+use super::{lambertian::diffuse_ray, ScatterRay};
+
+// A diffuse material for fractals, colored by the orbit trap where it's hit (the `Value`
+// attribute): the trap value is multiplied by `scale`, and picks a color along the gradient
+// through `palette` (0 is the first color, and 1 is the last). Surfaces without orbit traps get
+// the first color.
+#[derive(Serialize, Deserialize)]
+pub struct OrbitTrap {
+    palette: Vec<Color>,
+    #[serde(default = "default_scale")]
+    scale: Textured<f32>,
+}
+
+fn default_scale() -> Textured<f32> {
+    Textured::Constant(1.0)
+}
+
+impl OrbitTrap {
+    pub fn new(palette: Vec<Color>, scale: impl Into<Textured<f32>>) -> OrbitTrap {
+        OrbitTrap {
+            palette,
+            scale: scale.into(),
+        }
+    }
+}
+
+#[typetag::serde]
+impl ScatterRay for OrbitTrap {
+    fn scatter_ray(
+        &self,
+        _incoming_ray: &Ray,
+        intersection: &Intersection,
+    ) -> Option<(Ray, Color)> {
+        let trap = intersection.attribute_value().unwrap_or(0.0);
+        let color = blend(&self.palette, trap * self.scale.at(intersection));
+        Some((diffuse_ray(intersection), color))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::vec3::Vec3, scene::object::geometry::SurfaceAttribute};
+
+    fn color_for_trap(material: &OrbitTrap, trap: f32) -> Color {
+        let hit = Intersection::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            true,
+        )
+        .with_attribute(Some(SurfaceAttribute::Value(trap)));
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        material.scatter_ray(&ray, &hit).unwrap().1
+    }
+
+    #[test]
+    fn palette_gradient() {
+        let material = OrbitTrap::new(
+            vec![
+                Color::from_rgb_f32(0.0, 0.0, 0.0),
+                Color::from_rgb_f32(1.0, 0.0, 0.0),
+                Color::from_rgb_f32(1.0, 1.0, 1.0),
+            ],
+            0.5,
+        );
+
+        assert_eq!(
+            color_for_trap(&material, 0.0),
+            Color::from_rgb_f32(0.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            color_for_trap(&material, 0.5),
+            Color::from_rgb_f32(0.5, 0.0, 0.0)
+        );
+        assert_eq!(
+            color_for_trap(&material, 1.5),
+            Color::from_rgb_f32(1.0, 0.5, 0.5)
+        );
+        // past the end of the palette
+        assert_eq!(
+            color_for_trap(&material, 3.0),
+            Color::from_rgb_f32(1.0, 1.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn single_color_palette() {
+        let material = OrbitTrap::new(vec![Color::from_rgb_f32(0.2, 0.4, 0.6)], 1.0);
+        assert_eq!(
+            color_for_trap(&material, 0.7),
+            Color::from_rgb_f32(0.2, 0.4, 0.6)
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::{
+    math::{color::Color, ray::Ray},
+    scene::object::{
+        geometry::{voxel_grid::vox, Intersection},
+        Material,
+    },
+};
+
+use super::{lambertian::Lambertian, ScatterRay};
+
+// Picks a material by the palette index (the `Id` attribute) of the hit (eg. on a `VoxelGrid`),
+// falling back to `default` for indices that aren't in `materials` and hits without an index.
+// Without a default, those absorb all light.
+#[derive(Deserialize)]
+#[serde(try_from = "PaletteParts")]
+pub struct Palette {
+    parts: PaletteParts,
+    // `parts.materials`, filled in with the colors from `parts.vox`
+    materials: BTreeMap<u8, Material>,
+}
+
+// what a palette looks like in a scene file
+#[derive(Serialize, Deserialize)]
+struct PaletteParts {
+    #[serde(default)]
+    materials: BTreeMap<u8, Material>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default: Option<Material>,
+    // a MagicaVoxel file whose colors become diffuse materials, for the indices that aren't in
+    // `materials` (so a model can be colored as it was made, with some entries overridden)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    vox: Option<PathBuf>,
+}
+
+impl Palette {
+    pub fn new(materials: BTreeMap<u8, Material>, default: Option<Material>) -> Palette {
+        Palette {
+            materials: materials.clone(),
+            parts: PaletteParts {
+                materials,
+                default,
+                vox: None,
+            },
+        }
+    }
+
+    // a diffuse material for each color, eg. from a .vox file's palette (starting at index 1)
+    pub fn from_colors(colors: &[Color]) -> Palette {
+        Palette::new(diffuse_materials(colors), None)
+    }
+
+    // the colors of a .vox file, as the `vox` entry of a scene file would give
+    pub fn load_vox(path: impl Into<PathBuf>) -> Result<Palette, String> {
+        Palette::try_from(PaletteParts {
+            materials: BTreeMap::new(),
+            default: None,
+            vox: Some(path.into()),
+        })
+    }
+
+    fn material_for(&self, intersection: &Intersection) -> Option<&Material> {
+        intersection
+            .attribute_id()
+            .and_then(|index| self.materials.get(&index))
+            .or(self.parts.default.as_ref())
+    }
+}
+
+fn diffuse_materials(colors: &[Color]) -> BTreeMap<u8, Material> {
+    (1..=255)
+        .zip(colors)
+        .map(|(index, color)| {
+            let material: Material = Arc::new(Lambertian::new(color.clone()));
+            (index, material)
+        })
+        .collect()
+}
+
+impl TryFrom<PaletteParts> for Palette {
+    type Error = String;
+
+    fn try_from(parts: PaletteParts) -> Result<Self, Self::Error> {
+        let mut materials = match &parts.vox {
+            Some(path) => diffuse_materials(&vox::load(path)?.palette),
+            None => BTreeMap::new(),
+        };
+        materials.extend(
+            parts
+                .materials
+                .iter()
+                .map(|(index, material)| (*index, material.clone())),
+        );
+        Ok(Palette { parts, materials })
+    }
+}
+
+// only the parts from the scene file get saved
+impl Serialize for Palette {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.parts.serialize(serializer)
+    }
+}
+
+#[typetag::serde]
+impl ScatterRay for Palette {
+    fn scatter_ray(&self, incoming_ray: &Ray, intersection: &Intersection) -> Option<(Ray, Color)> {
+        self.material_for(intersection)?
+            .scatter_ray(incoming_ray, intersection)
+    }
+
+    fn absorption(&self, intersection: &Intersection) -> Option<Color> {
+        self.material_for(intersection)?.absorption(intersection)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::*;
+    use crate::{
+        math::vec3::Vec3,
+        scene::object::geometry::{
+            voxel_grid::vox::tests::vox_file, IntersectRay, SurfaceAttribute,
+        },
+    };
+
+    fn hit(palette_index: Option<u8>) -> Intersection {
+        Intersection::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            true,
+        )
+        .with_attribute(palette_index.map(SurfaceAttribute::Id))
+    }
+
+    #[test]
+    fn pick_material_by_index() {
+        let red = Color::from_rgb_f32(1.0, 0.0, 0.0);
+        let green = Color::from_rgb_f32(0.0, 1.0, 0.0);
+        let palette = Palette::from_colors(&[red.clone(), green.clone()]);
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+
+        let color = |index| {
+            palette
+                .scatter_ray(&ray, &hit(index))
+                .map(|(_, color)| color)
+        };
+        assert_eq!(color(Some(1)), Some(red));
+        assert_eq!(color(Some(2)), Some(green));
+        assert_eq!(color(Some(3)), None);
+        assert_eq!(color(None), None);
+    }
+
+    #[test]
+    fn deserialize_with_default() {
+        let yaml = "
+materials:
+  4:
+    Lambertian:
+      albedo: { x: 1.0, y: 1.0, z: 1.0 }
+default:
+  Lambertian:
+    albedo: { x: 0.5, y: 0.5, z: 0.5 }
+";
+        let palette: Palette = serde_yaml::from_str(yaml).unwrap();
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let (_, color) = palette.scatter_ray(&ray, &hit(Some(7))).unwrap();
+        assert_eq!(color, Color::from_rgb_f32(0.5, 0.5, 0.5));
+    }
+
+    #[test]
+    fn color_a_vox_model_with_its_palette() {
+        let path = env::temp_dir().join("rays_palette_color_a_vox_model.vox");
+        fs::write(&path, vox_file(true)).unwrap();
+
+        // the model's grid and its palette, with index 3 swapped for a mirror
+        let yaml = format!(
+            "
+geometry:
+  VoxelGrid:
+    corner: {{ x: 0.0, y: 0.0, z: 0.0 }}
+    voxels:
+      Vox: {path}
+material:
+  Palette:
+    vox: {path}
+    materials:
+      3:
+        Metal:
+          albedo: {{ x: 1.0, y: 1.0, z: 1.0 }}
+          fuzz: 0.0
+",
+            path = path.display()
+        );
+        let object: BTreeMap<String, serde_yaml::Value> = serde_yaml::from_str(&yaml).unwrap();
+        let geometry: Box<dyn IntersectRay> =
+            serde_yaml::from_value(object["geometry"].clone()).unwrap();
+        let palette: Box<dyn ScatterRay> =
+            serde_yaml::from_value(object["material"].clone()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // straight down onto the voxel with index 1, which is green
+        let ray = Ray::new(Vec3::new(0.5, 10.0, 2.5), Vec3::new(0.0, -1.0, 0.0));
+        let intersection = geometry.intersect_ray(&ray).unwrap();
+        assert_eq!(intersection.attribute_id(), Some(1));
+        let (_, color) = palette.scatter_ray(&ray, &intersection).unwrap();
+        assert_eq!(color, Color::from_rgb_u8(0, 255, 0));
+
+        // the override is used over the file's red, and saved as it was given
+        let (_, color) = palette.scatter_ray(&ray, &hit(Some(3))).unwrap();
+        assert_eq!(color, Color::from_rgb_f32(1.0, 1.0, 1.0));
+        let saved = serde_yaml::to_string(&palette).unwrap();
+        assert!(saved.contains("vox:") && saved.contains("Metal"));
+    }
+}
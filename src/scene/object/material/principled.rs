@@ -0,0 +1,398 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    math::{color::Color, ray::Ray, sampler, shaping::lerp, vec3::Vec3},
+    scene::object::{geometry::Intersection, texture::Textured},
+};
+
+use super::{
+    fresnel,
+    microfacet::{self, Frame, Ggx},
+    ScatterRay,
+};
+
+// An all-in-one material with the parameters artists are used to, after Disney's "principled"
+// BRDF (Burley, "Physically-Based Shading at Disney", 2012) with its transmission extension.
+// Everything is texturable, goes from 0 to 1 (except `ior`), and defaults to what Blender uses,
+// so that eg. `Principled: { base_color: ... }` is a good default for imported assets.
+//
+// It's a stack of lobes, one of which is picked at random for each ray:
+// - a clear coat on top, which reflects a little light off of a smooth layer,
+// - then a metallic fraction, which reflects tinted by `base_color`,
+// - then (of the rest) a transmission fraction, which is rough glass tinted by `base_color`,
+// - and the rest is a specular reflection over a diffuse base, with sheen at grazing angles and
+//   `subsurface` flattening the diffuse as if light scattered around under the surface.
+#[derive(Serialize, Deserialize)]
+pub struct Principled {
+    #[serde(default = "default_base_color")]
+    base_color: Textured<Color>,
+    #[serde(default = "zero")]
+    metallic: Textured<f32>,
+    #[serde(default = "half")]
+    roughness: Textured<f32>,
+    #[serde(default = "zero")]
+    anisotropic: Textured<f32>,
+
+    // the reflectance of the dielectric parts, where 0.5 is about 4% head on (most materials)
+    #[serde(default = "half")]
+    specular: Textured<f32>,
+    #[serde(default = "zero")]
+    specular_tint: Textured<f32>,
+
+    #[serde(default = "zero")]
+    sheen: Textured<f32>,
+    #[serde(default = "half")]
+    sheen_tint: Textured<f32>,
+
+    #[serde(default = "zero")]
+    clearcoat: Textured<f32>,
+    #[serde(default = "default_clearcoat_roughness")]
+    clearcoat_roughness: Textured<f32>,
+
+    #[serde(default = "zero")]
+    transmission: Textured<f32>,
+    #[serde(default = "default_ior")]
+    ior: Textured<f32>,
+
+    #[serde(default = "zero")]
+    subsurface: Textured<f32>,
+}
+
+fn default_base_color() -> Textured<Color> {
+    Textured::Constant(Color::from_rgb_f32(0.8, 0.8, 0.8))
+}
+
+fn zero() -> Textured<f32> {
+    Textured::Constant(0.0)
+}
+
+fn half() -> Textured<f32> {
+    Textured::Constant(0.5)
+}
+
+fn default_clearcoat_roughness() -> Textured<f32> {
+    Textured::Constant(0.03)
+}
+
+fn default_ior() -> Textured<f32> {
+    Textured::Constant(1.45)
+}
+
+// the parameters looked up at one point
+struct Parameters {
+    base_color: Color,
+    metallic: f32,
+    roughness: f32,
+    anisotropic: f32,
+    specular: f32,
+    specular_tint: f32,
+    sheen: f32,
+    sheen_tint: f32,
+    clearcoat: f32,
+    clearcoat_roughness: f32,
+    transmission: f32,
+    ior: f32,
+    subsurface: f32,
+}
+
+impl Parameters {
+    // the base color's hue and saturation, without its brightness
+    fn tint(&self) -> Color {
+        let c = &self.base_color;
+        let luminance = 0.3 * c.r() + 0.6 * c.g() + 0.1 * c.b();
+        if luminance > 0.0 {
+            (1.0 / luminance) * c
+        } else {
+            white()
+        }
+    }
+
+    fn ggx(&self) -> Ggx {
+        Ggx::new(self.roughness, self.anisotropic)
+    }
+}
+
+fn white() -> Color {
+    Color::from_rgb_f32(1.0, 1.0, 1.0)
+}
+
+// (each lobe gives a direction in the local frame and the weight for it, or nothing if the
+// light was lost)
+type Sample = Option<(Vec3, Color)>;
+
+impl Principled {
+    pub fn new(base_color: impl Into<Textured<Color>>) -> Principled {
+        Principled {
+            base_color: base_color.into(),
+            metallic: zero(),
+            roughness: half(),
+            anisotropic: zero(),
+            specular: half(),
+            specular_tint: zero(),
+            sheen: zero(),
+            sheen_tint: half(),
+            clearcoat: zero(),
+            clearcoat_roughness: default_clearcoat_roughness(),
+            transmission: zero(),
+            ior: default_ior(),
+            subsurface: zero(),
+        }
+    }
+
+    pub fn with_roughness(self, roughness: impl Into<Textured<f32>>) -> Principled {
+        Principled {
+            roughness: roughness.into(),
+            ..self
+        }
+    }
+
+    pub fn with_clearcoat(self, clearcoat: impl Into<Textured<f32>>) -> Principled {
+        Principled {
+            clearcoat: clearcoat.into(),
+            ..self
+        }
+    }
+
+    fn parameters(&self, intersection: &Intersection) -> Parameters {
+        let unit = |value: &Textured<f32>| value.at(intersection).clamp(0.0, 1.0);
+        Parameters {
+            base_color: self.base_color.at(intersection),
+            metallic: unit(&self.metallic),
+            roughness: unit(&self.roughness),
+            anisotropic: unit(&self.anisotropic),
+            specular: unit(&self.specular),
+            specular_tint: unit(&self.specular_tint),
+            sheen: unit(&self.sheen),
+            sheen_tint: unit(&self.sheen_tint),
+            clearcoat: unit(&self.clearcoat),
+            clearcoat_roughness: unit(&self.clearcoat_roughness),
+            transmission: unit(&self.transmission),
+            ior: self.ior.at(intersection),
+            subsurface: unit(&self.subsurface),
+        }
+    }
+}
+
+// A smooth-ish dielectric layer reflecting up to a quarter of the light (as in Disney's model),
+// with an index of refraction of 1.5. Light that it doesn't reflect goes on through to the
+// layers below, which is when this doesn't give a sample.
+fn clearcoat(p: &Parameters, wo: &Vec3) -> Option<Sample> {
+    let coat = Ggx::new(p.clearcoat_roughness, 0.0);
+    let mut rng = sampler::rng();
+    let h = coat.sample_visible_normal(wo, rng.gen(), rng.gen());
+    let reflectance = 0.25 * p.clearcoat * fresnel::dielectric(Vec3::dot(wo, &h), 1.5);
+    if rng.gen::<f32>() >= reflectance {
+        return None;
+    }
+
+    let wi = microfacet::reflect(wo, &h);
+    let weight = coat.g2(wo, &wi) / coat.g1(wo);
+    Some(above(wi, weight * &white()))
+}
+
+// reflections off of facets that end up going into the surface are lost
+fn above(wi: Vec3, weight: Color) -> Sample {
+    (wi.z > 0.0).then_some((wi, weight))
+}
+
+fn metal(p: &Parameters, wo: &Vec3) -> Sample {
+    let ggx = p.ggx();
+    let mut rng = sampler::rng();
+    let h = ggx.sample_visible_normal(wo, rng.gen(), rng.gen());
+    let wi = microfacet::reflect(wo, &h);
+
+    let reflectance = fresnel::schlick(Vec3::dot(wo, &h), &p.base_color);
+    let weight = ggx.g2(wo, &wi) / ggx.g1(wo);
+    above(wi, weight * &reflectance)
+}
+
+// rough glass (like `RoughDielectric`), where `eta` is the index of refraction on the far side
+// relative to this one, and light going in is tinted by `tint`
+fn glass(p: &Parameters, wo: &Vec3, eta: f32, tint: Color) -> Sample {
+    let ggx = p.ggx();
+    let mut rng = sampler::rng();
+    let h = ggx.sample_visible_normal(wo, rng.gen(), rng.gen());
+
+    let reflectance = fresnel::dielectric(Vec3::dot(wo, &h), eta);
+    let (wi, tint, is_reflection) = match microfacet::refract(wo, &h, eta) {
+        Some(refracted) if rng.gen::<f32>() >= reflectance => (refracted, tint, false),
+        _ => (microfacet::reflect(wo, &h), white(), true),
+    };
+    if (wi.z > 0.0) != is_reflection {
+        return None;
+    }
+    let weight = ggx.g2(wo, &wi) / ggx.g1(wo);
+    Some((wi, weight * &tint))
+}
+
+// A specular reflection over a diffuse base: the specular lobe is picked by how much it
+// reflects, which leaves the rest of the light to the diffuse one.
+fn dielectric_base(p: &Parameters, wo: &Vec3) -> Sample {
+    let ggx = p.ggx();
+    let mut rng = sampler::rng();
+    let h = ggx.sample_visible_normal(wo, rng.gen(), rng.gen());
+
+    let specular_color = lerp(p.specular_tint, &white(), &p.tint());
+    let reflectance = fresnel::schlick(Vec3::dot(wo, &h), &((0.08 * p.specular) * &specular_color));
+    let specular_chance = reflectance.r().max(reflectance.g()).max(reflectance.b());
+    if rng.gen::<f32>() < specular_chance {
+        let wi = microfacet::reflect(wo, &h);
+        let weight = ggx.g2(wo, &wi) / (ggx.g1(wo) * specular_chance);
+        return above(wi, weight * &reflectance);
+    }
+
+    // Diffuse directions are picked in proportion to the cosine (like `Lambertian`), which
+    // cancels out of the weight along with the 1 / pi.
+    let mut wi = &Vec3::new(0.0, 0.0, 1.0) + &Vec3::random_unit_vector();
+    if wi.is_small() {
+        wi = Vec3::new(0.0, 0.0, 1.0);
+    }
+    let wi = wi.normalize();
+
+    let half_vector = (wo + &wi).normalize();
+    let cos_d = Vec3::dot(&wi, &half_vector);
+    let fresnel_in = (1.0 - wi.z.clamp(0.0, 1.0)).powi(5);
+    let fresnel_out = (1.0 - wo.z.clamp(0.0, 1.0)).powi(5);
+    let retro = |at_grazing: f32| {
+        (1.0 + (at_grazing - 1.0) * fresnel_in) * (1.0 + (at_grazing - 1.0) * fresnel_out)
+    };
+
+    // rough surfaces get brighter at grazing angles (retro-reflection)
+    let diffuse = retro(0.5 + 2.0 * p.roughness * cos_d * cos_d);
+
+    // Hanrahan-Krueger-like flattening, which is brighter at the edges
+    let flattened =
+        1.25 * (retro(p.roughness * cos_d * cos_d) * (1.0 / (wi.z + wo.z).max(1e-4) - 0.5) + 0.5);
+
+    let sheen_color = lerp(p.sheen_tint, &white(), &p.tint());
+    let sheen = p.sheen * (1.0 - cos_d).powi(5);
+
+    let shape = lerp(p.subsurface, &diffuse, &flattened);
+    Some((wi, &(shape * &p.base_color) + &(sheen * &sheen_color)))
+}
+
+#[typetag::serde]
+impl ScatterRay for Principled {
+    fn scatter_ray(&self, incoming_ray: &Ray, intersection: &Intersection) -> Option<(Ray, Color)> {
+        let p = self.parameters(intersection);
+
+        // work on the side the ray came from
+        let (wo, frame, true_normal) = Frame::facing(incoming_ray, intersection);
+        let wo = frame.to_local(&wo);
+        if wo.z <= 0.0 {
+            return None;
+        }
+
+        // Light inside has come through the glass part of the surface, so it can only be going
+        // back out through it. Everywhere else, one of the layers from the top down is picked.
+        let sample = if !intersection.is_into_surface && p.transmission > 0.0 {
+            glass(&p, &wo, 1.0 / p.ior, white())
+        } else {
+            let chance: f32 = sampler::rng().gen();
+            let coat = if p.clearcoat > 0.0 {
+                clearcoat(&p, &wo)
+            } else {
+                None
+            };
+
+            match coat {
+                Some(coat) => coat,
+                None if chance < p.metallic => metal(&p, &wo),
+                None if chance < p.metallic + (1.0 - p.metallic) * p.transmission => {
+                    glass(&p, &wo, p.ior, p.base_color.clone())
+                }
+                None => dielectric_base(&p, &wo),
+            }
+        };
+        let (wi, weight) = sample?;
+
+        // (a tilted shading normal can still put it on the wrong side of the true surface)
+        let dir = frame.to_world(&wi);
+        if (wi.z > 0.0) != (Vec3::dot(&dir, &true_normal) > 0.0) {
+            return None;
+        }
+        Some((Ray::new(intersection.point.clone(), dir), weight))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn floor_hit() -> Intersection {
+        Intersection::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            1.0,
+            true,
+        )
+    }
+
+    fn from_yaml(yaml: &str) -> Principled {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn defaults() {
+        let material = from_yaml("{}");
+        let p = material.parameters(&floor_hit());
+        assert_eq!(p.base_color, Color::from_rgb_f32(0.8, 0.8, 0.8));
+        assert_eq!((p.roughness, p.specular, p.ior), (0.5, 0.5, 1.45));
+        assert_eq!((p.metallic, p.transmission, p.clearcoat), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn smooth_metal_reflects_its_color() {
+        let gold = Color::from_rgb_f32(1.0, 0.8, 0.3);
+        let material =
+            from_yaml("base_color: { x: 1.0, y: 0.8, z: 0.3 }\nmetallic: 1.0\nroughness: 0.0");
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+
+        let (scattered, color) = material.scatter_ray(&ray, &floor_hit()).unwrap();
+        assert!(scattered.dir.normalize().y > 0.999);
+        assert!((color.r() - gold.r()).abs() < 1e-3);
+        assert!((color.b() - gold.b()).abs() < 1e-3);
+    }
+
+    #[test]
+    fn diffuse_stays_above_the_surface() {
+        let material = from_yaml("roughness: 1.0\nsheen: 1.0\nsubsurface: 0.5");
+        let ray = Ray::new(Vec3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+        for _ in 0..200 {
+            if let Some((scattered, color)) = material.scatter_ray(&ray, &floor_hit()) {
+                assert!(scattered.dir.y > 0.0);
+                assert!(color.is_finite() && color.r() >= 0.0 && color.r() < 4.0);
+            }
+        }
+    }
+
+    #[test]
+    fn clear_glass_lets_light_through() {
+        let material = from_yaml(
+            "base_color: { x: 1.0, y: 1.0, z: 1.0 }\ntransmission: 1.0\nroughness: 0.0\nior: 1.5",
+        );
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+
+        let through = (0..500)
+            .filter_map(|_| material.scatter_ray(&ray, &floor_hit()))
+            .filter(|(scattered, _)| scattered.dir.y < 0.0)
+            .count();
+        assert!(through > 440, "only {through} went through");
+    }
+
+    #[test]
+    fn clearcoat_reflects_off_of_black() {
+        // with nothing else reflecting, light only comes back off of the coat
+        let material =
+            from_yaml("base_color: { x: 0.0, y: 0.0, z: 0.0 }\nspecular: 0.0\nclearcoat: 1.0");
+
+        // head on, where the coat reflects 1% of the light
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+        let reflected = (0..4000)
+            .filter_map(|_| material.scatter_ray(&ray, &floor_hit()))
+            .filter(|(_, color)| color.r() > 0.0)
+            .count();
+        assert!((15..=80).contains(&reflected), "{reflected} reflected");
+    }
+}
@@ -0,0 +1,199 @@
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    math::{color::Color, ray::Ray, sampler, vec3::Vec3},
+    scene::object::{geometry::Intersection, texture::Textured},
+};
+
+use super::{
+    fresnel,
+    medium::Absorption,
+    microfacet::{self, Frame, Ggx},
+    ScatterRay,
+};
+
+// Glass, ice, clear plastic and so on, with a surface of microscopic facets (see `microfacet`)
+// that each reflect or refract light, following Walter et al., "Microfacet Models for Refraction
+// through Rough Surfaces" (2007). A roughness of 0 is smooth glass, and higher ones give frosted
+// or sandblasted surfaces. Unlike `Translucent`, reflectance uses the exact Fresnel equations.
+//
+// Like `Translucent`, it can absorb light going through the inside, so that thick parts look
+// darker.
+#[derive(Serialize, Deserialize)]
+pub struct RoughDielectric {
+    refractive_index: Textured<f32>,
+    #[serde(default = "default_roughness")]
+    roughness: Textured<f32>,
+    #[serde(default = "default_anisotropy")]
+    anisotropy: Textured<f32>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "serde_yaml::with::singleton_map"
+    )]
+    absorption: Option<Absorption>,
+}
+
+fn default_roughness() -> Textured<f32> {
+    Textured::Constant(0.0)
+}
+
+fn default_anisotropy() -> Textured<f32> {
+    Textured::Constant(0.0)
+}
+
+impl RoughDielectric {
+    pub fn new(
+        refractive_index: impl Into<Textured<f32>>,
+        roughness: impl Into<Textured<f32>>,
+    ) -> RoughDielectric {
+        RoughDielectric {
+            refractive_index: refractive_index.into(),
+            roughness: roughness.into(),
+            anisotropy: default_anisotropy(),
+            absorption: None,
+        }
+    }
+
+    pub fn with_absorption(self, absorption: Absorption) -> RoughDielectric {
+        RoughDielectric {
+            absorption: Some(absorption),
+            ..self
+        }
+    }
+}
+
+#[typetag::serde]
+impl ScatterRay for RoughDielectric {
+    fn scatter_ray(&self, incoming_ray: &Ray, intersection: &Intersection) -> Option<(Ray, Color)> {
+        let refractive_index = self.refractive_index.at(intersection);
+        let eta = if intersection.is_into_surface {
+            refractive_index
+        } else {
+            1.0 / refractive_index
+        };
+
+        // work on the side the ray came from
+        let (wo, frame, true_normal) = Frame::facing(incoming_ray, intersection);
+
+        let ggx = Ggx::new(
+            self.roughness.at(intersection),
+            self.anisotropy.at(intersection),
+        );
+        let wo_local = frame.to_local(&wo);
+        let mut rng = sampler::rng();
+        let h = ggx.sample_visible_normal(&wo_local, rng.gen(), rng.gen());
+
+        // Choosing between reflecting and refracting by the facet's reflectance cancels it out
+        // of the weight, which (as for `Conductor`) leaves the left over masking.
+        let reflectance = fresnel::dielectric(Vec3::dot(&wo_local, &h), eta);
+        let refracted = microfacet::refract(&wo_local, &h, eta);
+        let (wi, is_reflection) = match refracted {
+            Some(refracted) if rng.gen::<f32>() >= reflectance => (refracted, false),
+            _ => (microfacet::reflect(&wo_local, &h), true),
+        };
+
+        // facets can send light to the wrong side of the surface, where it's lost
+        let dir = frame.to_world(&wi);
+        if (wi.z > 0.0) != is_reflection || (Vec3::dot(&dir, &true_normal) > 0.0) != is_reflection {
+            return None;
+        }
+
+        let weight = ggx.g2(&wo_local, &wi) / ggx.g1(&wo_local);
+        Some((
+            Ray::new(intersection.point.clone(), dir),
+            Color::from_rgb_f32(weight, weight, weight),
+        ))
+    }
+
+    fn absorption(&self, _intersection: &Intersection) -> Option<Color> {
+        self.absorption.as_ref().map(Absorption::coefficient)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hit(is_into_surface: bool, t: f32) -> Intersection {
+        Intersection::new(
+            Vec3::new(0.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.0, 0.0),
+            t,
+            is_into_surface,
+        )
+    }
+
+    #[test]
+    fn smooth_glass_mostly_lets_light_through() {
+        let glass = RoughDielectric::new(1.5, 0.0);
+        let ray = Ray::new(Vec3::new(0.0, 1.0, 0.0), Vec3::new(0.0, -1.0, 0.0));
+
+        let (mut reflected, mut bent) = (0, 0);
+        for _ in 0..2000 {
+            let (scattered, color) = glass.scatter_ray(&ray, &hit(true, 1.0)).unwrap();
+            if scattered.dir.y > 0.0 {
+                reflected += 1;
+            } else if scattered.dir.x.abs() > 1e-2 || scattered.dir.z.abs() > 1e-2 {
+                bent += 1;
+            }
+            assert!(color.r() > 0.99);
+        }
+
+        // about 4% reflects, and the rest goes straight through (except for the odd facet from
+        // far out in GGX's long tail)
+        assert!((40..=130).contains(&reflected), "{reflected} reflected");
+        assert!(bent <= 5, "{bent} bent");
+    }
+
+    #[test]
+    fn light_inside_reflects_past_the_critical_angle() {
+        let glass = RoughDielectric::new(1.5, 0.0);
+
+        // 60 degrees from the normal, going out
+        let ray = Ray::new(
+            Vec3::new(0.0, -0.5, -0.75f32.sqrt()),
+            Vec3::new(0.0, 0.5, 0.75f32.sqrt()),
+        );
+        for _ in 0..100 {
+            let (scattered, _) = glass.scatter_ray(&ray, &hit(false, 1.0)).unwrap();
+            assert!(scattered.dir.y < 0.0);
+        }
+    }
+
+    #[test]
+    fn rough_glass_scatters_to_both_sides() {
+        let frosted = RoughDielectric::new(1.5, 0.6);
+        let ray = Ray::new(Vec3::new(-1.0, 1.0, 0.0), Vec3::new(1.0, -1.0, 0.0));
+
+        let mut directions = vec![];
+        for _ in 0..500 {
+            if let Some((scattered, _)) = frosted.scatter_ray(&ray, &hit(true, 1.0)) {
+                directions.push(scattered.dir);
+            }
+        }
+        assert!(directions.iter().any(|dir| dir.y > 0.0));
+        assert!(directions.iter().any(|dir| dir.y < 0.0));
+
+        // and spreads them out, unlike a smooth surface
+        assert!(directions.iter().any(|dir| dir.z.abs() > 0.1));
+    }
+
+    #[test]
+    fn deserialize() {
+        let yaml = "refractive_index: 1.31\nroughness: 0.2";
+        let ice: RoughDielectric = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(ice.absorption, None);
+
+        let yaml = r#"
+refractive_index: 1.5
+absorption:
+  ColorAtDistance:
+    color: { x: 0.5, y: 0.8, z: 0.9 }
+    distance: 1.0
+"#;
+        let glass: RoughDielectric = serde_yaml::from_str(yaml).unwrap();
+        assert!(glass.absorption(&hit(true, 1.0)).is_some());
+    }
+}
@@ -2,23 +2,39 @@ use rand::Rng;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    math::{color::Color, ray::Ray, vec3::Vec3},
-    scene::object::geometry::Intersection,
+    math::{color::Color, ray::Ray, sampler, vec3::Vec3},
+    scene::object::{geometry::Intersection, texture::Textured},
 };
 
-use super::ScatterRay;
+use super::{medium::Absorption, ScatterRay};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+// `albedo` tints light at the surface, while `absorption` tints it by how far it goes through
+// the inside, which is what colored glass does.
+#[derive(Serialize, Deserialize)]
 pub struct Translucent {
-    albedo: Color,
-    refractive_index: f32,
+    albedo: Textured<Color>,
+    refractive_index: Textured<f32>,
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "serde_yaml::with::singleton_map"
+    )]
+    absorption: Option<Absorption>,
 }
 
 impl Translucent {
-    pub fn new(refractive_index: f32) -> Translucent {
+    pub fn new(refractive_index: impl Into<Textured<f32>>) -> Translucent {
         Translucent {
-            albedo: Color::from_rgb_u8(255, 255, 255),
-            refractive_index,
+            albedo: Color::from_rgb_u8(255, 255, 255).into(),
+            refractive_index: refractive_index.into(),
+            absorption: None,
+        }
+    }
+
+    pub fn with_absorption(self, absorption: Absorption) -> Translucent {
+        Translucent {
+            absorption: Some(absorption),
+            ..self
         }
     }
 
@@ -32,22 +48,19 @@ impl Translucent {
 
 #[typetag::serde]
 impl ScatterRay for Translucent {
-    fn scatter_ray(
-        &self,
-        incoming_ray: &Ray,
-        intersection: &Intersection,
-    ) -> Option<(Ray, &Color)> {
+    fn scatter_ray(&self, incoming_ray: &Ray, intersection: &Intersection) -> Option<(Ray, Color)> {
+        let refractive_index = self.refractive_index.at(intersection);
         let refractive_ratio = if intersection.is_into_surface {
-            1.0 / self.refractive_index
+            1.0 / refractive_index
         } else {
-            self.refractive_index
+            refractive_index
         };
 
         let normal = (if intersection.is_into_surface {
             1.0
         } else {
             -1.0
-        }) * &intersection.normal;
+        }) * &intersection.shading_normal;
 
         let cos_theta = -Vec3::dot(&incoming_ray.dir, &normal) / incoming_ray.dir.length();
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
@@ -58,7 +71,7 @@ impl ScatterRay for Translucent {
         // compute the reflectance of the material, then determine if this ray will
         // be reflected
         let reflectance = Translucent::reflectance(cos_theta, refractive_ratio);
-        let mut rng = rand::thread_rng();
+        let mut rng = sampler::rng();
         let reflect_ray = must_reflect || (reflectance > rng.gen());
 
         let new_ray_dir = if reflect_ray {
@@ -69,7 +82,11 @@ impl ScatterRay for Translucent {
 
         let new_ray = Ray::new(intersection.point.clone(), new_ray_dir);
 
-        // return white as the color
-        Some((new_ray, &self.albedo))
+        // (white by default)
+        Some((new_ray, self.albedo.at(intersection)))
+    }
+
+    fn absorption(&self, _intersection: &Intersection) -> Option<Color> {
+        self.absorption.as_ref().map(Absorption::coefficient)
     }
 }
@@ -0,0 +1,110 @@
+use serde::{Deserialize, Serialize};
+
+use super::geometry::Intersection;
+use crate::math::color::Color;
+
+pub mod checkerboard;
+pub mod constant;
+pub mod gradient;
+pub mod image_texture;
+pub mod mipmap;
+pub mod procedural;
+
+// A color that varies over a surface, looked up by where the surface was hit
+// (by its texture coordinates, or by the point in space).
+#[typetag::serde]
+pub trait Texture: Send + Sync {
+    fn color(&self, intersection: &Intersection) -> Color;
+}
+
+// A material parameter that's either the same everywhere or given by a texture.
+// In scene files it's written as either the value itself or a texture, eg.
+//    albedo: { x: 0.5, y: 0.5, z: 0.5 }
+//    albedo: { Checkerboard: { ... } }
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Textured<T> {
+    Constant(T),
+    Texture(Box<dyn Texture>),
+}
+
+impl Textured<Color> {
+    pub fn at(&self, intersection: &Intersection) -> Color {
+        match self {
+            Textured::Constant(color) => color.clone(),
+            Textured::Texture(texture) => texture.color(intersection),
+        }
+    }
+}
+
+impl Textured<f32> {
+    // scalar parameters use the average of a texture's channels, so a gray texture gives its brightness
+    pub fn at(&self, intersection: &Intersection) -> f32 {
+        match self {
+            Textured::Constant(value) => *value,
+            Textured::Texture(texture) => {
+                let color = texture.color(intersection);
+                (color.r() + color.g() + color.b()) / 3.0
+            }
+        }
+    }
+}
+
+impl<T> From<T> for Textured<T> {
+    fn from(value: T) -> Self {
+        Textured::Constant(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::checkerboard::Checkerboard;
+    use super::*;
+    use crate::math::vec3::Vec3;
+
+    fn hit_at_uv(u: f32, v: f32) -> Intersection {
+        Intersection {
+            uv: (u, v),
+            ..Intersection::new(
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                1.0,
+                true,
+            )
+        }
+    }
+
+    #[test]
+    fn deserialize_constant_or_texture() {
+        let constant: Textured<Color> = serde_yaml::from_str("{ x: 0.5, y: 0.5, z: 0.5 }").unwrap();
+        assert_eq!(
+            constant.at(&hit_at_uv(0.0, 0.0)),
+            Color::from_rgb_f32(0.5, 0.5, 0.5)
+        );
+
+        let yaml = r#"
+Checkerboard:
+  even: { x: 1.0, y: 1.0, z: 1.0 }
+  odd: { x: 0.0, y: 0.0, z: 0.0 }
+  size: 0.5
+"#;
+        let textured: Textured<Color> = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            textured.at(&hit_at_uv(0.75, 0.25)),
+            Color::from_rgb_f32(0.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn scalar_from_texture() {
+        let textured: Textured<f32> = Textured::Texture(Box::new(Checkerboard::new(
+            Color::from_rgb_f32(0.3, 0.6, 0.9).into(),
+            Color::from_rgb_f32(0.0, 0.0, 0.0).into(),
+            1.0,
+        )));
+        assert!((textured.at(&hit_at_uv(0.5, 0.5)) - 0.6).abs() < 1e-6);
+
+        let constant: Textured<f32> = serde_yaml::from_str("0.25").unwrap();
+        assert_eq!(constant.at(&hit_at_uv(0.5, 0.5)), 0.25);
+    }
+}
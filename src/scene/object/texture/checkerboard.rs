@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+
+use crate::math::color::Color;
+use crate::scene::object::geometry::Intersection;
+
+use super::{Texture, Textured};
+
+// Alternating squares of two textures, laid out by texture coordinates. Squares are `size` wide
+// in uv space, which is a distance on unbounded surfaces like planes, and a fraction of the
+// surface on bounded ones (where eg. a size of 0.1 gives 10 squares across).
+#[derive(Serialize, Deserialize)]
+pub struct Checkerboard {
+    even: Textured<Color>,
+    odd: Textured<Color>,
+    #[serde(default = "default_size")]
+    size: f32,
+}
+
+// Alternating cubes of two textures, by position in space, so that the pattern carries on through
+// objects instead of wrapping around them. Surfaces lying right on a boundary between cubes
+// (eg. a floor at y = 0 with a size of 1) flicker between the two, so it's best to offset those.
+#[derive(Serialize, Deserialize)]
+pub struct SolidCheckerboard {
+    even: Textured<Color>,
+    odd: Textured<Color>,
+    #[serde(default = "default_size")]
+    size: f32,
+}
+
+fn default_size() -> f32 {
+    1.0
+}
+
+impl Checkerboard {
+    pub fn new(even: Textured<Color>, odd: Textured<Color>, size: f32) -> Checkerboard {
+        Checkerboard { even, odd, size }
+    }
+}
+
+impl SolidCheckerboard {
+    pub fn new(even: Textured<Color>, odd: Textured<Color>, size: f32) -> SolidCheckerboard {
+        SolidCheckerboard { even, odd, size }
+    }
+}
+
+// whether the sum of the coordinates' square numbers is even
+fn is_even(coords: &[f32], size: f32) -> bool {
+    let sum: i64 = coords.iter().map(|c| (c / size).floor() as i64).sum();
+    sum.rem_euclid(2) == 0
+}
+
+#[typetag::serde]
+impl Texture for Checkerboard {
+    fn color(&self, intersection: &Intersection) -> Color {
+        let (u, v) = intersection.uv;
+        if is_even(&[u, v], self.size) {
+            self.even.at(intersection)
+        } else {
+            self.odd.at(intersection)
+        }
+    }
+}
+
+#[typetag::serde]
+impl Texture for SolidCheckerboard {
+    fn color(&self, intersection: &Intersection) -> Color {
+        let p = &intersection.point;
+        if is_even(&[p.x, p.y, p.z], self.size) {
+            self.even.at(intersection)
+        } else {
+            self.odd.at(intersection)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::vec3::Vec3;
+
+    fn black() -> Color {
+        Color::from_rgb_f32(0.0, 0.0, 0.0)
+    }
+
+    fn white() -> Color {
+        Color::from_rgb_f32(1.0, 1.0, 1.0)
+    }
+
+    fn hit(point: Vec3, uv: (f32, f32)) -> Intersection {
+        Intersection {
+            uv,
+            ..Intersection::new(point, Vec3::new(0.0, 1.0, 0.0), 1.0, true)
+        }
+    }
+
+    #[test]
+    fn uv_squares_alternate() {
+        let board = Checkerboard::new(white().into(), black().into(), 0.25);
+        let at = |u, v| board.color(&hit(Vec3::new(0.0, 0.0, 0.0), (u, v)));
+
+        assert_eq!(at(0.1, 0.1), white());
+        assert_eq!(at(0.3, 0.1), black());
+        assert_eq!(at(0.3, 0.3), white());
+
+        // negative coordinates (eg. on a plane) keep alternating past zero
+        assert_eq!(at(-0.1, 0.1), black());
+        assert_eq!(at(-0.1, -0.1), white());
+    }
+
+    #[test]
+    fn solid_cubes_alternate() {
+        let board = SolidCheckerboard::new(white().into(), black().into(), 1.0);
+        let at = |x, y, z| board.color(&hit(Vec3::new(x, y, z), (0.0, 0.0)));
+
+        assert_eq!(at(0.5, 0.5, 0.5), white());
+        assert_eq!(at(0.5, 1.5, 0.5), black());
+        assert_eq!(at(-0.5, 1.5, 0.5), white());
+    }
+
+    #[test]
+    fn nested_textures() {
+        let inner = Checkerboard::new(white().into(), black().into(), 0.5);
+        let board = Checkerboard::new(Textured::Texture(Box::new(inner)), black().into(), 1.0);
+        let at = |u, v| board.color(&hit(Vec3::new(0.0, 0.0, 0.0), (u, v)));
+
+        assert_eq!(at(0.25, 0.25), white());
+        assert_eq!(at(0.75, 0.25), black());
+    }
+}
@@ -0,0 +1,25 @@
+use serde::{Deserialize, Serialize};
+
+use crate::math::color::Color;
+use crate::scene::object::geometry::Intersection;
+
+use super::Texture;
+
+// the same color everywhere, which is mostly useful inside of other textures
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Constant {
+    color: Color,
+}
+
+impl Constant {
+    pub fn new(color: Color) -> Constant {
+        Constant { color }
+    }
+}
+
+#[typetag::serde]
+impl Texture for Constant {
+    fn color(&self, _intersection: &Intersection) -> Color {
+        self.color.clone()
+    }
+}
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+
+use crate::math::{color::Color, shaping::lerp, vec3::Vec3};
+use crate::scene::object::geometry::Intersection;
+
+use super::Texture;
+
+// Blends through a list of colors, spread evenly from 0 (the first color) to 1 (the last one),
+// going along u or v, or through space from one point to another.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Gradient {
+    colors: Vec<Color>,
+    #[serde(with = "serde_yaml::with::singleton_map")]
+    along: GradientAxis,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub enum GradientAxis {
+    U,
+    V,
+    Between { start: Vec3, end: Vec3 },
+}
+
+impl Gradient {
+    pub fn new(colors: Vec<Color>, along: GradientAxis) -> Gradient {
+        Gradient { colors, along }
+    }
+}
+
+// The color at some position along evenly spaced colors, clamped to the ends.
+// (Also used by `OrbitTrap`.) No colors at all gives black.
+pub fn blend(colors: &[Color], position: f32) -> Color {
+    let Some(last) = colors.len().checked_sub(1) else {
+        return Color::from_rgb_u8(0, 0, 0);
+    };
+
+    let position = position.clamp(0.0, 1.0) * last as f32;
+    let index = (position.floor() as usize).min(last.saturating_sub(1));
+    let next = (index + 1).min(last);
+    lerp(position - index as f32, &colors[index], &colors[next])
+}
+
+#[typetag::serde]
+impl Texture for Gradient {
+    fn color(&self, intersection: &Intersection) -> Color {
+        let position = match &self.along {
+            GradientAxis::U => intersection.uv.0,
+            GradientAxis::V => intersection.uv.1,
+            GradientAxis::Between { start, end } => {
+                let span = end - start;
+                Vec3::dot(&(&intersection.point - start), &span) / Vec3::dot(&span, &span)
+            }
+        };
+        blend(&self.colors, position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gradient_in_space() {
+        let gradient = Gradient::new(
+            vec![
+                Color::from_rgb_f32(0.0, 0.0, 0.0),
+                Color::from_rgb_f32(1.0, 1.0, 1.0),
+            ],
+            GradientAxis::Between {
+                start: Vec3::new(0.0, 0.0, 0.0),
+                end: Vec3::new(0.0, 2.0, 0.0),
+            },
+        );
+        let at = |y| {
+            let hit =
+                Intersection::new(Vec3::new(5.0, y, 0.0), Vec3::new(0.0, 1.0, 0.0), 1.0, true);
+            gradient.color(&hit)
+        };
+
+        assert_eq!(at(0.5), Color::from_rgb_f32(0.25, 0.25, 0.25));
+        // past the ends
+        assert_eq!(at(-1.0), Color::from_rgb_f32(0.0, 0.0, 0.0));
+        assert_eq!(at(3.0), Color::from_rgb_f32(1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn deserialize_axis() {
+        let yaml = "colors: [{ x: 0.0, y: 0.0, z: 0.0 }]\nalong: V";
+        let gradient: Gradient = serde_yaml::from_str(yaml).unwrap();
+        assert!(matches!(gradient.along, GradientAxis::V));
+
+        let yaml = r#"
+colors: []
+along:
+  Between:
+    start: { x: 0.0, y: 0.0, z: 0.0 }
+    end: { x: 1.0, y: 0.0, z: 0.0 }
+"#;
+        let gradient: Gradient = serde_yaml::from_str(yaml).unwrap();
+        assert!(matches!(gradient.along, GradientAxis::Between { .. }));
+    }
+}
@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock, Weak};
+
+use image::{ColorType, DynamicImage};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::math::color::Color;
+use crate::scene::object::geometry::Intersection;
+
+use super::mipmap::{Filter, MipMap, Wrap};
+use super::Texture;
+
+// An image (PNG, JPEG, HDR, or anything else the `image` crate reads) stretched over texture
+// coordinates from 0 to 1, with v = 1 at the top of the image. Lookups are filtered over the
+// pixel's footprint on the surface, from the camera ray's differentials.
+//
+// Textures using the same file share one copy of it, which is only loaded once.
+#[derive(Debug, Deserialize)]
+#[serde(try_from = "ImageTextureParts")]
+pub struct ImageTexture {
+    parts: ImageTextureParts,
+    mipmap: Arc<MipMap>,
+}
+
+// what an image texture looks like in a scene file.
+// the path is relative to the directory the renderer is run from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ImageTextureParts {
+    path: PathBuf,
+    #[serde(default = "default_wrap")]
+    wrap: Wrap,
+    #[serde(default = "default_filter")]
+    filter: Filter,
+    #[serde(default = "default_color_space")]
+    color_space: ColorSpace,
+}
+
+// How 8 and 16 bit images are stored: color maps are usually sRGB, while images of other things
+// (roughness, bumps, and so on) are usually linear. Float images (eg. HDR) are always linear.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ColorSpace {
+    Srgb,
+    Linear,
+}
+
+fn default_wrap() -> Wrap {
+    Wrap::Repeat
+}
+
+fn default_filter() -> Filter {
+    Filter::Trilinear
+}
+
+fn default_color_space() -> ColorSpace {
+    ColorSpace::Srgb
+}
+
+impl ImageTexture {
+    pub fn load(
+        path: impl Into<PathBuf>,
+        wrap: Wrap,
+        filter: Filter,
+        color_space: ColorSpace,
+    ) -> Result<ImageTexture, String> {
+        ImageTexture::try_from(ImageTextureParts {
+            path: path.into(),
+            wrap,
+            filter,
+            color_space,
+        })
+    }
+}
+
+impl TryFrom<ImageTextureParts> for ImageTexture {
+    type Error = String;
+
+    fn try_from(parts: ImageTextureParts) -> Result<Self, Self::Error> {
+        let mipmap = cached_mipmap(&parts.path, parts.wrap, parts.color_space)?;
+        Ok(ImageTexture { parts, mipmap })
+    }
+}
+
+// only the parts from the scene file get saved, not the pixels
+impl Serialize for ImageTexture {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.parts.serialize(serializer)
+    }
+}
+
+type CacheKey = (PathBuf, Wrap, ColorSpace);
+
+// Loaded images, by file and by how they were loaded. This only holds on to images while some
+// texture is using them, so they go away along with the scene.
+fn cache() -> &'static Mutex<HashMap<CacheKey, Weak<MipMap>>> {
+    static CACHE: OnceLock<Mutex<HashMap<CacheKey, Weak<MipMap>>>> = OnceLock::new();
+    CACHE.get_or_init(Default::default)
+}
+
+fn cached_mipmap(path: &Path, wrap: Wrap, color_space: ColorSpace) -> Result<Arc<MipMap>, String> {
+    let file = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let key = (file, wrap, color_space);
+
+    // (holding the lock while loading keeps two threads from loading the same file at once)
+    let mut cache = cache().lock().map_err(|err| err.to_string())?;
+    if let Some(mipmap) = cache.get(&key).and_then(Weak::upgrade) {
+        return Ok(mipmap);
+    }
+
+    let image = image::open(path)
+        .map_err(|err| format!("could not read texture image {}: {err}", path.display()))?;
+    let mipmap = Arc::new(
+        mipmap_from_image(&image, wrap, color_space)
+            .map_err(|err| format!("texture image {} {err}", path.display()))?,
+    );
+
+    cache.retain(|_, mipmap| mipmap.strong_count() > 0);
+    cache.insert(key, Arc::downgrade(&mipmap));
+    Ok(mipmap)
+}
+
+fn mipmap_from_image(
+    image: &DynamicImage,
+    wrap: Wrap,
+    color_space: ColorSpace,
+) -> Result<MipMap, String> {
+    let is_linear = match image.color() {
+        ColorType::Rgb32F | ColorType::Rgba32F => true,
+        _ => color_space == ColorSpace::Linear,
+    };
+
+    let image = image.to_rgb32f();
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    if width == 0 || height == 0 {
+        return Err("is empty".to_string());
+    }
+
+    let texels = image
+        .pixels()
+        .map(|pixel| {
+            pixel.0.map(|c| {
+                let c = c.max(0.0);
+                if is_linear {
+                    c
+                } else {
+                    srgb_to_linear(c)
+                }
+            })
+        })
+        .collect();
+    Ok(MipMap::new(width, height, texels, wrap))
+}
+
+// the sRGB transfer function, which is linear near black and close to a gamma of 2.2 elsewhere
+fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+#[typetag::serde]
+impl Texture for ImageTexture {
+    fn color(&self, intersection: &Intersection) -> Color {
+        // rows go down the image, while v goes up it
+        let (u, v) = intersection.uv;
+        let (dudx, dvdx) = intersection.duvdx;
+        let (dudy, dvdy) = intersection.duvdy;
+        self.mipmap.lookup(
+            self.parts.filter,
+            (u, 1.0 - v),
+            (dudx, -dvdx),
+            (dudy, -dvdy),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use image::{Rgb, RgbImage};
+
+    use super::*;
+    use crate::math::vec3::Vec3;
+
+    fn hit_at_uv(u: f32, v: f32) -> Intersection {
+        Intersection {
+            uv: (u, v),
+            ..Intersection::new(
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+                1.0,
+                true,
+            )
+        }
+    }
+
+    // 2x2, with a white pixel in the top left corner
+    fn corner_image() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(2, 2, |x, y| {
+            Rgb(if (x, y) == (0, 0) { [255; 3] } else { [0; 3] })
+        }))
+    }
+
+    fn save_temp(name: &str, image: &DynamicImage) -> PathBuf {
+        let path = env::temp_dir().join(format!("rays_image_texture_{name}.png"));
+        image.save(&path).unwrap();
+        path
+    }
+
+    #[test]
+    fn look_up_by_uv() {
+        let path = save_temp("look_up_by_uv", &corner_image());
+        let texture =
+            ImageTexture::load(path, Wrap::Repeat, Filter::Nearest, ColorSpace::Srgb).unwrap();
+        let white = Color::from_rgb_f32(1.0, 1.0, 1.0);
+
+        // the top of the image is at v = 1
+        assert_eq!(texture.color(&hit_at_uv(0.25, 0.75)), white);
+        assert_eq!(
+            texture.color(&hit_at_uv(0.25, 0.25)),
+            Color::from_rgb_f32(0.0, 0.0, 0.0)
+        );
+
+        // and it repeats
+        assert_eq!(texture.color(&hit_at_uv(-0.75, 1.75)), white);
+    }
+
+    #[test]
+    fn srgb_decoding() {
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-6);
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+
+        // half gray in an 8 bit image, decoded or not
+        let gray = DynamicImage::ImageRgb8(RgbImage::from_pixel(1, 1, Rgb([128; 3])));
+        let at_center = |color_space| {
+            let mipmap = mipmap_from_image(&gray, Wrap::Clamp, color_space).unwrap();
+            let none = (0.0, 0.0);
+            mipmap.lookup(Filter::Nearest, (0.5, 0.5), none, none).r()
+        };
+        assert!((at_center(ColorSpace::Srgb) - 0.216).abs() < 1e-3);
+        assert!((at_center(ColorSpace::Linear) - 0.502).abs() < 1e-3);
+    }
+
+    #[test]
+    fn shared_files_load_once() {
+        let path = save_temp("shared_files_load_once", &corner_image());
+        let load = |color_space| {
+            ImageTexture::load(&path, Wrap::Repeat, Filter::Bilinear, color_space).unwrap()
+        };
+
+        let first = load(ColorSpace::Srgb);
+        let second = load(ColorSpace::Srgb);
+        assert!(Arc::ptr_eq(&first.mipmap, &second.mipmap));
+
+        // but decoding it differently needs another copy
+        let linear = load(ColorSpace::Linear);
+        assert!(!Arc::ptr_eq(&first.mipmap, &linear.mipmap));
+    }
+
+    #[test]
+    fn load_from_scene_file() {
+        let path = save_temp("load_from_scene_file", &corner_image());
+
+        let yaml = format!(
+            "ImageTexture:\n  path: {}\n  wrap: Clamp\n  filter: Nearest",
+            path.display()
+        );
+        let texture: Box<dyn Texture> = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(
+            texture.color(&hit_at_uv(0.1, 0.9)),
+            Color::from_rgb_f32(1.0, 1.0, 1.0)
+        );
+
+        // clamped rather than repeating
+        assert_eq!(
+            texture.color(&hit_at_uv(1.1, 0.9)),
+            Color::from_rgb_f32(0.0, 0.0, 0.0)
+        );
+
+        let missing = "ImageTexture:\n  path: nowhere.png";
+        assert!(serde_yaml::from_str::<Box<dyn Texture>>(missing).is_err());
+    }
+}
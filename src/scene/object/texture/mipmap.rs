@@ -0,0 +1,420 @@
+use serde::{Deserialize, Serialize};
+
+use crate::math::color::Color;
+
+// An image along with copies of it at half the resolution, a quarter, and so on down to a single
+// pixel. Looking textures up in a smaller copy averages over more of the image at once, which
+// keeps textures that are far away (and so squeezed into a few pixels) from flickering.
+//
+// Lookups take coordinates (s, t) from 0 to 1 across the image, with t = 0 at the top.
+#[derive(Debug)]
+pub struct MipMap {
+    levels: Vec<Level>,
+    wrap: Wrap,
+}
+
+// what happens outside of the image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Wrap {
+    Repeat,
+    Clamp,
+    Mirror,
+}
+
+// how much of the image is averaged together for each lookup
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Filter {
+    // the closest pixel, blocky up close and noisy far away
+    Nearest,
+
+    // blends the four closest pixels, smooth up close but still noisy far away
+    Bilinear,
+
+    // a bilinear lookup in the copies that are just bigger and just smaller than the pixel's
+    // footprint, blended together. cheap, but blurry when looking along a surface
+    Trilinear,
+
+    // elliptically weighted averaging: averages over an ellipse fit to the pixel's footprint,
+    // which stays sharp when looking along a surface. the best looking, and the slowest
+    Ewa,
+}
+
+#[derive(Debug)]
+struct Level {
+    width: usize,
+    height: usize,
+    texels: Vec<[f32; 3]>,
+}
+
+// ellipses longer than this relative to their width get widened, to bound the number of texels
+const MAX_ANISOTROPY: f32 = 8.0;
+
+// how quickly EWA's gaussian weights fall off towards the edge of the ellipse
+const EWA_ALPHA: f32 = 2.0;
+
+impl Level {
+    // a copy at half the size (rounding up), where each texel averages (up to) 2x2 of these
+    fn halve(&self) -> Level {
+        let width = self.width.div_ceil(2);
+        let height = self.height.div_ceil(2);
+        let mut texels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let mut sum = [0.0; 3];
+                let mut count = 0.0;
+                for (sx, sy) in [
+                    (2 * x, 2 * y),
+                    (2 * x + 1, 2 * y),
+                    (2 * x, 2 * y + 1),
+                    (2 * x + 1, 2 * y + 1),
+                ] {
+                    if sx < self.width && sy < self.height {
+                        let texel = self.texels[sy * self.width + sx];
+                        (0..3).for_each(|c| sum[c] += texel[c]);
+                        count += 1.0;
+                    }
+                }
+                texels.push(sum.map(|c| c / count));
+            }
+        }
+        Level {
+            width,
+            height,
+            texels,
+        }
+    }
+}
+
+impl MipMap {
+    // `texels` go a row at a time from the top, and there have to be `width * height` of them
+    pub fn new(width: usize, height: usize, texels: Vec<[f32; 3]>, wrap: Wrap) -> MipMap {
+        let mut levels = vec![Level {
+            width,
+            height,
+            texels,
+        }];
+        while let Some(last) = levels.last().filter(|l| l.width > 1 || l.height > 1) {
+            let next = last.halve();
+            levels.push(next);
+        }
+        MipMap { levels, wrap }
+    }
+
+    pub fn width(&self) -> usize {
+        self.levels[0].width
+    }
+
+    pub fn height(&self) -> usize {
+        self.levels[0].height
+    }
+
+    // The color at (s, t), averaged over the pixel's footprint: `dst0` and `dst1` are how far
+    // (s, t) moves from one pixel to the next, across the image and down it.
+    pub fn lookup(
+        &self,
+        filter: Filter,
+        st: (f32, f32),
+        dst0: (f32, f32),
+        dst1: (f32, f32),
+    ) -> Color {
+        let [r, g, b] = match filter {
+            Filter::Nearest => self.nearest(st),
+            Filter::Bilinear => self.bilinear(0, st),
+            Filter::Trilinear => {
+                let width = 2.0
+                    * dst0
+                        .0
+                        .abs()
+                        .max(dst0.1.abs())
+                        .max(dst1.0.abs())
+                        .max(dst1.1.abs());
+                self.trilinear(st, width)
+            }
+            Filter::Ewa => self.ewa(st, dst0, dst1),
+        };
+        Color::from_rgb_f32(r, g, b)
+    }
+
+    fn texel(&self, level: usize, x: isize, y: isize) -> [f32; 3] {
+        let level = &self.levels[level];
+        let x = wrap(x, level.width, self.wrap);
+        let y = wrap(y, level.height, self.wrap);
+        level.texels[y * level.width + x]
+    }
+
+    fn nearest(&self, (s, t): (f32, f32)) -> [f32; 3] {
+        let x = (s * self.width() as f32).floor() as isize;
+        let y = (t * self.height() as f32).floor() as isize;
+        self.texel(0, x, y)
+    }
+
+    fn bilinear(&self, level: usize, (s, t): (f32, f32)) -> [f32; 3] {
+        let Level { width, height, .. } = self.levels[level];
+
+        // texel centers are at half-integers
+        let x = s * width as f32 - 0.5;
+        let y = t * height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as isize, y0 as isize);
+
+        let mut sum = [0.0; 3];
+        for (dx, dy, weight) in [
+            (0, 0, (1.0 - fx) * (1.0 - fy)),
+            (1, 0, fx * (1.0 - fy)),
+            (0, 1, (1.0 - fx) * fy),
+            (1, 1, fx * fy),
+        ] {
+            let texel = self.texel(level, x0 + dx, y0 + dy);
+            (0..3).for_each(|c| sum[c] += weight * texel[c]);
+        }
+        sum
+    }
+
+    // which level has texels about `width` wide (in st space), as a fraction between two levels
+    fn level_for_width(&self, width: f32) -> f32 {
+        let size = self.width().max(self.height()) as f32;
+        (width * size).max(1e-8).log2()
+    }
+
+    fn trilinear(&self, st: (f32, f32), width: f32) -> [f32; 3] {
+        let level = self.level_for_width(width);
+        let last = self.levels.len() - 1;
+        if level <= 0.0 {
+            return self.bilinear(0, st);
+        }
+        if level >= last as f32 {
+            return self.texel(last, 0, 0);
+        }
+
+        let below = level.floor() as usize;
+        let blend = level - below as f32;
+        lerp_texels(
+            blend,
+            self.bilinear(below, st),
+            self.bilinear(below + 1, st),
+        )
+    }
+
+    // This follows pbrt's implementation: pick a level where the ellipse's minor axis is a texel
+    // or so wide, and filter there (and in the next level up, to blend between them).
+    fn ewa(&self, st: (f32, f32), dst0: (f32, f32), dst1: (f32, f32)) -> [f32; 3] {
+        let length = |(s, t): (f32, f32)| s.hypot(t);
+        let (major, mut minor) = if length(dst0) < length(dst1) {
+            (dst1, dst0)
+        } else {
+            (dst0, dst1)
+        };
+        let (major_length, mut minor_length) = (length(major), length(minor));
+
+        // very thin ellipses would cover a lot of texels at the level picked for their width
+        if minor_length * MAX_ANISOTROPY < major_length && minor_length > 0.0 {
+            let scale = major_length / (minor_length * MAX_ANISOTROPY);
+            minor = (minor.0 * scale, minor.1 * scale);
+            minor_length *= scale;
+        }
+        if minor_length == 0.0 {
+            return self.bilinear(0, st);
+        }
+
+        // (an infinite footprint covers the whole texture, which is the coarsest level)
+        let coarsest = (self.levels.len() - 1) as f32;
+        let level = self.level_for_width(minor_length).clamp(0.0, coarsest);
+        let below = level.floor() as usize;
+        let blend = level - below as f32;
+        lerp_texels(
+            blend,
+            self.ewa_at_level(below, st, major, minor),
+            self.ewa_at_level(below + 1, st, major, minor),
+        )
+    }
+
+    fn ewa_at_level(
+        &self,
+        level: usize,
+        st: (f32, f32),
+        dst0: (f32, f32),
+        dst1: (f32, f32),
+    ) -> [f32; 3] {
+        if level >= self.levels.len() {
+            return self.texel(self.levels.len() - 1, 0, 0);
+        }
+
+        // work in this level's texel space
+        let Level { width, height, .. } = self.levels[level];
+        let (w, h) = (width as f32, height as f32);
+        let (s, t) = (st.0 * w - 0.5, st.1 * h - 0.5);
+        let dst0 = (dst0.0 * w, dst0.1 * h);
+        let dst1 = (dst1.0 * w, dst1.1 * h);
+
+        // the ellipse is where a x^2 + b x y + c y^2 < 1, relative to (s, t)
+        // (widened by a texel each way, so that it always covers at least one)
+        let mut a = dst0.1 * dst0.1 + dst1.1 * dst1.1 + 1.0;
+        let mut b = -2.0 * (dst0.0 * dst0.1 + dst1.0 * dst1.1);
+        let mut c = dst0.0 * dst0.0 + dst1.0 * dst1.0 + 1.0;
+        let scale = 1.0 / (a * c - b * b * 0.25);
+        a *= scale;
+        b *= scale;
+        c *= scale;
+
+        // its bounding box
+        let determinant = 4.0 * a * c - b * b;
+        let s_extent = 2.0 * (determinant * c).sqrt() / determinant;
+        let t_extent = 2.0 * (determinant * a).sqrt() / determinant;
+        let (s0, s1) = (
+            (s - s_extent).ceil() as isize,
+            (s + s_extent).floor() as isize,
+        );
+        let (t0, t1) = (
+            (t - t_extent).ceil() as isize,
+            (t + t_extent).floor() as isize,
+        );
+
+        let mut sum = [0.0; 3];
+        let mut total_weight = 0.0;
+        for y in t0..=t1 {
+            let dy = y as f32 - t;
+            for x in s0..=s1 {
+                let dx = x as f32 - s;
+                let r_squared = a * dx * dx + b * dx * dy + c * dy * dy;
+                if r_squared < 1.0 {
+                    let weight = (-EWA_ALPHA * r_squared).exp() - (-EWA_ALPHA).exp();
+                    let texel = self.texel(level, x, y);
+                    (0..3).for_each(|c| sum[c] += weight * texel[c]);
+                    total_weight += weight;
+                }
+            }
+        }
+
+        // (eg. for derivatives that aren't finite)
+        if total_weight <= 0.0 {
+            return self.bilinear(level, st);
+        }
+        sum.map(|c| c / total_weight)
+    }
+}
+
+fn wrap(i: isize, size: usize, wrap: Wrap) -> usize {
+    let n = size as isize;
+    match wrap {
+        Wrap::Repeat => i.rem_euclid(n) as usize,
+        Wrap::Clamp => i.clamp(0, n - 1) as usize,
+        Wrap::Mirror => {
+            let i = i.rem_euclid(2 * n);
+            (if i < n { i } else { 2 * n - 1 - i }) as usize
+        }
+    }
+}
+
+fn lerp_texels(t: f32, start: [f32; 3], end: [f32; 3]) -> [f32; 3] {
+    [0, 1, 2].map(|c| (1.0 - t) * start[c] + t * end[c])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // black and white stripes, a texel wide, going across
+    fn stripes(size: usize, wrap: Wrap) -> MipMap {
+        let texels = (0..size * size)
+            .map(|i| if i % 2 == 0 { [1.0; 3] } else { [0.0; 3] })
+            .collect();
+        MipMap::new(size, size, texels, wrap)
+    }
+
+    fn assert_gray(color: Color, value: f32) {
+        assert!(
+            (color.r() - value).abs() < 1e-3,
+            "{color:?} isn't close to {value}"
+        );
+    }
+
+    #[test]
+    fn levels_halve_down_to_one_texel() {
+        let mipmap = MipMap::new(5, 3, vec![[0.5; 3]; 15], Wrap::Repeat);
+        let sizes: Vec<(usize, usize)> =
+            mipmap.levels.iter().map(|l| (l.width, l.height)).collect();
+        assert_eq!(sizes, vec![(5, 3), (3, 2), (2, 1), (1, 1)]);
+        assert_eq!(mipmap.levels[3].texels[0], [0.5; 3]);
+    }
+
+    #[test]
+    fn wrap_modes() {
+        assert_eq!(wrap(-1, 4, Wrap::Repeat), 3);
+        assert_eq!(wrap(5, 4, Wrap::Clamp), 3);
+        assert_eq!(wrap(-1, 4, Wrap::Clamp), 0);
+        assert_eq!(wrap(4, 4, Wrap::Mirror), 3);
+        assert_eq!(wrap(-1, 4, Wrap::Mirror), 0);
+        assert_eq!(wrap(9, 4, Wrap::Mirror), 1);
+    }
+
+    #[test]
+    fn bilinear_blends_neighbors() {
+        let mipmap = stripes(4, Wrap::Repeat);
+        let none = (0.0, 0.0);
+
+        // on a texel center, and halfway between two
+        assert_gray(
+            mipmap.lookup(Filter::Bilinear, (0.125, 0.5), none, none),
+            1.0,
+        );
+        assert_gray(
+            mipmap.lookup(Filter::Bilinear, (0.25, 0.5), none, none),
+            0.5,
+        );
+        assert_gray(mipmap.lookup(Filter::Nearest, (0.3, 0.5), none, none), 0.0);
+    }
+
+    #[test]
+    fn wide_footprints_average_stripes_away() {
+        let mipmap = stripes(64, Wrap::Repeat);
+
+        // a footprint of 8 texels
+        let step = 8.0 / 64.0;
+        for filter in [Filter::Trilinear, Filter::Ewa] {
+            let color = mipmap.lookup(filter, (0.3, 0.6), (step, 0.0), (0.0, step));
+            assert_gray(color, 0.5);
+        }
+
+        // and no footprint at all is just bilinear
+        let color = mipmap.lookup(Filter::Ewa, (0.5 / 64.0, 0.5), (0.0, 0.0), (0.0, 0.0));
+        assert_gray(color, 1.0);
+    }
+
+    #[test]
+    fn ewa_falls_back_to_bilinear() {
+        // a footprint that no texel falls in, which is looked up where it is without filtering
+        let mipmap = stripes(4, Wrap::Repeat);
+        let nan = (f32::NAN, 0.0);
+        for (s, gray) in [(0.125, 1.0), (0.375, 0.0), (0.25, 0.5)] {
+            let [r, g, b] = mipmap.ewa_at_level(0, (s, 0.5), nan, nan);
+            assert_gray(Color::from_rgb_f32(r, g, b), gray);
+        }
+
+        // and one that covers everything is the texture's average
+        let (across, down) = ((f32::INFINITY, 0.0), (0.0, f32::INFINITY));
+        assert_gray(mipmap.lookup(Filter::Ewa, (0.5, 0.5), across, down), 0.5);
+    }
+
+    #[test]
+    fn ewa_stays_sharp_along_stripes() {
+        // a footprint that's long along the stripes (down the image), but thin across them
+        let mipmap = stripes(64, Wrap::Repeat);
+        let color = mipmap.lookup(
+            Filter::Ewa,
+            (0.5 / 64.0, 0.5),
+            (0.1 / 64.0, 0.0),
+            (0.0, 0.03),
+        );
+        assert!(color.r() > 0.8, "{color:?}");
+
+        // which trilinear filtering blurs
+        let color = mipmap.lookup(
+            Filter::Trilinear,
+            (0.5 / 64.0, 0.5),
+            (0.1 / 64.0, 0.0),
+            (0.0, 0.03),
+        );
+        assert!(color.r() < 0.6, "{color:?}");
+    }
+}
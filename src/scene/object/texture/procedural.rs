@@ -0,0 +1,224 @@
+use std::f32::consts::PI;
+
+use serde::{Deserialize, Serialize};
+
+use crate::math::color::Color;
+use crate::math::noise::{fbm, turbulence, worley};
+use crate::math::vec3::Vec3;
+use crate::scene::object::geometry::Intersection;
+
+use super::gradient::blend;
+use super::Texture;
+
+// Patterns made from noise, by position in space (like `SolidCheckerboard`), so they carry on
+// through objects the way the real materials do. Each one picks its color from a list of colors
+// spread evenly from 0 to 1, like `Gradient`. A bigger `scale` makes the pattern finer, and
+// changing the `seed` gives a different pattern with the same look.
+
+// Bands along x, pushed around by turbulence into veins.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Marble {
+    colors: Vec<Color>,
+    #[serde(default = "default_scale")]
+    scale: f32,
+    #[serde(default)]
+    seed: u32,
+
+    // how far the veins wander from straight bands
+    #[serde(default = "default_marble_turbulence")]
+    turbulence: f32,
+    #[serde(default = "default_octaves")]
+    octaves: u32,
+}
+
+// Rings around the y axis, like a log standing up, made a bit irregular with noise.
+// `scale` is the number of rings per unit.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Wood {
+    colors: Vec<Color>,
+    #[serde(default = "default_wood_scale")]
+    scale: f32,
+    #[serde(default)]
+    seed: u32,
+
+    // how much the rings wobble, in rings
+    #[serde(default = "default_grain")]
+    grain: f32,
+}
+
+// Crystals of different colors packed together: cells of Worley noise, mottled with fbm.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Granite {
+    colors: Vec<Color>,
+    #[serde(default = "default_granite_scale")]
+    scale: f32,
+    #[serde(default)]
+    seed: u32,
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+fn default_wood_scale() -> f32 {
+    4.0
+}
+
+fn default_granite_scale() -> f32 {
+    10.0
+}
+
+fn default_marble_turbulence() -> f32 {
+    4.0
+}
+
+fn default_grain() -> f32 {
+    0.3
+}
+
+fn default_octaves() -> u32 {
+    6
+}
+
+impl Marble {
+    pub fn new(colors: Vec<Color>, scale: f32, seed: u32) -> Marble {
+        Marble {
+            colors,
+            scale,
+            seed,
+            turbulence: default_marble_turbulence(),
+            octaves: default_octaves(),
+        }
+    }
+}
+
+impl Wood {
+    pub fn new(colors: Vec<Color>, scale: f32, seed: u32) -> Wood {
+        Wood {
+            colors,
+            scale,
+            seed,
+            grain: default_grain(),
+        }
+    }
+}
+
+impl Granite {
+    pub fn new(colors: Vec<Color>, scale: f32, seed: u32) -> Granite {
+        Granite {
+            colors,
+            scale,
+            seed,
+        }
+    }
+}
+
+#[typetag::serde]
+impl Texture for Marble {
+    fn color(&self, intersection: &Intersection) -> Color {
+        let p = self.scale * &intersection.point;
+        let phase = p.x + self.turbulence * turbulence(&p, self.seed, self.octaves);
+        blend(&self.colors, 0.5 + 0.5 * (PI * phase).sin())
+    }
+}
+
+#[typetag::serde]
+impl Texture for Wood {
+    fn color(&self, intersection: &Intersection) -> Color {
+        let p = &intersection.point;
+
+        // the noise is stretched along the trunk, so that the wobbles run along the grain
+        let noise_point = Vec3::new(p.x * self.scale, p.y * self.scale / 4.0, p.z * self.scale);
+        let rings = self.scale * p.x.hypot(p.z) + self.grain * fbm(&noise_point, self.seed, 4);
+
+        // each ring fades from the first color into the last, then starts over
+        blend(&self.colors, rings.rem_euclid(1.0))
+    }
+}
+
+#[typetag::serde]
+impl Texture for Granite {
+    fn color(&self, intersection: &Intersection) -> Color {
+        let p = self.scale * &intersection.point;
+
+        // (the cells' edges are the dark grain between crystals)
+        let (closest, second) = worley(&p, self.seed);
+        let mottling = fbm(&(4.0 * &p), self.seed.wrapping_add(1), 4);
+        blend(&self.colors, (second - closest) + 0.25 * mottling)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn black_to_white() -> Vec<Color> {
+        vec![
+            Color::from_rgb_f32(0.0, 0.0, 0.0),
+            Color::from_rgb_f32(1.0, 1.0, 1.0),
+        ]
+    }
+
+    fn at(texture: &dyn Texture, x: f32, y: f32, z: f32) -> Color {
+        let hit = Intersection::new(Vec3::new(x, y, z), Vec3::new(0.0, 1.0, 0.0), 1.0, true);
+        texture.color(&hit)
+    }
+
+    #[test]
+    fn patterns_vary_with_the_seed() {
+        let textures: [(Box<dyn Texture>, Box<dyn Texture>); 3] = [
+            (
+                Box::new(Marble::new(black_to_white(), 1.0, 0)),
+                Box::new(Marble::new(black_to_white(), 1.0, 1)),
+            ),
+            (
+                Box::new(Wood::new(black_to_white(), 4.0, 0)),
+                Box::new(Wood::new(black_to_white(), 4.0, 1)),
+            ),
+            (
+                Box::new(Granite::new(black_to_white(), 10.0, 0)),
+                Box::new(Granite::new(black_to_white(), 10.0, 1)),
+            ),
+        ];
+
+        for (texture, reseeded) in &textures {
+            let first = at(texture.as_ref(), 0.3, 0.2, 0.7);
+            assert_eq!(first, at(texture.as_ref(), 0.3, 0.2, 0.7));
+            assert_ne!(first, at(reseeded.as_ref(), 0.3, 0.2, 0.7));
+
+            // and the colors come from between the given ones
+            assert!((0.0..=1.0).contains(&first.r()));
+        }
+    }
+
+    #[test]
+    fn wood_rings_are_round_without_grain() {
+        let wood = Wood {
+            grain: 0.0,
+            ..Wood::new(black_to_white(), 1.0, 0)
+        };
+
+        // the same distance from the y axis, and partway through a ring
+        let color = at(&wood, 0.25, 3.0, 0.0);
+        assert_eq!(color, at(&wood, 0.0, -1.0, 0.25));
+        assert!((color.r() - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn deserialize_with_defaults() {
+        let yaml = "Marble:\n  colors: [{ x: 1.0, y: 1.0, z: 1.0 }]";
+        let texture: Box<dyn Texture> = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(
+            at(texture.as_ref(), 0.1, 0.2, 0.3),
+            Color::from_rgb_f32(1.0, 1.0, 1.0)
+        );
+
+        let yaml = "colors: []\nscale: 3.0\nseed: 17";
+        let granite: Granite = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!((granite.scale, granite.seed), (3.0, 17));
+
+        let yaml = "colors: []\nseed: 2";
+        let wood: Wood = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!((wood.scale, wood.seed, wood.grain), (4.0, 2, 0.3));
+    }
+}
@@ -0,0 +1,96 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use super::object::{geometry::IntersectRay, material::ScatterRay, Geometry, Material};
+
+// Materials and geometries that are stored once and shared between objects. Adding something
+// gives back a handle to it, which can be used in as many objects as needed. In a scene file,
+// objects refer to these by name, eg. `material: glass`.
+#[derive(Default)]
+pub struct Registry {
+    materials: BTreeMap<String, Material>,
+    geometries: BTreeMap<String, Geometry>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    // replaces any material already registered under the same name
+    pub fn add_material(&mut self, name: &str, material: impl ScatterRay + 'static) -> Material {
+        let handle: Material = Arc::new(material);
+        self.insert_material(name, handle.clone());
+        handle
+    }
+
+    // replaces any geometry already registered under the same name
+    pub fn add_geometry(&mut self, name: &str, geometry: impl IntersectRay + 'static) -> Geometry {
+        let handle: Geometry = Arc::new(geometry);
+        self.insert_geometry(name, handle.clone());
+        handle
+    }
+
+    pub fn insert_material(&mut self, name: &str, material: Material) {
+        self.materials.insert(name.to_string(), material);
+    }
+
+    pub fn insert_geometry(&mut self, name: &str, geometry: Geometry) {
+        self.geometries.insert(name.to_string(), geometry);
+    }
+
+    pub fn material(&self, name: &str) -> Option<Material> {
+        self.materials.get(name).cloned()
+    }
+
+    pub fn geometry(&self, name: &str) -> Option<Geometry> {
+        self.geometries.get(name).cloned()
+    }
+
+    // the name a material was registered under, if it was
+    pub fn material_name(&self, material: &Material) -> Option<&str> {
+        self.materials
+            .iter()
+            .find(|(_, registered)| Arc::ptr_eq(registered, material))
+            .map(|(name, _)| name.as_str())
+    }
+
+    // the name a geometry was registered under, if it was
+    pub fn geometry_name(&self, geometry: &Geometry) -> Option<&str> {
+        self.geometries
+            .iter()
+            .find(|(_, registered)| Arc::ptr_eq(registered, geometry))
+            .map(|(name, _)| name.as_str())
+    }
+
+    pub fn materials(&self) -> &BTreeMap<String, Material> {
+        &self.materials
+    }
+
+    pub fn geometries(&self) -> &BTreeMap<String, Geometry> {
+        &self.geometries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::{color::Color, vec3::Vec3};
+    use crate::scene::object::{geometry::sphere::Sphere, material::lambertian::Lambertian};
+
+    #[test]
+    fn share_and_look_up_by_name() {
+        let mut registry = Registry::new();
+        let gray =
+            registry.add_material("gray", Lambertian::new(Color::from_rgb_u8(128, 128, 128)));
+        let ball = registry.add_geometry("ball", Sphere::new(1.0, Vec3::new(0.0, 0.0, 0.0)));
+
+        assert!(Arc::ptr_eq(&registry.material("gray").unwrap(), &gray));
+        assert!(registry.material("glass").is_none());
+        assert_eq!(registry.material_name(&gray), Some("gray"));
+        assert_eq!(registry.geometry_name(&ball), Some("ball"));
+
+        // an identical but separately created material isn't the registered one
+        let other: Material = Arc::new(Lambertian::new(Color::from_rgb_u8(128, 128, 128)));
+        assert_eq!(registry.material_name(&other), None);
+    }
+}
@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 
 use crate::math::{color::Color, shaping::lerp, vec3::Vec3};
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Sky {
     nadir: Color,
     zenith: Color,